@@ -0,0 +1,127 @@
+//! Store size and load times for synthetic vaults.
+//!
+//!     cargo run --release --example bench -- [--keep DIR] [ENTRIES]...
+//!
+//! Builds a vault of ENTRIES (default 5000) entries with notes, URLs, tags,
+//! fields and password history, saves it unencrypted (plain JSON) and
+//! encrypted (Argon2 at m=8192 t=1), and times opening each in full, the
+//! index reads behind `list` and `get`, and adding an entry. The same count
+//! always builds the same entries. With `--keep`, the stores stay in DIR as
+//! plain-N.dat and encrypted-N.dat, for timing the CLI on them, e.g.
+//! `cargo run --release --example bench -- --keep /tmp/stores 1000 10000 50000`.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use revaultpass::{Entry, HistoricalPassword, KdfParams, Secret, StoreKey, Vault};
+use zeroize::Zeroizing;
+
+/// A fixed xorshift generator, so every run builds the same vault.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn word(&mut self, len: usize) -> String {
+        const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!@#";
+        (0..len).map(|_| CHARS[self.below(CHARS.len() as u64) as usize] as char).collect()
+    }
+}
+
+fn synthetic_entry(rng: &mut Rng, n: u64) -> Entry {
+    let folder = ["work", "home", "bank", "shop"][rng.below(4) as usize];
+    let mut entry = Entry::new(format!("{}/site-{}", folder, n), format!("user{}@example.com", n), Secret::from(rng.word(20)));
+    entry.url = Some(format!("https://s{}.example.com/login", n));
+    entry.notes = Some(format!("created for project {}; security question: {}", n, rng.word(30)));
+    entry.tags = vec![["a", "b", "c"][rng.below(3) as usize].to_string()];
+    entry.fields.insert("pin".to_string(), (1000 + rng.below(9000)).to_string());
+    entry.fields.insert("api key".to_string(), rng.word(32));
+    entry.history = (0..rng.below(5)).map(|j| HistoricalPassword { password: Secret::from(rng.word(20)), replaced_at: 1_700_000_000 + j }).collect();
+    if n.is_multiple_of(7) {
+        entry.totp = Some(Secret::from("JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP".to_string()));
+    }
+    entry.created_at = 1_600_000_000 + n;
+    entry.modified_at = 1_700_000_000 + n;
+    entry
+}
+
+const PASSPHRASE: &str = "bench passphrase";
+
+fn ms(d: Duration) -> String {
+    format!("{:.1} ms", d.as_secs_f64() * 1000.0)
+}
+
+/// Times one store of `count` entries: its size, then opening it in full,
+/// reading the index as `list` does, finding and loading one entry as `get`
+/// does, and adding an entry.
+fn bench(label: &str, path: &Path, key: &StoreKey, entries: &[Entry], rng: &mut Rng) -> Result<(), Box<dyn std::error::Error>> {
+    let mut vault = Vault::create(path, key.clone())?;
+    for entry in entries {
+        vault.add(entry.clone())?;
+    }
+    vault.save()?;
+    let size = std::fs::metadata(path)?.len();
+
+    let start = Instant::now();
+    let mut vault = Vault::open_with_key(path, key.clone())?;
+    let open = start.elapsed();
+    let start = Instant::now();
+    let listed = Vault::open_index(path, key.clone())?.list().len();
+    let list = start.elapsed();
+    let start = Instant::now();
+    let index = Vault::open_index(path, key.clone())?;
+    let name = &entries[entries.len() / 2].name;
+    let found = index.load(index.get(name).ok_or("entry missing from the index")?)?;
+    let get = start.elapsed();
+    assert!(listed == entries.len() && found.password == entries[entries.len() / 2].password);
+    let start = Instant::now();
+    vault.add(synthetic_entry(rng, entries.len() as u64))?;
+    vault.save()?;
+    let add = start.elapsed();
+    println!("  {:<10} {:>10} bytes   open {:>9}   list {:>9}   get {:>9}   add {:>9}", label, size, ms(open), ms(list), ms(get), ms(add));
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut keep = None;
+    let mut counts = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--keep" => keep = Some(PathBuf::from(args.next().ok_or("--keep needs a directory")?)),
+            n => counts.push(n.parse::<u64>().map_err(|_| format!("not a number of entries: {}", n))?),
+        }
+    }
+    if counts.is_empty() {
+        counts.push(5000);
+    }
+    let dir = keep.clone().unwrap_or_else(|| std::env::temp_dir().join(format!("revaultpass-bench-{}", std::process::id())));
+    std::fs::create_dir_all(&dir)?;
+    let kdf = KdfParams { m_cost: 8192, t_cost: 1, p_cost: 1 };
+    let encrypted = StoreKey::Passphrase(Zeroizing::new(PASSPHRASE.to_string()), kdf, None);
+
+    let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+        for &count in &counts {
+            let mut rng = Rng(0x5eed_0000_0000_0095);
+            let entries: Vec<Entry> = (0..count.max(1)).map(|n| synthetic_entry(&mut rng, n)).collect();
+            println!("{} entries, {} bytes of entry JSON", entries.len(), serde_json::to_vec(&entries)?.len());
+            bench("plain", &dir.join(format!("plain-{}.dat", count)), &StoreKey::Plain, &entries, &mut rng)?;
+            bench("encrypted", &dir.join(format!("encrypted-{}.dat", count)), &encrypted, &entries, &mut rng)?;
+        }
+        Ok(())
+    })();
+    match keep {
+        Some(dir) => println!("stores kept in {} (passphrase {:?})", dir.display(), PASSPHRASE),
+        None => std::fs::remove_dir_all(&dir)?,
+    }
+    result
+}
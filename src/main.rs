@@ -1,217 +1,7980 @@
 // RevaultPass - private password manager (user:password). Optional encryption.
-// Data in ~/.revaultpass/ ; only accessible with key when encryption is used.
+// The store is `--store <path>`, else $REVAULTPASS_STORE, else `store` in the
+// config file, else store.dat in the data directory ($XDG_DATA_HOME/revaultpass,
+// i.e. ~/.local/share/revaultpass on Linux). Only accessible with the key when
+// encryption is used.
 
-use chacha20poly1305::aead::{Aead, KeyInit, OsRng};
-use chacha20poly1305::ChaCha20Poly1305;
+use chacha20poly1305::aead::OsRng;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use rand::seq::SliceRandom;
 use rand::RngCore;
-use serde::{Deserialize, Serialize};
+use revaultpass::names::Resolved;
+use revaultpass::{
+    age, available_memory_kib, backup_store, backups_dir, decrypt_as, derive_key, encrypt_as, list_backups, lock_store, perms,
+    accepts_keyfile, check_layout, check_recovery_key, format_label, has_recovery_slot, ignore_signatures, journal_path, key_slots, parse_public_key, public_key_path, require_store, signature_path, signing_key_path, verify_signature, verify_signature_with, requires_keyfile, self_test, store_cipher, store_kind, store_meta, store_path, unix_now, write_atomic, Cipher, DataKey, Entry, KdfParams, Lock, RevaultError, Secret, SelfCheck, StoreKey,
+    StoreKind, Keyfile, KeySlotKind, RecoveryKey, Share, StoreIndex, Vault, KDF_MIN_MEM_KIB, MAGIC_AGE, MAGIC_BACKUP, MAGIC_PLAIN, SALT_LEN,
+};
+use serde::Deserialize;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{mpsc, Mutex, OnceLock};
+use zeroize::Zeroizing;
 
-const MAGIC_ENCRYPTED: &[u8; 4] = b"RVP1";
-const MAGIC_PLAIN: &[u8; 4] = b"RVP0";
-const SALT_LEN: usize = 16;
-const NONCE_LEN: usize = 12;
-const ARGON2_M_COST: u32 = 19456;
-const ARGON2_T_COST: u32 = 2;
+const KDF_TUNE_TARGET_MS: u64 = 500;
+const CLIPBOARD_TIMEOUT_SECS: u64 = 30;
+const TYPE_DELAY_SECS: u64 = 3;
+#[cfg(feature = "qr")]
+const QR_PNG_SCALE: usize = 8;
+const SHELL_IDLE_MINUTES: u64 = 5;
+const AGENT_IDLE_MINUTES: u64 = 15;
+const GEN_DEFAULT_LEN: usize = 20;
+/// Tries at a typed master key before a command gives up, the first included.
+const MASTER_KEY_ATTEMPTS: u32 = 3;
+/// Minimum strength score (0-4) for `--enforce-policy` without a value.
+const POLICY_MIN_SCORE: u8 = 3;
+/// The key file name from "Enter passphrase for key '~/.ssh/id_ed25519':", or
+/// user@host from "alice@example.org's password:".
+const ASKPASS_REGEX: &str = r"for key '(?:[^']*/)?([^'/]+)'|([^\s']+)'s password";
+const GEN_LOWER: &str = "abcdefghijklmnopqrstuvwxyz";
+const GEN_UPPER: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const GEN_DIGITS: &str = "0123456789";
+const GEN_SYMBOLS: &str = "!@#$%^&*()-_=+[]{};:,.<>/?~|";
+const GEN_AMBIGUOUS: &str = "0O1lI|";
 
-fn data_dir() -> Option<PathBuf> {
-    directories::ProjectDirs::from("com", "revaultpass", "revaultpass")
-        .map(|d| d.data_dir().to_path_buf())
+/// Set by `--quiet`: no status messages, only the command's output, warnings
+/// and errors.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// `eprintln!` for messages that aren't the command's output, so stdout holds
+/// only what was asked for and can be piped; `--quiet` drops them.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        }
+    };
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-struct Entry {
-    name: String,
-    user: String,
-    password: String,
+/// `--verbose` given this many times: 1 for `info!`, 2 for `debug!` too.
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+
+/// A diagnostic line on stderr with `-v`: which store, format and costs were
+/// used, how long unlocking took, what an importer did. Entry values only
+/// reach it as `Secret`, which prints as `****`, and passphrases are
+/// `Zeroizing<String>` or `StoreKey`, which can't be formatted at all.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if VERBOSITY.load(Ordering::Relaxed) >= 1 {
+            eprintln!("info: {}", format_args!($($arg)*))
+        }
+    };
+}
+
+/// Like `info!`, for details that need `-vv`, such as every import decision.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if VERBOSITY.load(Ordering::Relaxed) >= 2 {
+            eprintln!("debug: {}", format_args!($($arg)*))
+        }
+    };
+}
+
+/// Shell-style match of a whole name: `*` is any run of characters, `?` any one.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (p, n): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut pi, mut ni) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        match p.get(pi) {
+            Some('*') => {
+                star = Some((pi, ni));
+                pi += 1;
+            }
+            Some(&c) if c == '?' || c == n[ni] => {
+                pi += 1;
+                ni += 1;
+            }
+            _ => match star {
+                // Let the last `*` swallow one more character and retry.
+                Some((sp, sn)) => {
+                    star = Some((sp, sn + 1));
+                    pi = sp + 1;
+                    ni = sn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+fn read_passphrase(prompt: &str) -> io::Result<Zeroizing<String>> {
+    read_hidden(prompt, false)
+}
+
+/// Reads a passphrase: with pinentry under `--pinentry`, else with the askpass
+/// program, else with the config file's pinentry, else at the terminal.
+/// `new` marks a new passphrase, see `read_terminal`.
+fn read_hidden(prompt: &str, new: bool) -> io::Result<Zeroizing<String>> {
+    let pinentry = config::get().pinentry.clone();
+    match (PINENTRY.load(Ordering::Relaxed), askpass_program(), pinentry) {
+        (true, _, program) => return read_pinentry_or_prompt(&program.unwrap_or_else(|| PathBuf::from("pinentry")), prompt, new),
+        (false, Some(program), _) => return read_askpass(&program, prompt),
+        (false, None, Some(program)) => return read_pinentry_or_prompt(&program, prompt, new),
+        (false, None, None) => {}
+    }
+    read_terminal(prompt, new)
+}
+
+/// `read_pinentry`, or the terminal prompt if `program` can't be started.
+fn read_pinentry_or_prompt(program: &std::path::Path, prompt: &str, new: bool) -> io::Result<Zeroizing<String>> {
+    match read_pinentry(program, prompt) {
+        Err(Pinentry::Unavailable(e)) => {
+            eprintln!("warning: could not run pinentry program '{}': {}; asking here instead", program.display(), e);
+            read_terminal(prompt, new)
+        }
+        Err(Pinentry::Failed(e)) => Err(e),
+        Ok(pin) => Ok(pin),
+    }
 }
 
-fn read_passphrase(prompt: &str) -> io::Result<String> {
-    print!("{}", prompt);
-    io::stdout().flush()?;
-    rpassword::read_password()
+enum Pinentry {
+    /// The program didn't start or doesn't speak the protocol.
+    Unavailable(io::Error),
+    /// It ran and the answer was a cancellation or an error.
+    Failed(io::Error),
 }
 
-fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], argon2::Error> {
-    let mut key = [0u8; 32];
-    let params = argon2::Params::new(ARGON2_M_COST, ARGON2_T_COST, 1, Some(32))?;
-    let argon = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
-    argon.hash_password_into(passphrase.as_bytes(), salt, &mut key)?;
-    Ok(key)
+/// `%`, CR and LF percent-escaped, as Assuan requires in arguments.
+fn assuan_escape(text: &str) -> String {
+    text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
 }
 
-fn encrypt(plain: &[u8], passphrase: &str) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
-    let mut salt = [0u8; SALT_LEN];
-    let mut nonce = [0u8; NONCE_LEN];
-    RngCore::fill_bytes(&mut OsRng, &mut salt);
-    RngCore::fill_bytes(&mut OsRng, &mut nonce);
+fn assuan_unescape(data: &str) -> Zeroizing<Vec<u8>> {
+    let bytes = data.as_bytes();
+    let mut out = Zeroizing::new(Vec::with_capacity(bytes.len()));
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    out
+}
 
-    let key = derive_key(passphrase, &salt).map_err(|e| format!("argon2: {:?}", e))?;
-    let cipher = ChaCha20Poly1305::new_from_slice(&key).map_err(|e| format!("{:?}", e))?;
-    let ciphertext = cipher
-        .encrypt((&nonce).into(), plain)
-        .map_err(|e| format!("{:?}", e))?;
+/// Asks with a pinentry program over the Assuan protocol: the prompt goes in
+/// SETDESC, then GETPIN answers with `D <pin>` and `OK`, or `ERR` when the
+/// user cancels. The terminal is passed on with OPTION ttyname, so curses
+/// pinentries work too; graphical ones use DISPLAY or WAYLAND_DISPLAY from
+/// the environment.
+fn read_pinentry(program: &std::path::Path, prompt: &str) -> Result<Zeroizing<String>, Pinentry> {
+    use std::io::BufRead;
 
-    let mut out = Vec::with_capacity(4 + SALT_LEN + NONCE_LEN + ciphertext.len());
-    out.extend_from_slice(MAGIC_ENCRYPTED);
-    out.extend_from_slice(&salt);
-    out.extend_from_slice(&nonce);
-    out.extend_from_slice(&ciphertext);
-    Ok(out)
+    let mut child = Command::new(program)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(Pinentry::Unavailable)?;
+    let (Some(mut to), Some(from)) = (child.stdin.take(), child.stdout.take()) else {
+        return Err(Pinentry::Unavailable(io::Error::other("no pipes")));
+    };
+    let mut from = io::BufReader::new(from);
+    // Every line but the last of a response is data, status or a comment.
+    let mut response = |pin: &mut Option<Zeroizing<String>>| -> io::Result<Result<(), String>> {
+        loop {
+            let mut line = Zeroizing::new(String::new());
+            if from.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "pinentry closed the connection"));
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line == "OK" || line.starts_with("OK ") {
+                return Ok(Ok(()));
+            }
+            if let Some(err) = line.strip_prefix("ERR ") {
+                return Ok(Err(err.to_string()));
+            }
+            if let Some(data) = line.strip_prefix("D ") {
+                let bytes = assuan_unescape(data);
+                let text = String::from_utf8(bytes.to_vec()).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "pinentry returned a PIN that isn't UTF-8"))?;
+                *pin = Some(Zeroizing::new(text));
+            }
+        }
+    };
+    let mut pin = None;
+    match response(&mut pin) {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => return Err(Pinentry::Unavailable(io::Error::other(e))),
+        Err(e) => return Err(Pinentry::Unavailable(e)),
+    }
+    let mut commands = vec!["SETTITLE revaultpass".to_string(), format!("SETDESC {}", assuan_escape(prompt.trim_end()))];
+    commands.push("SETPROMPT Passphrase:".to_string());
+    #[cfg(unix)]
+    {
+        // SAFETY: ttyname returns NULL or a NUL-terminated string that stays valid until the next call.
+        let tty = unsafe { libc::ttyname(libc::STDIN_FILENO) };
+        if !tty.is_null() {
+            let tty = unsafe { std::ffi::CStr::from_ptr(tty) }.to_string_lossy().into_owned();
+            commands.push(format!("OPTION ttyname={}", tty));
+            if let Ok(term) = std::env::var("TERM") {
+                commands.push(format!("OPTION ttytype={}", term));
+            }
+        }
+    }
+    let failed = |e: io::Error| Pinentry::Failed(io::Error::new(e.kind(), format!("pinentry: {}", e)));
+    for command in &commands {
+        writeln!(to, "{}", command).map_err(failed)?;
+        // OPTION is allowed to fail on pinentries without that option.
+        match response(&mut pin).map_err(failed)? {
+            Err(e) if !command.starts_with("OPTION ") => {
+                return Err(Pinentry::Failed(io::Error::other(format!("pinentry refused {}: {}", command.split(' ').next().unwrap_or_default(), e))));
+            }
+            _ => {}
+        }
+    }
+    writeln!(to, "GETPIN").map_err(failed)?;
+    let answer = response(&mut pin).map_err(failed)?;
+    let _ = writeln!(to, "BYE");
+    drop(to);
+    let _ = child.wait();
+    match answer {
+        Ok(()) => Ok(pin.unwrap_or_default()),
+        // 83886179 is GPG_ERR_CANCELED from the pinentry source.
+        Err(e) if e.starts_with("83886179") => Err(Pinentry::Failed(io::Error::new(io::ErrorKind::Interrupted, "passphrase prompt cancelled in pinentry"))),
+        Err(e) => Err(Pinentry::Failed(io::Error::other(format!("pinentry: {}", e)))),
+    }
 }
 
-fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
-    if data.len() < 4 + SALT_LEN + NONCE_LEN + 16 {
-        return Err("file too short".into());
+/// Reads a line at the terminal without echoing it. With `mask_input` in the
+/// config file, or for a new passphrase (`new`) when the file doesn't say,
+/// every character shows as `*` so typos are noticed; otherwise nothing shows.
+fn read_terminal(prompt: &str, new: bool) -> io::Result<Zeroizing<String>> {
+    if !has_terminal() {
+        let what = prompt.trim_end().trim_end_matches(':');
+        return Err(io::Error::new(io::ErrorKind::NotConnected, format!("no terminal to ask for '{}' on", what)));
+    }
+    let quiet = NO_PROMPT_ECHO.load(Ordering::Relaxed);
+    if !quiet {
+        eprint!("{}", prompt);
+        io::stderr().flush()?;
     }
-    if &data[0..4] != MAGIC_ENCRYPTED {
-        return Err("not encrypted or wrong format".into());
+    #[cfg(unix)]
+    if !quiet && config::get().mask_input.unwrap_or(new) {
+        if let Some(line) = read_masked()? {
+            return Ok(line);
+        }
     }
-    let salt = &data[4..4 + SALT_LEN];
-    let nonce = &data[4 + SALT_LEN..4 + SALT_LEN + NONCE_LEN];
-    let ciphertext = &data[4 + SALT_LEN + NONCE_LEN..];
+    rpassword::read_password().map(Zeroizing::new)
+}
 
-    let key = derive_key(passphrase, salt).map_err(|e| format!("argon2: {:?}", e))?;
-    let cipher = ChaCha20Poly1305::new_from_slice(&key).map_err(|e| format!("{:?}", e))?;
-    let plain = cipher
-        .decrypt(nonce.into(), ciphertext)
-        .map_err(|_| "wrong passphrase or corrupted data")?;
-    Ok(plain)
+/// Whether there is a terminal to prompt on. rpassword reads from the
+/// controlling terminal even when stdin is a pipe, so on Unix that is what
+/// counts; under systemd or cron there is none.
+fn has_terminal() -> bool {
+    #[cfg(unix)]
+    return fs::OpenOptions::new().read(true).write(true).open("/dev/tty").is_ok();
+    #[cfg(not(unix))]
+    return io::stdin().is_terminal() && io::stderr().is_terminal();
 }
 
-fn store_path() -> Option<PathBuf> {
-    data_dir().map(|d| d.join("store.dat"))
+/// `$REVAULTPASS_ASKPASS`, else `askpass` from the config file: a program
+/// that asks for passphrases instead of the terminal.
+fn askpass_program() -> Option<PathBuf> {
+    std::env::var_os("REVAULTPASS_ASKPASS").filter(|p| !p.is_empty()).map(PathBuf::from).or_else(|| config::get().askpass.clone())
 }
 
-fn load_entries(path: &PathBuf, passphrase: Option<&str>) -> Result<Vec<Entry>, Box<dyn std::error::Error + Send + Sync>> {
-    let data = match fs::read(path) {
-        Ok(d) => d,
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
-        Err(e) => return Err(e.into()),
+/// Runs `program` with the prompt as its argument, like `SSH_ASKPASS`, and
+/// takes its output without one trailing newline. A non-zero exit cancels.
+/// An empty answer would mean "no encryption" at some prompts, so it only
+/// counts after the program is asked again and answers `yes`.
+fn read_askpass(program: &std::path::Path, prompt: &str) -> io::Result<Zeroizing<String>> {
+    let ask = |prompt: &str| -> io::Result<Zeroizing<String>> {
+        let output = Command::new(program)
+            .arg(prompt.trim_end())
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(|e| io::Error::new(e.kind(), format!("could not run askpass program '{}': {}", program.display(), e)))?;
+        let mut answer = Zeroizing::new(output.stdout);
+        if !output.status.success() {
+            let how = output.status.code().map(|code| format!("exited with status {}", code)).unwrap_or_else(|| "was killed by a signal".to_string());
+            return Err(io::Error::new(io::ErrorKind::Interrupted, format!("passphrase prompt cancelled ('{}' {})", program.display(), how)));
+        }
+        if answer.last() == Some(&b'\n') {
+            answer.pop();
+            if answer.last() == Some(&b'\r') {
+                answer.pop();
+            }
+        }
+        String::from_utf8(answer.to_vec())
+            .map(Zeroizing::new)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("askpass program '{}' printed something that isn't UTF-8", program.display())))
     };
-    if data.len() < 4 {
-        return Ok(Vec::new());
+    let answer = ask(prompt)?;
+    if answer.is_empty() && ask("The passphrase is empty. Type 'yes' to go on without one:")?.trim() != "yes" {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "passphrase prompt cancelled (empty passphrase not confirmed)"));
+    }
+    Ok(answer)
+}
+
+/// Reads a line from the terminal in non-canonical mode, echoing `*` per
+/// character. Backspace removes one, Ctrl+U all of them, and Ctrl+C restores
+/// the terminal before interrupting as usual. `None` when stdin isn't a
+/// terminal or the terminal can't be switched, for the plain hidden read.
+#[cfg(unix)]
+fn read_masked() -> io::Result<Option<Zeroizing<String>>> {
+    use std::io::Read;
+    use std::os::unix::io::AsRawFd;
+
+    /// Puts the terminal settings back when dropped, on errors too.
+    struct Restore(i32, libc::termios);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            // SAFETY: `self.1` is the termios tcgetattr filled in for `self.0`.
+            unsafe { libc::tcsetattr(self.0, libc::TCSANOW, &self.1) };
+        }
+    }
+
+    if !io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    let Ok(mut tty) = fs::OpenOptions::new().read(true).write(true).open("/dev/tty") else { return Ok(None) };
+    let fd = tty.as_raw_fd();
+    // SAFETY: tcgetattr and tcsetattr only read and write the termios passed in.
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+        return Ok(None);
     }
-    if &data[0..4] == MAGIC_PLAIN {
-        let s = String::from_utf8_lossy(&data[4..]);
-        let entries: Vec<Entry> = serde_json::from_str(&s).unwrap_or_default();
-        return Ok(entries);
+    let mut raw = original;
+    raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::ISIG | libc::IEXTEN);
+    raw.c_cc[libc::VMIN] = 1;
+    raw.c_cc[libc::VTIME] = 0;
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+        return Ok(None);
     }
-    if &data[0..4] == MAGIC_ENCRYPTED {
-        let pass = passphrase.ok_or("encrypted store: passphrase required (use same key you set with init)")?;
-        let plain = decrypt(&data, pass)?;
-        let entries: Vec<Entry> = serde_json::from_slice(&plain)?;
-        return Ok(entries);
+    let restore = Restore(fd, original);
+    let mut line = Zeroizing::new(Vec::new());
+    let mut byte = [0u8];
+    loop {
+        if tty.read(&mut byte)? == 0 {
+            break;
+        }
+        match byte[0] {
+            b'\r' | b'\n' | 0x04 => break,
+            0x03 => {
+                drop(restore);
+                eprintln!();
+                // SAFETY: raise only sends a signal to this process.
+                unsafe { libc::raise(libc::SIGINT) };
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+            }
+            0x7f | 0x08 => {
+                // Drop UTF-8 continuation bytes, then the character's first byte.
+                while line.last().is_some_and(|b| b & 0xc0 == 0x80) {
+                    line.pop();
+                }
+                if line.pop().is_some() {
+                    tty.write_all(b"\x08 \x08")?;
+                }
+            }
+            0x15 => {
+                let shown = line.iter().filter(|b| *b & 0xc0 != 0x80).count();
+                tty.write_all(&b"\x08 \x08".repeat(shown))?;
+                line.clear();
+            }
+            // An escape sequence, like an arrow key: skipped up to its final byte.
+            0x1b => {
+                let mut next = [0u8];
+                if tty.read(&mut next)? == 1 && matches!(next[0], b'[' | b'O') {
+                    while tty.read(&mut next)? == 1 && !(0x40..=0x7e).contains(&next[0]) {}
+                }
+            }
+            b if b < 0x20 => {}
+            b => {
+                line.push(b);
+                if b & 0xc0 != 0x80 {
+                    tty.write_all(b"*")?;
+                }
+            }
+        }
+    }
+    tty.write_all(b"\n")?;
+    drop(restore);
+    Ok(Some(Zeroizing::new(String::from_utf8_lossy(&line).into_owned())))
+}
+
+/// Asks for a new passphrase twice, showing a rough strength estimate in
+/// between. A mismatch starts over, up to `MASTER_KEY_ATTEMPTS` times in all;
+/// `None` if none matched. An empty answer is returned without the repeat.
+fn read_new_passphrase(prompt: &str, repeat: &str) -> io::Result<Option<Zeroizing<String>>> {
+    for attempt in 1..=MASTER_KEY_ATTEMPTS {
+        let new = read_hidden(prompt, true)?;
+        if new.is_empty() {
+            return Ok(Some(new));
+        }
+        let strength = revaultpass::strength::estimate(&new);
+        match strength.warning {
+            Some(warning) => eprintln!("Strength: {}: {} bits ({})", strength.label(), strength.bits, warning),
+            None => status!("Strength: {}: {} bits", strength.label(), strength.bits),
+        }
+        if read_hidden(repeat, true)? == new {
+            return Ok(Some(new));
+        }
+        if attempt < MASTER_KEY_ATTEMPTS {
+            eprintln!("They don't match; try again.");
+        }
+    }
+    Ok(None)
+}
+
+/// Set by `--json`: stdout carries only JSON, everything else goes to stderr.
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--passphrase-stdin`: take the master key from the first line of stdin.
+static MASTER_KEY_FROM_STDIN: AtomicBool = AtomicBool::new(false);
+static STDIN_MASTER_KEY: OnceLock<Zeroizing<String>> = OnceLock::new();
+
+/// Set by `unlock_key` when the master key was typed at the prompt, so
+/// `open_vault` may ask again if it is wrong.
+static MASTER_KEY_TYPED: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--pinentry`: prompts go to pinentry even without `pinentry` in the config file.
+static PINENTRY: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--keyfile`; `keyfile_path` falls back to `REVAULTPASS_KEYFILE`.
+static KEYFILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Set by `get --no-prompt-echo`: no prompt text or status messages, so a
+/// menu script sees nothing but the result.
+static NO_PROMPT_ECHO: AtomicBool = AtomicBool::new(false);
+
+/// The master key of an unlocked `shell` session.
+static SESSION_KEY: Mutex<Option<Zeroizing<String>>> = Mutex::new(None);
+
+fn session_key() -> std::sync::MutexGuard<'static, Option<Zeroizing<String>>> {
+    SESSION_KEY.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Master key lookup, in order: an unlocked `shell` session, `--passphrase-stdin`,
+/// `REVAULTPASS_PASSPHRASE`, then an interactive prompt. An empty key means
+/// "no encryption" either way.
+fn read_master_key(prompt: &str) -> io::Result<Zeroizing<String>> {
+    if let Some(key) = session_key().as_ref() {
+        return Ok(key.clone());
+    }
+    if MASTER_KEY_FROM_STDIN.load(Ordering::Relaxed) {
+        if let Some(key) = STDIN_MASTER_KEY.get() {
+            return Ok(key.clone());
+        }
+        let mut line = Zeroizing::new(String::new());
+        io::stdin().read_line(&mut line)?;
+        let len = line.trim_end_matches(['\r', '\n']).len();
+        line.truncate(len);
+        return Ok(STDIN_MASTER_KEY.get_or_init(|| line).clone());
     }
-    Ok(Vec::new())
+    if let Ok(key) = std::env::var("REVAULTPASS_PASSPHRASE") {
+        return Ok(Zeroizing::new(key));
+    }
+    read_passphrase(prompt).map_err(no_terminal_hint)
+}
+
+/// Adds the non-interactive ways to give a master key to a "no terminal" error.
+fn no_terminal_hint(e: io::Error) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::NotConnected => io::Error::new(
+            e.kind(),
+            format!("{}; give the master key with --passphrase-stdin or REVAULTPASS_PASSPHRASE, unlock an agent (REVAULTPASS_AGENT_SOCK), or ask with REVAULTPASS_ASKPASS or --pinentry", e),
+        ),
+        _ => e,
+    }
+}
+
+/// An entry name argument, or the first line of stdin for `-` (after the
+/// master key, with `--passphrase-stdin`).
+fn entry_arg(name: String) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    if name != "-" {
+        return Ok(name);
+    }
+    let line = stdin_line()?;
+    if line.is_empty() {
+        return Err("no entry name on stdin".into());
+    }
+    Ok(line.to_string())
 }
 
-fn save_entries(path: &PathBuf, entries: &[Entry], passphrase: Option<&str>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let json = serde_json::to_vec(entries)?;
-    let data = if let Some(pass) = passphrase {
-        encrypt(&json, pass)?
+/// Entry names from a file (`-` for the rest of stdin), one per line;
+/// blank lines and `#` comments are skipped.
+fn read_names(file: &std::path::Path) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let text = if file.as_os_str() == "-" {
+        if MASTER_KEY_FROM_STDIN.load(Ordering::Relaxed) {
+            read_master_key("")?;
+        }
+        let mut text = String::new();
+        io::Read::read_to_string(&mut io::stdin(), &mut text)?;
+        text
     } else {
-        let mut out = MAGIC_PLAIN.to_vec();
-        out.extend_from_slice(&json);
-        out
+        fs::read_to_string(file).map_err(|e| format!("{}: {}", file.display(), e))?
     };
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
+    Ok(text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')).map(String::from).collect())
+}
+
+/// The next line of stdin without its line ending, after the master key's
+/// line with `--passphrase-stdin`.
+fn stdin_line() -> io::Result<Zeroizing<String>> {
+    if MASTER_KEY_FROM_STDIN.load(Ordering::Relaxed) {
+        read_master_key("")?;
+    }
+    let mut line = Zeroizing::new(String::new());
+    io::stdin().read_line(&mut line)?;
+    let len = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(len);
+    Ok(line)
+}
+
+/// Where `read_master_key` takes the key from, for `--verbose`.
+fn master_key_source() -> &'static str {
+    if session_key().is_some() {
+        "the shell session"
+    } else if MASTER_KEY_FROM_STDIN.load(Ordering::Relaxed) {
+        "--passphrase-stdin"
+    } else if std::env::var_os("REVAULTPASS_PASSPHRASE").is_some() {
+        "REVAULTPASS_PASSPHRASE"
+    } else {
+        "the prompt"
+    }
+}
+
+/// Whether `read_master_key` has a key without prompting: a `shell` session,
+/// `--passphrase-stdin` or `REVAULTPASS_PASSPHRASE`. These win over the agent
+/// and the keychain.
+fn master_key_given() -> bool {
+    session_key().is_some() || MASTER_KEY_FROM_STDIN.load(Ordering::Relaxed) || std::env::var_os("REVAULTPASS_PASSPHRASE").is_some()
+}
+
+/// Asks a yes/no question; anything but `y` or `yes` is a no.
+fn confirm(prompt: &str) -> io::Result<bool> {
+    let answer = read_line(prompt)?;
+    Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
+}
+
+fn read_line(prompt: &str) -> io::Result<String> {
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Overwrites a file with zeros before unlinking it, so plaintext doesn't
+/// linger on disk; best effort, errors are ignored.
+fn shred(path: &std::path::Path) {
+    if let Ok(meta) = fs::metadata(path) {
+        if let Ok(mut file) = fs::OpenOptions::new().write(true).open(path) {
+            let _ = file.write_all(&vec![0u8; meta.len() as usize]).and_then(|()| file.sync_all());
+        }
+    }
+    let _ = fs::remove_file(path);
+}
+
+/// Body for a secure note: all of stdin when it is piped in, otherwise what
+/// `$EDITOR` (default `vi`) leaves in a private temp file seeded with `current`.
+fn read_note_body(current: &str) -> Result<Secret, Box<dyn std::error::Error + Send + Sync>> {
+    use std::io::Read;
+    if !io::stdin().is_terminal() {
+        let mut body = Zeroizing::new(String::new());
+        io::stdin().read_to_string(&mut body)?;
+        return Ok(body.into());
+    }
+    let mut suffix = [0u8; 8];
+    RngCore::fill_bytes(&mut OsRng, &mut suffix);
+    let suffix: String = suffix.iter().map(|b| format!("{:02x}", b)).collect();
+    let tmp = std::env::temp_dir().join(format!("revaultpass-note-{}.txt", suffix));
+    perms::private_open_options().write(true).create_new(true).open(&tmp)?.write_all(current.as_bytes())?;
+    let edited = run_editor(&tmp);
+    let body = fs::read_to_string(&tmp).map(Zeroizing::new);
+    shred(&tmp);
+    edited?;
+    Ok(body?.into())
+}
+
+/// Opens `file` in `$EDITOR` (default `vi`) and waits for it; an editor that
+/// fails means nothing is saved.
+fn run_editor(file: &std::path::Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let editor = std::env::var("EDITOR").ok().filter(|e| !e.trim().is_empty()).unwrap_or_else(|| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program).args(words).arg(file).status();
+    let status = status.map_err(|e| format!("could not run editor '{}': {}", program, e))?;
+    if !status.success() {
+        return Err(format!("editor '{}' exited with {}; nothing saved", program, status).into());
     }
-    fs::write(path, data)?;
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let path = store_path().ok_or("could not determine data directory")?;
+/// Prints how strong a typed or given password is and, with `min_score`,
+/// refuses one that scores lower.
+fn check_strength(password: &str, min_score: Option<u8>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let strength = revaultpass::strength::estimate(password);
+    match strength.warning {
+        Some(warning) => eprintln!("Strength: {}/4 ({}): {}", strength.score, strength.label(), warning),
+        None => status!("Strength: {}/4 ({})", strength.score, strength.label()),
+    }
+    if let Some(min) = min_score.filter(|&min| strength.score < min) {
+        return Err(format!("password scores {}/4, the policy needs at least {}/4; nothing saved", strength.score, min).into());
+    }
+    Ok(())
+}
 
-    let args: Vec<String> = std::env::args().collect();
-    let cmd = args.get(1).map(|s| s.as_str()).unwrap_or("help");
+struct GenOptions {
+    length: usize,
+    uppercase: bool,
+    digits: bool,
+    symbols: bool,
+    exclude_ambiguous: bool,
+}
 
-    match cmd {
-        "init" => {
-            println!("RevaultPass init. Encryption is recommended.");
-            let pass = read_passphrase("Set master key (or leave empty for no encryption): ")?;
-            let entries: Vec<Entry> = Vec::new();
-            save_entries(&path, &entries, if pass.is_empty() { None } else { Some(&pass) })?;
-            if pass.is_empty() {
-                println!("Store created (unencrypted). Use 'revaultpass init' again to set a key.");
-            } else {
-                println!("Store created. Your data is encrypted with your key.");
-            }
+/// The `[generate]` settings from the config file, else 20 characters of
+/// every class.
+impl Default for GenOptions {
+    fn default() -> Self {
+        let config = config::get();
+        GenOptions {
+            length: config.generate_length.unwrap_or(GEN_DEFAULT_LEN),
+            uppercase: config.generate_uppercase.unwrap_or(true),
+            digits: config.generate_digits.unwrap_or(true),
+            symbols: config.generate_symbols.unwrap_or(true),
+            exclude_ambiguous: config.generate_exclude_ambiguous.unwrap_or(false),
         }
-        "add" => {
-            let name = args.get(2).cloned().unwrap_or_else(|| "".into());
-            let user = args.get(3).cloned().unwrap_or_else(|| "".into());
-            let pass_entry = args.get(4).cloned();
-            if name.is_empty() {
-                println!("usage: revaultpass add <name> <user> [password]");
-                return Ok(());
-            }
-            let password = pass_entry.unwrap_or_else(|| read_passphrase("Password: ").unwrap_or_default());
-            let passphrase = read_passphrase("Master key (or Enter for no encryption): ")?;
-            let use_key = !passphrase.is_empty();
-            let mut entries = load_entries(&path, if use_key { Some(&passphrase) } else { None })?;
-            if entries.iter().any(|e| e.name == name) {
-                println!("Name already exists. Use a different name or delete first.");
-                return Ok(());
+    }
+}
+
+impl GenOptions {
+    /// The defaults with the command line's changes: flags can only turn a
+    /// class off, or ambiguous characters out.
+    fn from_flags(length: Option<usize>, no_uppercase: bool, no_digits: bool, no_symbols: bool, exclude_ambiguous: bool) -> Self {
+        let defaults = GenOptions::default();
+        GenOptions {
+            length: length.unwrap_or(defaults.length),
+            uppercase: defaults.uppercase && !no_uppercase,
+            digits: defaults.digits && !no_digits,
+            symbols: defaults.symbols && !no_symbols,
+            exclude_ambiguous: defaults.exclude_ambiguous || exclude_ambiguous,
+        }
+    }
+}
+
+/// `--timeout`, else `clipboard_timeout` from the config file, else 30.
+fn clipboard_timeout(flag: Option<u64>) -> u64 {
+    flag.or(config::get().clipboard_timeout).unwrap_or(CLIPBOARD_TIMEOUT_SECS)
+}
+
+/// Random password with at least one character from every enabled class.
+fn generate_password(opts: &GenOptions) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let mut classes = vec![GEN_LOWER];
+    if opts.uppercase {
+        classes.push(GEN_UPPER);
+    }
+    if opts.digits {
+        classes.push(GEN_DIGITS);
+    }
+    if opts.symbols {
+        classes.push(GEN_SYMBOLS);
+    }
+    let classes: Vec<Vec<char>> = classes
+        .iter()
+        .map(|c| c.chars().filter(|ch| !(opts.exclude_ambiguous && GEN_AMBIGUOUS.contains(*ch))).collect())
+        .collect();
+    if opts.length < classes.len() {
+        return Err(format!("length must be at least {} for the selected character classes", classes.len()).into());
+    }
+    let all: Vec<char> = classes.concat();
+    let mut rng = OsRng;
+    let mut out: Vec<char> = classes.iter().map(|c| *c.choose(&mut rng).unwrap()).collect();
+    while out.len() < opts.length {
+        out.push(*all.choose(&mut rng).unwrap());
+    }
+    out.shuffle(&mut rng);
+    Ok(out.into_iter().collect())
+}
+
+fn in_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+/// External programs used to write and read the clipboard on this platform.
+struct ClipboardTool {
+    copy: &'static [&'static str],
+    paste: &'static [&'static str],
+}
+
+fn clipboard_tool() -> Option<ClipboardTool> {
+    if cfg!(target_os = "macos") {
+        return Some(ClipboardTool { copy: &["pbcopy"], paste: &["pbpaste"] });
+    }
+    if cfg!(windows) {
+        return Some(ClipboardTool { copy: &["clip"], paste: &["powershell", "-NoProfile", "-Command", "Get-Clipboard"] });
+    }
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() && in_path("wl-copy") {
+        return Some(ClipboardTool { copy: &["wl-copy"], paste: &["wl-paste", "-n"] });
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        if in_path("xclip") {
+            return Some(ClipboardTool {
+                copy: &["xclip", "-selection", "clipboard"],
+                paste: &["xclip", "-selection", "clipboard", "-o"],
+            });
+        }
+        if in_path("xsel") {
+            return Some(ClipboardTool { copy: &["xsel", "--clipboard", "--input"], paste: &["xsel", "--clipboard", "--output"] });
+        }
+    }
+    None
+}
+
+fn copy_to_clipboard(text: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let cmd = clipboard_tool().ok_or("no clipboard available")?.copy;
+    let mut child = Command::new(cmd[0])
+        .args(&cmd[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    child.stdin.take().ok_or("clipboard: no stdin")?.write_all(text.as_bytes())?;
+    if !child.wait()?.success() {
+        return Err(format!("clipboard command '{}' failed", cmd[0]).into());
+    }
+    Ok(())
+}
+
+/// What the clipboard holds, most likely a password we put there, so both
+/// the tool's output and the text are wiped when dropped.
+fn read_clipboard() -> Result<Zeroizing<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let cmd = clipboard_tool().ok_or("no clipboard available")?.paste;
+    let out = Zeroizing::new(Command::new(cmd[0]).args(&cmd[1..]).stderr(Stdio::null()).output()?.stdout);
+    Ok(Zeroizing::new(String::from_utf8_lossy(&out).into_owned()))
+}
+
+/// Set by `get --osc52`: copy through the terminal even if a clipboard tool is there.
+static OSC52: AtomicBool = AtomicBool::new(false);
+
+/// Longest base64 payload sent in one OSC 52 sequence; many terminals drop
+/// anything longer.
+const OSC52_MAX_LEN: usize = 74_994;
+
+/// Copy through the terminal with OSC 52: asked for with `--osc52`, or over
+/// SSH (`SSH_TTY` is set) when there's no clipboard tool to run.
+fn use_osc52() -> bool {
+    OSC52.load(Ordering::Relaxed) || (std::env::var_os("SSH_TTY").is_some() && clipboard_tool().is_none())
+}
+
+/// Sets the clipboard of the terminal revaultpass runs in, which over SSH is
+/// the local one, by writing an OSC 52 sequence to the controlling terminal.
+/// Refused when stdout isn't a terminal, so the sequence never lands in a
+/// pipe or file.
+fn copy_osc52(text: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !io::stdout().is_terminal() {
+        return Err("OSC 52 copies through the terminal, and stdout isn't one".into());
+    }
+    let payload = Zeroizing::new(base64_encode(text.as_bytes()));
+    if payload.len() > OSC52_MAX_LEN {
+        return Err(format!("too long to copy with OSC 52 ({} bytes encoded, terminals take {})", payload.len(), OSC52_MAX_LEN).into());
+    }
+    let sequence = Zeroizing::new(format!("\x1b]52;c;{}\x07", payload.as_str()));
+    #[cfg(unix)]
+    let mut tty = fs::OpenOptions::new().write(true).open("/dev/tty")?;
+    #[cfg(not(unix))]
+    let mut tty = io::stdout();
+    tty.write_all(sequence.as_bytes())?;
+    tty.flush()?;
+    Ok(())
+}
+
+/// Copies `text` and leaves a background `_clear-clipboard` process behind that
+/// empties the clipboard after `timeout` seconds (0 disables clearing).
+fn copy_with_clear(text: &str, timeout: u64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let osc52 = use_osc52();
+    match osc52 {
+        true => copy_osc52(text)?,
+        false => copy_to_clipboard(text)?,
+    }
+    if timeout == 0 {
+        return Ok(());
+    }
+    let mut cmd = Command::new(std::env::current_exe()?);
+    cmd.args(["_clear-clipboard", &timeout.to_string()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if osc52 {
+        cmd.arg("--osc52");
+    }
+    // Own process group, so closing the terminal right after doesn't take it down.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+    let mut child = cmd.spawn()?;
+    // The value goes over stdin so it never shows up in the process list.
+    child.stdin.take().ok_or("clipboard: no stdin")?.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+/// Body of the hidden `_clear-clipboard` command: clears only if the clipboard
+/// still holds the value we put there, so later copies are left alone. A
+/// terminal's clipboard can't be read back, so with `osc52` it is emptied
+/// regardless, and only if the terminal is still there.
+fn clear_clipboard_after(timeout: u64, osc52: bool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut expected = Zeroizing::new(String::new());
+    io::Read::read_to_string(&mut io::stdin(), &mut expected)?;
+    std::thread::sleep(std::time::Duration::from_secs(timeout));
+    if osc52 {
+        #[cfg(unix)]
+        fs::OpenOptions::new().write(true).open("/dev/tty")?.write_all(b"\x1b]52;c;\x07")?;
+        return Ok(());
+    }
+    let current = read_clipboard()?;
+    if current.trim_end_matches(['\r', '\n']) == expected.as_str() {
+        copy_to_clipboard("")?;
+    }
+    Ok(())
+}
+
+/// A `WIFI:` payload, which phone cameras offer to join: the `ssid` field
+/// names the network, else the last part of the entry's name does, and an
+/// empty password means an open network.
+#[cfg(feature = "qr")]
+fn wifi_payload(e: &Entry) -> Zeroizing<String> {
+    let escape = |text: &str| {
+        let mut out = String::with_capacity(text.len());
+        for c in text.chars() {
+            if matches!(c, '\\' | ';' | ',' | ':' | '"') {
+                out.push('\\');
             }
-            entries.push(Entry { name, user, password });
-            save_entries(&path, &entries, if use_key { Some(&passphrase) } else { None })?;
-            println!("Saved.");
+            out.push(c);
         }
-        "list" => {
-            let passphrase = read_passphrase("Master key (or press Enter if store is unencrypted): ")?;
-            let entries = load_entries(&path, if passphrase.is_empty() { None } else { Some(&passphrase) })?;
-            if entries.is_empty() {
-                println!("(none)");
-            } else {
-                for e in &entries {
-                    println!("  {}  ->  {}:****", e.name, e.user);
+        out
+    };
+    let ssid = e
+        .fields
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("ssid"))
+        .map(|(_, value)| value.as_str())
+        .unwrap_or_else(|| e.name.rsplit('/').next().unwrap_or_default());
+    if e.password.expose().is_empty() {
+        return Zeroizing::new(format!("WIFI:S:{};T:nopass;;", escape(ssid)));
+    }
+    let password = Zeroizing::new(escape(e.password.expose()));
+    Zeroizing::new(format!("WIFI:S:{};T:WPA;P:{};;", escape(ssid), password.as_str()))
+}
+
+/// Prints a QR code with half-block characters, two rows of modules per line,
+/// inside the four-module quiet zone. With color, dark modules are black on
+/// white; without it, light modules are drawn in the terminal's foreground,
+/// which is right for the usual light text on a dark background.
+#[cfg(feature = "qr")]
+fn print_qr(code: &revaultpass::qr::QrCode) {
+    const BORDER: usize = 4;
+    let color = use_color();
+    let side = code.size() + 2 * BORDER;
+    // Outside the symbol `is_dark` is false, which is the quiet zone.
+    let dark = |x: usize, y: usize| code.is_dark(x.wrapping_sub(BORDER), y.wrapping_sub(BORDER));
+    let mut out = Zeroizing::new(String::new());
+    for y in (0..side).step_by(2) {
+        if color {
+            out.push_str("\x1b[30;107m");
+        }
+        for x in 0..side {
+            let (top, bottom) = (dark(x, y), y + 1 < side && dark(x, y + 1));
+            // The halves drawn in the foreground: dark ones with color, light ones without.
+            let (top, bottom) = if color { (top, bottom) } else { (!top, !bottom) };
+            out.push(match (top, bottom) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            });
+        }
+        if color {
+            out.push_str("\x1b[0m");
+        }
+        out.push('\n');
+    }
+    print!("{}", out.as_str());
+}
+
+/// How `type` sends keystrokes to the focused window.
+#[derive(Clone, Copy)]
+enum Typist {
+    /// `xdotool type`, which maps characters missing from the keyboard
+    /// layout onto a spare keycode.
+    Xdotool,
+    /// `wtype`, for Wayland compositors with the virtual keyboard protocol.
+    Wtype,
+    /// System Events through `osascript`.
+    AppleScript,
+    /// .NET `SendKeys` through PowerShell.
+    SendKeys,
+}
+
+fn typist() -> Result<Typist, String> {
+    if cfg!(target_os = "macos") {
+        return Ok(Typist::AppleScript);
+    }
+    if cfg!(windows) {
+        return Ok(Typist::SendKeys);
+    }
+    // Under Wayland, xdotool would only reach X11 windows, so it doesn't count.
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        return match in_path("wtype") {
+            true => Ok(Typist::Wtype),
+            false => Err("can't type under Wayland: it doesn't let programs send keys to other windows, except through wtype on compositors with the virtual keyboard protocol (sway, Hyprland, ...); install it or use 'get --copy'".into()),
+        };
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        return match in_path("xdotool") {
+            true => Ok(Typist::Xdotool),
+            false => Err("typing into X11 windows needs xdotool".into()),
+        };
+    }
+    Err("no graphical session to type into".into())
+}
+
+/// Runs `cmd` with `input` on its stdin, so the text never shows up in the
+/// process list.
+fn run_with_input(cmd: &[&str], input: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut child = Command::new(cmd[0])
+        .args(&cmd[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("could not run '{}': {}", cmd[0], e))?;
+    child.stdin.take().ok_or("no stdin")?.write_all(input.as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(format!("'{}' failed ({})", cmd[0], status).into());
+    }
+    Ok(())
+}
+
+/// Types `text` into the focused window; tabs and newlines press Tab and Enter.
+fn type_text(typist: Typist, text: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match typist {
+        Typist::Xdotool => run_with_input(&["xdotool", "type", "--clearmodifiers", "--file", "-"], text),
+        Typist::Wtype => run_with_input(&["wtype", "-"], text),
+        Typist::AppleScript => {
+            let mut script = Zeroizing::new(String::from("tell application \"System Events\"\n"));
+            for (i, line) in text.split('\n').enumerate() {
+                if i > 0 {
+                    script.push_str("key code 36\n");
+                }
+                for (j, part) in line.split('\t').enumerate() {
+                    if j > 0 {
+                        script.push_str("key code 48\n");
+                    }
+                    if !part.is_empty() {
+                        script.push_str(&format!("keystroke \"{}\"\n", part.replace('\\', "\\\\").replace('"', "\\\"")));
+                    }
                 }
             }
+            script.push_str("end tell\n");
+            run_with_input(&["osascript", "-"], &script)
         }
-        "get" => {
-            let name = args.get(2).map(|s| s.as_str()).unwrap_or("");
-            if name.is_empty() {
-                println!("usage: revaultpass get <name>");
-                return Ok(());
-            }
-            let passphrase = read_passphrase("Master key (or Enter if unencrypted): ")?;
-            let entries = load_entries(&path, if passphrase.is_empty() { None } else { Some(&passphrase) })?;
-            if let Some(e) = entries.iter().find(|e| e.name == name) {
-                println!("{}:{}", e.user, e.password);
-            } else {
-                println!("Not found.");
+        Typist::SendKeys => {
+            let mut keys = Zeroizing::new(String::new());
+            for c in text.chars() {
+                match c {
+                    '\t' => keys.push_str("{TAB}"),
+                    '\n' => keys.push_str("{ENTER}"),
+                    '+' | '^' | '%' | '~' | '(' | ')' | '{' | '}' | '[' | ']' => keys.push_str(&format!("{{{}}}", c)),
+                    '\'' => keys.push_str("''"),
+                    c => keys.push(c),
+                }
             }
+            let script = Zeroizing::new(format!(
+                "Add-Type -AssemblyName System.Windows.Forms\n[System.Windows.Forms.SendKeys]::SendWait('{}')\n",
+                keys.as_str()
+            ));
+            run_with_input(&["powershell", "-NoProfile", "-Command", "-"], &script)
         }
-        "delete" => {
-            let name = args.get(2).map(|s| s.as_str()).unwrap_or("");
-            if name.is_empty() {
-                println!("usage: revaultpass delete <name>");
-                return Ok(());
+    }
+}
+
+/// Turns `\t`, `\n` and `\\` in a `type --sequence` into Tab, newline and a
+/// backslash; any other backslash stays as it is.
+fn unescape_sequence(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('t')) => out.push('\t'),
+            ('\\', Some('n')) => out.push('\n'),
+            ('\\', Some('\\')) => out.push('\\'),
+            _ => {
+                out.push(c);
+                continue;
             }
-            let passphrase = read_passphrase("Master key (or Enter if unencrypted): ")?;
-            let key_opt = if passphrase.is_empty() { None } else { Some(passphrase.as_str()) };
-            let mut entries = load_entries(&path, key_opt)?;
-            let len_before = entries.len();
-            entries.retain(|e| e.name != name);
-            if entries.len() == len_before {
-                println!("Not found.");
-                return Ok(());
+        }
+        chars.next();
+    }
+    out
+}
+
+const KEYCHAIN_SERVICE: &str = "revaultpass";
+
+/// The OS secret store that can hold master keys: the macOS keychain through
+/// `security`, or the Secret Service (GNOME Keyring, KWallet) through
+/// libsecret's `secret-tool`.
+#[derive(Clone, Copy)]
+enum Keychain {
+    MacOs,
+    SecretService,
+}
+
+fn keychain() -> Option<Keychain> {
+    if cfg!(target_os = "macos") {
+        return Some(Keychain::MacOs);
+    }
+    if cfg!(unix) && in_path("secret-tool") {
+        return Some(Keychain::SecretService);
+    }
+    None
+}
+
+/// Why `keychain()` found none, for errors and `keychain status`.
+fn keychain_missing() -> &'static str {
+    if cfg!(windows) {
+        "the keychain isn't supported on Windows: its Credential Manager has no command-line tool that can read a password back"
+    } else {
+        "no keychain available (needs macOS, or secret-tool from libsecret)"
+    }
+}
+
+/// Keychain items are per store, keyed by the store's absolute path.
+fn keychain_account(path: &std::path::Path) -> io::Result<String> {
+    Ok(fs::canonicalize(path)?.to_string_lossy().into_owned())
+}
+
+/// The master key saved for this store, or `None` if there is none or the
+/// keychain can't be asked (locked, no session bus, no tool).
+fn keychain_lookup(path: &std::path::Path) -> Option<Zeroizing<String>> {
+    let account = keychain_account(path).ok()?;
+    let mut cmd = match keychain()? {
+        Keychain::MacOs => {
+            let mut cmd = Command::new("security");
+            cmd.args(["find-generic-password", "-s", KEYCHAIN_SERVICE, "-a", &account, "-w"]);
+            cmd
+        }
+        Keychain::SecretService => {
+            let mut cmd = Command::new("secret-tool");
+            cmd.args(["lookup", "service", KEYCHAIN_SERVICE, "store", &account]);
+            cmd
+        }
+    };
+    let out = cmd.stdin(Stdio::null()).stderr(Stdio::null()).output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let mut key = Zeroizing::new(String::from_utf8(out.stdout).ok()?);
+    let len = key.trim_end_matches('\n').len();
+    key.truncate(len);
+    Some(key).filter(|k| !k.is_empty())
+}
+
+/// Saves `key` for this store, replacing any earlier one. The key goes over
+/// stdin so it never shows up in the process list.
+fn keychain_save(path: &std::path::Path, key: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let account = keychain_account(path)?;
+    let tool = keychain().ok_or(keychain_missing())?;
+    let (mut cmd, input) = match tool {
+        Keychain::MacOs => {
+            // `security -i` reads its commands from stdin, arguments in double quotes.
+            let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+            let line = format!("add-generic-password -U -s {} -a {} -w {}\n", KEYCHAIN_SERVICE, quote(&account), quote(key));
+            let mut cmd = Command::new("security");
+            cmd.arg("-i");
+            (cmd, Zeroizing::new(line))
+        }
+        Keychain::SecretService => {
+            let mut cmd = Command::new("secret-tool");
+            cmd.args(["store", "--label", &format!("revaultpass master key ({})", account)])
+                .args(["service", KEYCHAIN_SERVICE, "store", &account]);
+            (cmd, Zeroizing::new(key.to_string()))
+        }
+    };
+    let mut child = cmd.stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::piped()).spawn()?;
+    child.stdin.take().ok_or("keychain: no stdin")?.write_all(input.as_bytes())?;
+    let out = child.wait_with_output()?;
+    if !out.status.success() {
+        return Err(format!("keychain: {}", String::from_utf8_lossy(&out.stderr).trim()).into());
+    }
+    Ok(())
+}
+
+fn keychain_delete(path: &std::path::Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let account = keychain_account(path)?;
+    let mut cmd = match keychain().ok_or(keychain_missing())? {
+        Keychain::MacOs => {
+            let mut cmd = Command::new("security");
+            cmd.args(["delete-generic-password", "-s", KEYCHAIN_SERVICE, "-a", &account]);
+            cmd
+        }
+        Keychain::SecretService => {
+            let mut cmd = Command::new("secret-tool");
+            cmd.args(["clear", "service", KEYCHAIN_SERVICE, "store", &account]);
+            cmd
+        }
+    };
+    let out = cmd.stdin(Stdio::null()).stdout(Stdio::null()).output()?;
+    if !out.status.success() {
+        return Err(format!("keychain: {}", String::from_utf8_lossy(&out.stderr).trim()).into());
+    }
+    Ok(())
+}
+
+/// After a key change: a saved key would be wrong from now on, so replace
+/// it (or remove it when encryption was dropped).
+fn keychain_follow(path: &std::path::Path, new: &str) {
+    if keychain_lookup(path).is_some() {
+        let updated = if new.is_empty() { keychain_delete(path) } else { keychain_save(path, new) };
+        if let Err(e) = updated {
+            eprintln!("warning: {}; run 'revaultpass keychain disable'", e);
+        }
+    }
+}
+
+/// Prints a freshly made recovery key; nothing else ever shows it again.
+fn show_recovery_key(key: &RecoveryKey) {
+    if JSON_OUTPUT.load(Ordering::Relaxed) {
+        println!("{}", serde_json::json!({ "recovery_key": key.encode().as_str() }));
+        return;
+    }
+    eprintln!("Recovery key (shown only this once; write it down and keep it away from this computer):");
+    println!("{}", key.encode().as_str());
+    status!("With it, 'revaultpass recover' opens the store without the master key or keyfile.");
+}
+
+/// Adds a share for `shares recover`, refusing one that can't go with the
+/// shares already given.
+fn add_share(shares: &mut Vec<Share>, share: Share) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(first) = shares.first() {
+        if share.set != first.set || share.threshold != first.threshold {
+            return Err("that share comes from a different split".into());
+        }
+        if shares.iter().any(|s| s.index == share.index) {
+            return Err(format!("share {} was already given", share.index).into());
+        }
+    }
+    shares.push(share);
+    Ok(())
+}
+
+/// Opens the store with the recovery key from `read_key` (typed in, or
+/// rebuilt from shares) and replaces a passphrase slot with a new master key.
+fn recover_store(
+    path: &std::path::Path,
+    kdf: &KdfArgs,
+    slot: Option<u8>,
+    keyfile: Option<&std::path::Path>,
+    wrong: &str,
+    read_key: impl FnOnce() -> Result<RecoveryKey, Box<dyn std::error::Error + Send + Sync>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    require_store(path)?;
+    let data = fs::read(path)?;
+    if !has_recovery_slot(&data) {
+        return Err(RevaultError::KeyMismatch("this store has no recovery key").at(path).into());
+    }
+    let passphrases: Vec<(u8, KdfParams)> = key_slots(&data)
+        .iter()
+        .filter_map(|s| match s.kind {
+            KeySlotKind::Passphrase { kdf, .. } => Some((s.id, kdf)),
+            KeySlotKind::Recovery => None,
+        })
+        .collect();
+    let base = match slot {
+        Some(id) => passphrases.iter().find(|(i, _)| *i == id).ok_or_else(|| format!("no passphrase slot {}; see 'revaultpass slots list'", id))?.1,
+        None if passphrases.len() <= 1 => passphrases.first().map_or_else(KdfParams::default, |(_, kdf)| *kdf),
+        None => return Err("this store has several passphrase slots; pass --slot <id> to pick the one to replace (see 'revaultpass slots list')".into()),
+    };
+    let kdf = kdf_from_args(kdf, base)?;
+    // Only --keyfile, not REVAULTPASS_KEYFILE: recovering is often about a lost keyfile.
+    let keyfile = keyfile.map(read_keyfile).transpose()?;
+    let recovery = read_key()?;
+    match check_recovery_key(&data, &recovery) {
+        Err(RevaultError::WrongPassphrase) => {
+            if JSON_OUTPUT.load(Ordering::Relaxed) {
+                eprintln!("{}", json_error(wrong, 4));
+            } else {
+                eprintln!("Error: {}", wrong);
+            }
+            return Err(Exit(4).into());
+        }
+        checked => checked.map_err(|e| e.at(path))?,
+    }
+    let new = read_new_passphrase("New master key: ", "Repeat new master key: ")?.ok_or("keys do not match; store left unchanged")?;
+    if new.is_empty() {
+        return Err("the new master key can't be empty; use 'revaultpass rekey' afterwards to drop encryption".into());
+    }
+    let key = StoreKey::from_passphrase(new.clone(), kdf).with_keyfile(keyfile.clone());
+    let _lock = lock_store(path, Lock::Exclusive)?;
+    let mut vault = Vault::recover(path, &recovery, slot, key)?;
+    vault.save()?;
+    keychain_follow(path, &new);
+    status!("Store recovered. Your data is encrypted with the new master key; the recovery key (or its shares) still works.");
+    match (requires_keyfile(&data), keyfile.is_some()) {
+        (false, true) => status!("The keyfile is now needed as well to open the store."),
+        (true, false) => status!("The store no longer needs a keyfile."),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The recipients for `export --age-recipient`: each value is a key, or a
+/// file of keys one per line such as `~/.ssh/id_ed25519.pub`.
+fn age_recipients(values: &[String]) -> Result<Vec<age::Recipient>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut recipients = Vec::new();
+    for value in values {
+        match age::Recipient::parse(value) {
+            Ok(recipient) => recipients.push(recipient),
+            Err(e) if !std::path::Path::new(value).is_file() => return Err(format!("--age-recipient {}: {}", value, e).into()),
+            Err(_) => {
+                let text = fs::read_to_string(value).map_err(|e| format!("{}: {}", value, e))?;
+                let before = recipients.len();
+                for line in text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+                    recipients.push(age::Recipient::parse(line).map_err(|e| format!("{}: {}", value, e))?);
+                }
+                if recipients.len() == before {
+                    return Err(format!("{}: no public keys in the file", value).into());
+                }
+            }
+        }
+    }
+    Ok(recipients)
+}
+
+/// Removes `<store>.pub` and `<store>.sig` once a store is no longer signed.
+fn remove_signature_files(path: &std::path::Path) -> io::Result<()> {
+    for file in [public_key_path(path), signature_path(path), signing_key_path(path)] {
+        match fs::remove_file(&file) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Renames the store to `<stem>.replaced-<unix-ts>.<ext>` in the same
+/// directory, with its `.sig` and `.pub` if it is signed, so `init --force`
+/// never destroys one.
+fn move_store_aside(path: &std::path::Path) -> io::Result<PathBuf> {
+    let stem = path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy();
+    let mut name = format!("{}.replaced-{}", stem, unix_now());
+    if let Some(ext) = path.extension() {
+        name = format!("{}.{}", name, ext.to_string_lossy());
+    }
+    let aside = path.with_file_name(name);
+    fs::rename(path, &aside)?;
+    let sidecars = [
+        (signature_path(path), signature_path(&aside)),
+        (public_key_path(path), public_key_path(&aside)),
+        (signing_key_path(path), signing_key_path(&aside)),
+        (journal_path(path), journal_path(&aside)),
+    ];
+    for (from, to) in sidecars {
+        match fs::rename(&from, &to) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(aside)
+}
+
+/// Applies `--kdf-mem <KiB>` and `--kdf-iters <n>` on top of `base`.
+fn kdf_from_args(args: &KdfArgs, base: KdfParams) -> Result<KdfParams, Box<dyn std::error::Error + Send + Sync>> {
+    let mut kdf = base;
+    if let Some(m) = args.kdf_mem {
+        kdf.m_cost = m;
+    }
+    if let Some(t) = args.kdf_iters {
+        kdf.t_cost = t;
+    }
+    if kdf.m_cost < KDF_MIN_MEM_KIB {
+        return Err(format!("--kdf-mem {} is too low; use at least {} (8 MiB)", kdf.m_cost, KDF_MIN_MEM_KIB).into());
+    }
+    if let Some(avail) = available_memory_kib() {
+        if u64::from(kdf.m_cost) > avail {
+            return Err(format!("--kdf-mem {} KiB is more than the {} KiB of memory available", kdf.m_cost, avail).into());
+        }
+    }
+    if !kdf.is_plausible() {
+        return Err("KDF parameters out of range (at most 4194304 KiB and 64 iterations)".into());
+    }
+    Ok(kdf)
+}
+
+/// The KDF of each key slot in `data`, or of the single key of an older
+/// store, as `fsck` and `info` print them.
+fn header_kdfs(data: &[u8]) -> Vec<String> {
+    match key_slots(data).as_slice() {
+        [] => vec![kdf_label(&KdfParams::from_header(data))],
+        slots => slots
+            .iter()
+            .map(|s| match s.kind {
+                KeySlotKind::Passphrase { kdf, keyfile } => {
+                    format!("slot {}: passphrase, {}{}", s.id, kdf_label(&kdf), if keyfile { " + keyfile" } else { "" })
+                }
+                KeySlotKind::Recovery => format!("slot {}: recovery key", s.id),
+            })
+            .collect(),
+    }
+}
+
+/// Times one key derivation with `kdf`.
+fn time_kdf(kdf: &KdfParams) -> Result<std::time::Duration, Box<dyn std::error::Error + Send + Sync>> {
+    let start = std::time::Instant::now();
+    derive_key("benchmark", &[0u8; SALT_LEN], kdf).map_err(RevaultError::from)?;
+    Ok(start.elapsed())
+}
+
+/// Picks the most memory that derives within the target at the default
+/// iteration count, then adds iterations if time is left over.
+fn tune_kdf(target: std::time::Duration) -> Result<(KdfParams, std::time::Duration), Box<dyn std::error::Error + Send + Sync>> {
+    // Leave the machine some headroom while benchmarking.
+    let cap = available_memory_kib().map(|a| a / 2).unwrap_or(u64::MAX).min(1024 * 1024);
+    let mut best = KdfParams::default();
+    let mut best_time = time_kdf(&best)?;
+    let mut m = 32 * 1024;
+    while u64::from(m) <= cap && best_time < target {
+        let kdf = KdfParams { m_cost: m, ..best };
+        let t = time_kdf(&kdf)?;
+        if t > target {
+            break;
+        }
+        best = kdf;
+        best_time = t;
+        m *= 2;
+    }
+    let per_iter = best_time / best.t_cost;
+    let iters = (target.as_millis() / per_iter.as_millis().max(1)) as u32;
+    if iters > best.t_cost {
+        best.t_cost = iters.min(64);
+        best_time = time_kdf(&best)?;
+    }
+    Ok((best, best_time))
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn entries_to_csv(entries: &[Entry]) -> String {
+    let mut out = String::from("name,user,password,url,notes,tags,fields,totp,kind\n");
+    for e in entries {
+        // Extra fields as a JSON object, the only way to keep arbitrary names and values in one cell.
+        let fields = if e.fields.is_empty() { String::new() } else { serde_json::json!(e.fields).to_string() };
+        // A note's body goes in the notes column.
+        let (notes, kind) = match e.is_note() {
+            true => (e.body.as_ref().map(Secret::expose), "note"),
+            false => (e.notes.as_deref(), ""),
+        };
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{}\n",
+            csv_field(&e.name),
+            csv_field(&e.user),
+            csv_field(e.password.expose()),
+            csv_field(e.url.as_deref().unwrap_or_default()),
+            csv_field(notes.unwrap_or_default()),
+            csv_field(&e.tags.join(",")),
+            csv_field(&fields),
+            csv_field(e.totp.as_ref().map(Secret::expose).unwrap_or_default()),
+            kind
+        ));
+    }
+    out
+}
+
+/// A CSV row and the line number it starts on.
+type CsvRecord = (usize, Vec<String>);
+
+/// RFC 4180 style parser: quoted fields may contain commas, doubled quotes and
+/// newlines.
+fn parse_csv(text: &str) -> Result<Vec<CsvRecord>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut start_line = 1;
+    let mut chars = text.strip_prefix('\u{feff}').unwrap_or(text).chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line += 1;
+        }
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                record.push(std::mem::take(&mut field));
+                if !(record.len() == 1 && record[0].is_empty()) {
+                    records.push((start_line, std::mem::take(&mut record)));
+                }
+                record.clear();
+                start_line = line;
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(format!("csv: unterminated quoted field starting on line {}", start_line).into());
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((start_line, record));
+    }
+    Ok(records)
+}
+
+/// Looks up each wanted column (case-insensitive) in a CSV header row.
+fn csv_columns(header: &[String], wanted: &[&str]) -> Result<Vec<usize>, Box<dyn std::error::Error + Send + Sync>> {
+    wanted
+        .iter()
+        .map(|w| {
+            header
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(w))
+                .ok_or_else(|| format!("csv: missing column '{}'", w).into())
+        })
+        .collect()
+}
+
+/// Entries parsed from an import file, before merging into the store.
+#[derive(Default)]
+struct ImportBatch {
+    entries: Vec<Entry>,
+    skipped: usize,
+    warnings: Vec<String>,
+}
+
+/// Generic CSV import. `map` renames the expected `name,user,password`
+/// columns, e.g. `name=Title,user=Login`; `url`, `notes`, `tags`
+/// (comma-separated), `fields` (a JSON object), `totp` and `kind` are read
+/// when present. A `kind` of `note` makes a secure note out of the notes column.
+fn import_csv(text: &str, map: Option<&str>) -> Result<ImportBatch, Box<dyn std::error::Error + Send + Sync>> {
+    let mut columns = ["name", "user", "password", "url", "notes", "tags", "fields", "totp", "kind"].map(|f| (f, f.to_string()));
+    for pair in map.unwrap_or("").split(',').filter(|p| !p.is_empty()) {
+        let (field, column) = pair.split_once('=').ok_or_else(|| format!("--map: expected field=column, got '{}'", pair))?;
+        let slot = columns
+            .iter_mut()
+            .find(|(f, _)| *f == field.trim())
+            .ok_or_else(|| format!("--map: unknown field '{}' (expected name, user, password, url, notes, tags, fields, totp or kind)", field))?;
+        slot.1 = column.trim().to_string();
+    }
+    let mut records = parse_csv(text)?.into_iter();
+    let (_, header) = records.next().ok_or("csv: file is empty")?;
+    let wanted: Vec<&str> = columns[..3].iter().map(|(_, c)| c.as_str()).collect();
+    let idx = csv_columns(&header, &wanted)?;
+    let optional: Vec<Option<usize>> =
+        columns[3..].iter().map(|(_, c)| header.iter().position(|h| h.trim().eq_ignore_ascii_case(c))).collect();
+    let mut batch = ImportBatch::default();
+    for (line, row) in records {
+        let get = |i: usize| row.get(idx[i]).cloned().unwrap_or_default();
+        let get_optional = |i: usize| optional[i].and_then(|c| row.get(c)).filter(|v| !v.is_empty()).cloned();
+        if get(0).is_empty() {
+            batch.skipped += 1;
+            batch.warnings.push(format!("line {}: empty name, skipped", line));
+            continue;
+        }
+        let tags = get_optional(2).map(|t| t.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect());
+        if get_optional(5).is_some_and(|k| k.eq_ignore_ascii_case("note")) {
+            let body = get_optional(1).unwrap_or_default();
+            batch.entries.push(Entry { tags: tags.unwrap_or_default(), ..Entry::note(get(0), body.into()) });
+            continue;
+        }
+        let fields = match get_optional(3) {
+            Some(f) => serde_json::from_str(&f).map_err(|e| format!("line {}: fields: {}", line, e))?,
+            None => Default::default(),
+        };
+        batch.entries.push(Entry {
+            url: get_optional(0),
+            notes: get_optional(1),
+            tags: tags.unwrap_or_default(),
+            fields,
+            totp: get_optional(4).map(Secret::from),
+            ..Entry::new(get(0), get(1), get(2).into())
+        });
+    }
+    Ok(batch)
+}
+
+/// Host part of a URL, without scheme, credentials, port or path.
+fn url_host(url: &str) -> &str {
+    let rest = url.split_once("://").map(|(_, r)| r).unwrap_or(url);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = authority.rsplit_once('@').map(|(_, h)| h).unwrap_or(authority);
+    host.split(':').next().unwrap_or("")
+}
+
+/// Chrome/Chromium password export (`name,url,username,password`). Several
+/// accounts on one site share a name, so those get the username appended and,
+/// if that still clashes, a numeric suffix.
+fn import_chrome(text: &str) -> Result<ImportBatch, Box<dyn std::error::Error + Send + Sync>> {
+    let mut records = parse_csv(text)?.into_iter();
+    let (_, header) = records.next().ok_or("csv: file is empty")?;
+    let idx = csv_columns(&header, &["name", "url", "username", "password"])?;
+    let mut batch = ImportBatch::default();
+    for (line, row) in records {
+        let get = |i: usize| row.get(idx[i]).cloned().unwrap_or_default();
+        let (name, url) = (get(0), get(1));
+        let name = if name.is_empty() { url_host(&url).to_string() } else { name };
+        if name.is_empty() {
+            batch.skipped += 1;
+            batch.warnings.push(format!("line {}: no name or url, skipped", line));
+            continue;
+        }
+        batch.entries.push(Entry::new(name, get(2), get(3).into()));
+    }
+    let names: Vec<String> = batch.entries.iter().map(|e| e.name.clone()).collect();
+    for e in batch.entries.iter_mut() {
+        if names.iter().filter(|n| **n == e.name).count() > 1 && !e.user.is_empty() {
+            e.name = format!("{} ({})", e.name, e.user);
+        }
+    }
+    let mut seen: Vec<String> = Vec::new();
+    for e in batch.entries.iter_mut() {
+        if seen.contains(&e.name) {
+            let mut n = 2;
+            while seen.contains(&format!("{}-{}", e.name, n)) {
+                n += 1;
+            }
+            e.name = format!("{}-{}", e.name, n);
+        }
+        seen.push(e.name.clone());
+    }
+    Ok(batch)
+}
+
+/// Collects entry files below `dir` (recursively), skipping dotfiles such as
+/// `.git` and `.gpg-id`.
+fn walk_files(dir: &std::path::Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut items: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    items.sort_by_key(|e| e.file_name());
+    for item in items {
+        if item.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = item.path();
+        if item.file_type()?.is_dir() {
+            walk_files(&path, out)?;
+        } else {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Parses one `pass` entry: the password is the first line, the user comes
+/// from a `user:`, `login:` or `username:` line if there is one.
+fn parse_pass_entry(name: String, text: &str) -> Entry {
+    let mut lines = text.lines();
+    let password = lines.next().unwrap_or("").to_string();
+    let user = lines
+        .find_map(|l| {
+            let (key, value) = l.split_once(':')?;
+            let key = key.trim().to_lowercase();
+            (key == "user" || key == "login" || key == "username").then(|| value.trim().to_string())
+        })
+        .unwrap_or_default();
+    Entry::new(name, user, password.into())
+}
+
+/// Imports a `pass` (password-store) tree. Each `.gpg` file is decrypted with
+/// `gpg --decrypt`, or read as-is with `plain` for pre-decrypted trees. Files
+/// that fail are reported as warnings and skipped.
+fn import_pass(dir: &std::path::Path, plain: bool) -> Result<ImportBatch, Box<dyn std::error::Error + Send + Sync>> {
+    let mut files = Vec::new();
+    walk_files(dir, &mut files).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut batch = ImportBatch::default();
+    for file in files {
+        let rel = file.strip_prefix(dir).unwrap_or(&file);
+        let is_gpg = rel.extension().is_some_and(|e| e == "gpg");
+        if !plain && !is_gpg {
+            continue;
+        }
+        let stem = if is_gpg { rel.with_extension("") } else { rel.to_path_buf() };
+        let name = stem
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let text = if plain {
+            fs::read_to_string(&file).map_err(|e| e.to_string())
+        } else {
+            Command::new("gpg")
+                .args(["--quiet", "--decrypt"])
+                .arg(&file)
+                .stderr(Stdio::piped())
+                .output()
+                .map_err(|e| format!("gpg: {}", e))
+                .and_then(|out| {
+                    if out.status.success() {
+                        Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+                    } else {
+                        Err(String::from_utf8_lossy(&out.stderr).trim().to_string())
+                    }
+                })
+        };
+        match text {
+            Ok(text) => batch.entries.push(parse_pass_entry(name, &text)),
+            Err(e) => {
+                batch.skipped += 1;
+                batch.warnings.push(format!("could not decrypt {}: {}", file.display(), e));
+            }
+        }
+    }
+    Ok(batch)
+}
+
+/// KeePass/KeePassXC CSV export (`Group,Title,Username,Password,URL,Notes`).
+/// Entries are named `Group/Title` to keep the folder structure; URL and
+/// Notes are read when the export has them.
+fn import_keepass(text: &str) -> Result<ImportBatch, Box<dyn std::error::Error + Send + Sync>> {
+    let mut records = parse_csv(text)?.into_iter();
+    let (_, header) = records.next().ok_or("csv: file is empty")?;
+    let idx = csv_columns(&header, &["group", "title", "username", "password"])?;
+    let optional: Vec<Option<usize>> = ["url", "notes"].iter().map(|c| header.iter().position(|h| h.trim().eq_ignore_ascii_case(c))).collect();
+    let mut batch = ImportBatch::default();
+    let mut untitled = Vec::new();
+    for (line, row) in records {
+        let get = |i: usize| row.get(idx[i]).cloned().unwrap_or_default();
+        let get_optional = |i: usize| optional[i].and_then(|c| row.get(c)).filter(|v| !v.is_empty()).cloned();
+        let (group, title) = (get(0), get(1));
+        if title.is_empty() {
+            untitled.push(line.to_string());
+            continue;
+        }
+        let name = if group.is_empty() { title } else { format!("{}/{}", group.trim_end_matches('/'), title) };
+        batch.entries.push(Entry { url: get_optional(0), notes: get_optional(1), ..Entry::new(name, get(2), get(3).into()) });
+    }
+    if !untitled.is_empty() {
+        batch.skipped += untitled.len();
+        batch.warnings.push(format!("skipped rows with empty Title on line(s) {}", untitled.join(", ")));
+    }
+    Ok(batch)
+}
+
+/// LastPass CSV export (`url,username,password,extra,name,grouping,fav`).
+/// Secure notes (url `http://sn`) become notes with `extra` as the body; for
+/// logins `extra` goes into the notes. "Generated Password for ..."
+/// placeholders are skipped.
+fn import_lastpass(text: &str) -> Result<ImportBatch, Box<dyn std::error::Error + Send + Sync>> {
+    let mut records = parse_csv(text)?.into_iter();
+    let (_, header) = records.next().ok_or("csv: file is empty")?;
+    let idx = csv_columns(&header, &["url", "username", "password", "name", "grouping"])?;
+    let extra = header.iter().position(|h| h.trim().eq_ignore_ascii_case("extra"));
+    let mut batch = ImportBatch::default();
+    let mut generated = 0;
+    for (line, row) in records {
+        let get = |i: usize| row.get(idx[i]).cloned().unwrap_or_default();
+        let (url, name, grouping) = (get(0), get(3), get(4));
+        let extra = extra.and_then(|c| row.get(c)).cloned().unwrap_or_default();
+        if name.starts_with("Generated Password for ") {
+            generated += 1;
+            continue;
+        }
+        if name.is_empty() {
+            batch.skipped += 1;
+            batch.warnings.push(format!("line {}: empty name, skipped", line));
+            continue;
+        }
+        let name = if grouping.is_empty() { name } else { format!("{}/{}", grouping, name) };
+        if url == "http://sn" {
+            batch.entries.push(Entry::note(name, extra.into()));
+            continue;
+        }
+        batch.entries.push(Entry {
+            url: Some(url).filter(|u| !u.is_empty()),
+            notes: Some(extra).filter(|e| !e.is_empty()),
+            ..Entry::new(name, get(1), get(2).into())
+        });
+    }
+    if generated > 0 {
+        batch.skipped += generated;
+        batch.warnings.push(format!("skipped {} generated-password placeholder(s)", generated));
+    }
+    Ok(batch)
+}
+
+#[derive(Deserialize)]
+struct BitwardenExport {
+    items: Vec<BitwardenItem>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenItem {
+    name: String,
+    #[serde(rename = "type")]
+    kind: u8,
+    login: Option<BitwardenLogin>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenLogin {
+    username: Option<String>,
+    password: Option<String>,
+    #[serde(default)]
+    uris: Option<Vec<BitwardenUri>>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenUri {
+    uri: Option<String>,
+}
+
+/// Bitwarden item type for logins; cards, identities and notes are skipped.
+const BITWARDEN_LOGIN: u8 = 1;
+
+/// Unencrypted Bitwarden JSON export. The first of a login's URIs becomes
+/// the entry's URL.
+fn import_bitwarden(text: &str) -> Result<ImportBatch, Box<dyn std::error::Error + Send + Sync>> {
+    let export: BitwardenExport = serde_json::from_str(text).map_err(|e| format!("bitwarden: {}", e))?;
+    let mut batch = ImportBatch::default();
+    let mut non_login = 0;
+    for item in export.items {
+        let login = match item.login {
+            Some(l) if item.kind == BITWARDEN_LOGIN => l,
+            _ => {
+                non_login += 1;
+                continue;
+            }
+        };
+        let password = login.password.unwrap_or_default();
+        if password.is_empty() {
+            batch.warnings.push(format!("'{}' has no password, imported with an empty one", item.name));
+        }
+        // The first URI is the one Bitwarden shows and fills in.
+        let url = login.uris.unwrap_or_default().into_iter().find_map(|u| u.uri.filter(|u| !u.is_empty()));
+        batch.entries.push(Entry { url, ..Entry::new(item.name, login.username.unwrap_or_default(), password.into()) });
+    }
+    if non_login > 0 {
+        batch.skipped += non_login;
+        batch.warnings.push(format!("skipped {} non-login item(s)", non_login));
+    }
+    Ok(batch)
+}
+
+/// An `[[entry]]` of a TOML import while it is read.
+struct TomlEntry {
+    line: usize,
+    note: bool,
+    has_password: bool,
+    entry: Entry,
+}
+
+impl TomlEntry {
+    /// "entry 3 ('name')", its position in the file counting from 1.
+    fn describe(&self, index: usize) -> String {
+        match self.entry.name.is_empty() {
+            true => format!("entry {}", index + 1),
+            false => format!("entry {} ('{}')", index + 1, self.entry.name),
+        }
+    }
+}
+
+/// What `export --format toml` writes. Each `[[entry]]` needs a name and a
+/// password, or `kind = "note"` and a body; timestamps left out are the time
+/// of the import.
+fn import_toml(text: &str) -> Result<ImportBatch, Box<dyn std::error::Error + Send + Sync>> {
+    enum Table {
+        None,
+        Entry,
+        Fields,
+        History,
+    }
+    let mut lines = text.strip_prefix('\u{feff}').unwrap_or(text).lines();
+    let mut parsed: Vec<TomlEntry> = Vec::new();
+    let mut table = Table::None;
+    let mut seen: Vec<String> = Vec::new();
+    let mut line = 0;
+    while let Some(raw) = lines.next() {
+        line += 1;
+        let start = line;
+        let text = raw.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let context = match parsed.len() {
+            0 => String::new(),
+            n => format!("{}, ", parsed[n - 1].describe(n - 1)),
+        };
+        let at = |message: String| format!("toml: {}line {}: {}", context, start, message);
+        if let Some((path, array)) = toml::header(text, &mut lines, &mut line).map_err(at)? {
+            seen.clear();
+            let path: Vec<&str> = path.iter().map(String::as_str).collect();
+            table = match (path.as_slice(), array) {
+                (["entry"], true) => {
+                    let entry = Entry::new(String::new(), String::new(), Secret::default());
+                    parsed.push(TomlEntry { line: start, note: false, has_password: false, entry });
+                    Table::Entry
+                }
+                (["entry", "fields"], false) if !parsed.is_empty() => Table::Fields,
+                (["entry", "history"], true) if !parsed.is_empty() => {
+                    let old = revaultpass::HistoricalPassword { password: Secret::default(), replaced_at: 0 };
+                    parsed.last_mut().expect("checked above").entry.history.push(old);
+                    Table::History
+                }
+                (["entry", ..], _) if parsed.is_empty() => return Err(at("this table must follow an [[entry]]".into()).into()),
+                _ => return Err(at("expected [[entry]], [entry.fields] or [[entry.history]]".into()).into()),
+            };
+            continue;
+        }
+        let (key, rest) = toml::read_key(text, &mut lines, &mut line).map_err(at)?;
+        let rest = rest.trim_start().strip_prefix('=').ok_or_else(|| at(format!("expected '=' after '{}'", key)))?.trim_start();
+        let (value, rest) = toml::value(rest, &mut lines, &mut line).map_err(at)?;
+        toml::trailing(rest).map_err(at)?;
+        if seen.contains(&key) {
+            return Err(at(format!("'{}' is set twice in this table", key)).into());
+        }
+        seen.push(key.clone());
+        let Some(current) = parsed.last_mut() else {
+            return Err(at(format!("'{}' is outside any [[entry]] table", key)).into());
+        };
+        let typed = |e: String| at(format!("{}: {}", key, e));
+        let e = &mut current.entry;
+        match table {
+            Table::None => unreachable!("any key after a header is in a table"),
+            Table::Fields => {
+                e.fields.insert(key.clone(), value.into_string().map_err(typed)?);
+            }
+            Table::History => {
+                let old = e.history.last_mut().expect("pushed with the header");
+                match key.as_str() {
+                    "password" => old.password = value.into_string().map_err(typed)?.into(),
+                    "replaced_at" => old.replaced_at = value.into_integer().map_err(typed)?,
+                    _ => return Err(at(format!("unknown key '{}' (expected password or replaced_at)", key)).into()),
+                }
+            }
+            Table::Entry => match key.as_str() {
+                "name" => e.name = value.into_string().map_err(typed)?,
+                "kind" => {
+                    current.note = match value.into_string().map_err(typed)?.as_str() {
+                        "note" => true,
+                        "login" => false,
+                        other => return Err(at(format!("kind is \"login\" or \"note\", not \"{}\"", other)).into()),
+                    }
+                }
+                "user" => e.user = value.into_string().map_err(typed)?,
+                "password" => {
+                    e.password = value.into_string().map_err(typed)?.into();
+                    current.has_password = true;
+                }
+                "body" => e.body = Some(value.into_string().map_err(typed)?.into()),
+                "url" => e.url = Some(value.into_string().map_err(typed)?),
+                "notes" => e.notes = Some(value.into_string().map_err(typed)?),
+                "totp" => {
+                    let totp = value.into_string().map_err(typed)?;
+                    parse_totp(&totp).map_err(typed)?;
+                    e.totp = Some(totp.into());
+                }
+                "tags" => e.tags = value.into_list().map_err(typed)?,
+                "created_at" => e.created_at = value.into_integer().map_err(typed)?,
+                "modified_at" => e.modified_at = value.into_integer().map_err(typed)?,
+                _ => {
+                    return Err(at(format!(
+                        "unknown key '{}' (expected name, kind, user, password, url, tags, totp, notes, body, created_at or modified_at)",
+                        key
+                    ))
+                    .into())
+                }
+            },
+        }
+    }
+    let mut batch = ImportBatch::default();
+    for (index, mut p) in parsed.into_iter().enumerate() {
+        let problem = match (p.note, p.entry.body.is_some()) {
+            _ if p.entry.name.is_empty() => Some("missing name"),
+            (true, false) => Some("a note needs a body"),
+            (true, true) if !p.entry.user.is_empty() || p.has_password => Some("a note can't have a user or password"),
+            (false, true) => Some("has a body but no kind = \"note\""),
+            (false, false) if !p.has_password => Some("missing password"),
+            _ if p.entry.history.iter().any(|h| h.password.is_empty()) => Some("[[entry.history]] without a password"),
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            return Err(format!("toml: {} on line {}: {}", p.describe(index), p.line, problem).into());
+        }
+        if p.note {
+            p.entry.kind = revaultpass::EntryKind::Note;
+        }
+        batch.entries.push(p.entry);
+    }
+    Ok(batch)
+}
+
+/// A line of `add --batch`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchLine {
+    name: String,
+    user: String,
+    password: String,
+    url: Option<String>,
+    notes: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    fields: std::collections::BTreeMap<String, String>,
+    totp: Option<String>,
+}
+
+/// The entry a valid `add --batch` line makes; `earlier` are those of the
+/// lines before it, with their line numbers.
+fn batch_entry(line: BatchLine, earlier: &[(usize, Entry)], allow_empty: bool, min_score: Option<u8>) -> Result<Entry, String> {
+    let name = parse_entry_name(&line.name)?;
+    if let Some((n, _)) = earlier.iter().find(|(_, e)| revaultpass::names::same(&e.name, &name)) {
+        return Err(format!("'{}' is also on line {}", name, n));
+    }
+    if line.password.is_empty() && !allow_empty {
+        return Err("the password is empty; pass --allow-empty if that is intended".into());
+    }
+    if let Some(min) = min_score.filter(|_| !line.password.is_empty()) {
+        let score = revaultpass::strength::estimate(&line.password).score;
+        if score < min {
+            return Err(format!("password scores {}/4, the policy needs at least {}/4", score, min));
+        }
+    }
+    if let Some(totp) = &line.totp {
+        parse_totp(totp).map_err(|e| format!("totp: {}", e))?;
+    }
+    if line.fields.keys().any(|k| k.trim().is_empty()) {
+        return Err("field name must not be empty".into());
+    }
+    let BatchLine { user, password, url, notes, tags, fields, totp, .. } = line;
+    Ok(Entry { url, notes, tags, fields, totp: totp.map(Secret::from), ..Entry::new(name, user, password.into()) })
+}
+
+/// `add --batch` input: one JSON object per line, blank lines ignored. Every
+/// line is checked, so all problems come back at once, by line number.
+fn parse_batch(text: &str, allow_empty: bool, min_score: Option<u8>) -> Result<Vec<Entry>, Vec<String>> {
+    let mut entries: Vec<(usize, Entry)> = Vec::new();
+    let mut errors = Vec::new();
+    for (i, raw) in text.lines().enumerate() {
+        let n = i + 1;
+        if raw.trim().is_empty() {
+            continue;
+        }
+        let line: BatchLine = match serde_json::from_str(raw) {
+            Ok(line) => line,
+            Err(e) => {
+                // Each line is a document of its own, so serde's "at line 1" says nothing.
+                let message = e.to_string();
+                let message = message.rsplit_once(" at line ").map_or(message.as_str(), |(m, _)| m);
+                errors.push(format!("line {}, column {}: {}", n, e.column(), message));
+                continue;
+            }
+        };
+        match batch_entry(line, &entries, allow_empty, min_score) {
+            Ok(e) => entries.push((n, e)),
+            Err(problem) => errors.push(format!("line {}: {}", n, problem)),
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(entries.into_iter().map(|(_, e)| e).collect()),
+        false => Err(errors),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum OnConflict {
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+impl std::str::FromStr for OnConflict {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(OnConflict::Skip),
+            "overwrite" => Ok(OnConflict::Overwrite),
+            "rename" => Ok(OnConflict::Rename),
+            _ => Err(format!("expected skip, overwrite or rename, got '{}'", s)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct MergeSummary {
+    imported: usize,
+    skipped: usize,
+    renamed: usize,
+    overwritten: usize,
+    conflicts: usize,
+}
+
+impl std::fmt::Display for MergeSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "imported {}, skipped {}, renamed {}, conflicts {}",
+            self.imported, self.skipped, self.renamed, self.conflicts
+        )?;
+        if self.overwritten > 0 {
+            write!(f, ", overwritten {}", self.overwritten)?;
+        }
+        Ok(())
+    }
+}
+
+/// `entries` with their names as `add` would store them; those it would
+/// refuse are left out, with a warning each.
+fn normalize_names(entries: Vec<Entry>) -> (Vec<Entry>, usize) {
+    let before = entries.len();
+    let kept: Vec<Entry> = entries
+        .into_iter()
+        .filter_map(|mut e| match parse_entry_name(&e.name) {
+            Ok(name) => {
+                if name != e.name {
+                    debug!("name {:?} normalized to {:?}", e.name, name);
+                }
+                e.name = name;
+                Some(e)
+            }
+            Err(reason) => {
+                eprintln!("warning: {}, skipped", reason);
+                None
+            }
+        })
+        .collect();
+    let skipped = before - kept.len();
+    (kept, skipped)
+}
+
+/// Merges `incoming` into `entries`, resolving name clashes per `on_conflict`.
+/// Renamed entries get the first free `-2`, `-3`, ... suffix.
+fn merge_entries(entries: &mut Vec<Entry>, incoming: Vec<Entry>, on_conflict: OnConflict) -> MergeSummary {
+    let mut summary = MergeSummary::default();
+    for mut entry in incoming {
+        let Some(pos) = entries.iter().position(|e| revaultpass::names::same(&e.name, &entry.name)) else {
+            entries.push(entry);
+            summary.imported += 1;
+            continue;
+        };
+        summary.conflicts += 1;
+        match on_conflict {
+            OnConflict::Skip => {
+                debug!("{:?} already exists, skipped", entry.name);
+                summary.skipped += 1;
+            }
+            OnConflict::Overwrite => {
+                // Everything comes from the import except the stored name, the
+                // creation time and the history, which gains the old password.
+                let existing = &mut entries[pos];
+                let old = std::mem::replace(existing, entry);
+                existing.name = old.name;
+                existing.created_at = old.created_at;
+                let password = std::mem::replace(&mut existing.password, old.password);
+                let incoming = std::mem::replace(&mut existing.history, old.history);
+                for h in incoming {
+                    if !existing.history.iter().any(|o| o.password == h.password) {
+                        existing.history.push(h);
+                    }
+                }
+                existing.history.sort_by_key(|h| h.replaced_at);
+                let excess = existing.history.len().saturating_sub(revaultpass::HISTORY_KEEP);
+                existing.history.drain(..excess);
+                existing.set_password(password);
+                existing.touch();
+                debug!("{:?} already exists, overwritten", existing.name);
+                summary.imported += 1;
+                summary.overwritten += 1;
+            }
+            OnConflict::Rename => {
+                let base = entry.name.clone();
+                let mut n = 2;
+                while entries.iter().any(|e| revaultpass::names::same(&e.name, &format!("{}-{}", base, n))) {
+                    n += 1;
+                }
+                entry.name = format!("{}-{}", base, n);
+                debug!("{:?} already exists, imported as {:?}", base, entry.name);
+                entries.push(entry);
+                summary.imported += 1;
+                summary.renamed += 1;
+            }
+        }
+    }
+    summary
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Escapes text for XML element content and attributes. Control characters
+/// other than tab and newlines can't be represented in XML 1.0 and are dropped.
+fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// KeePass 2.x XML (as accepted by `keepassxc-cli import`) with a single
+/// "RevaultPass" group.
+fn entries_to_keepass_xml(entries: &[Entry]) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<KeePassFile>\n");
+    out.push_str("\t<Meta>\n\t\t<Generator>RevaultPass</Generator>\n\t</Meta>\n\t<Root>\n\t\t<Group>\n");
+    let mut uuid = [0u8; 16];
+    RngCore::fill_bytes(&mut OsRng, &mut uuid);
+    out.push_str(&format!("\t\t\t<UUID>{}</UUID>\n\t\t\t<Name>RevaultPass</Name>\n", base64_encode(&uuid)));
+    for e in entries {
+        RngCore::fill_bytes(&mut OsRng, &mut uuid);
+        out.push_str(&format!("\t\t\t<Entry>\n\t\t\t\t<UUID>{}</UUID>\n", base64_encode(&uuid)));
+        out.push_str(&format!("\t\t\t\t<String><Key>Title</Key><Value>{}</Value></String>\n", xml_escape(&e.name)));
+        out.push_str(&format!("\t\t\t\t<String><Key>UserName</Key><Value>{}</Value></String>\n", xml_escape(&e.user)));
+        out.push_str(&format!(
+            "\t\t\t\t<String><Key>Password</Key><Value ProtectInMemory=\"True\">{}</Value></String>\n",
+            xml_escape(e.password.expose())
+        ));
+        if let Some(url) = &e.url {
+            out.push_str(&format!("\t\t\t\t<String><Key>URL</Key><Value>{}</Value></String>\n", xml_escape(url)));
+        }
+        let notes = if e.is_note() { e.body.as_ref().map(Secret::expose) } else { e.notes.as_deref() };
+        if let Some(notes) = notes {
+            out.push_str(&format!("\t\t\t\t<String><Key>Notes</Key><Value>{}</Value></String>\n", xml_escape(notes)));
+        }
+        if let Some(totp) = &e.totp {
+            // KeePassXC's attribute name for TOTP seeds.
+            out.push_str(&format!(
+                "\t\t\t\t<String><Key>otp</Key><Value ProtectInMemory=\"True\">{}</Value></String>\n",
+                xml_escape(totp.expose())
+            ));
+        }
+        for (k, v) in &e.fields {
+            out.push_str(&format!("\t\t\t\t<String><Key>{}</Key><Value>{}</Value></String>\n", xml_escape(k), xml_escape(v)));
+        }
+        if !e.tags.is_empty() {
+            out.push_str(&format!("\t\t\t\t<Tags>{}</Tags>\n", xml_escape(&e.tags.join(";"))));
+        }
+        out.push_str("\t\t\t</Entry>\n");
+    }
+    out.push_str("\t\t</Group>\n\t</Root>\n</KeePassFile>\n");
+    out
+}
+
+const TOML_EXPORT_HEADER: &str = "\
+# revaultpass entries, one [[entry]] table each; read back with import --format toml.
+# Keys: name, user, password, url, tags = [\"...\"], totp, notes, created_at and
+# modified_at (Unix time), an [entry.fields] table for extra fields and
+# [[entry.history]] tables for earlier passwords. Notes have kind = \"note\" and a
+# body instead of user and password.
+";
+
+/// Everything the store keeps per entry, history and timestamps included, as
+/// a TOML array of `[[entry]]` tables.
+fn entries_to_toml(entries: &[Entry]) -> String {
+    let mut out = String::from(TOML_EXPORT_HEADER);
+    for e in entries {
+        out.push_str("\n[[entry]]\n");
+        let mut push = |key: &str, value: &str| out.push_str(&format!("{} = {}\n", key, toml::quote(value)));
+        push("name", &e.name);
+        if e.is_note() {
+            push("kind", "note");
+        } else {
+            push("user", &e.user);
+            push("password", e.password.expose());
+        }
+        if let Some(url) = &e.url {
+            push("url", url);
+        }
+        if let Some(totp) = &e.totp {
+            push("totp", totp.expose());
+        }
+        if let Some(notes) = &e.notes {
+            push("notes", notes);
+        }
+        if let Some(body) = &e.body {
+            push("body", body.expose());
+        }
+        if !e.tags.is_empty() {
+            let tags: Vec<String> = e.tags.iter().map(|t| toml::quote(t)).collect();
+            out.push_str(&format!("tags = [{}]\n", tags.join(", ")));
+        }
+        for (key, time) in [("created_at", e.created_at), ("modified_at", e.modified_at)] {
+            if time != 0 {
+                out.push_str(&format!("{} = {}\n", key, time));
+            }
+        }
+        if !e.fields.is_empty() {
+            out.push_str("[entry.fields]\n");
+            for (name, value) in &e.fields {
+                out.push_str(&format!("{} = {}\n", toml::key(name), toml::quote(value)));
+            }
+        }
+        for old in &e.history {
+            out.push_str(&format!("[[entry.history]]\npassword = {}\nreplaced_at = {}\n", toml::quote(old.password.expose()), old.replaced_at));
+        }
+    }
+    out
+}
+
+/// An entry as `--json` prints it. The password and field values only come
+/// with `secrets` (otherwise just the field names), the notes with `notes`.
+/// A note is its name, `"kind": "note"`, its tags and, with `secrets`, its body.
+/// Both carry `created_at`/`modified_at` when the store has them.
+fn entry_json(e: &Entry, secrets: bool, notes: bool) -> serde_json::Value {
+    let mut obj = if e.is_note() {
+        let mut obj = serde_json::json!({ "name": e.name, "kind": "note" });
+        if let (true, Some(body)) = (secrets, &e.body) {
+            obj["body"] = body.expose().into();
+        }
+        obj
+    } else {
+        let mut obj = serde_json::json!({ "name": e.name, "user": e.user });
+        if secrets {
+            obj["password"] = e.password.expose().into();
+        }
+        if !e.fields.is_empty() && secrets {
+            obj["fields"] = serde_json::json!(e.fields);
+        } else if !e.fields.is_empty() {
+            obj["field_names"] = serde_json::json!(e.fields.keys().collect::<Vec<_>>());
+        }
+        if let Some(url) = &e.url {
+            obj["url"] = url.as_str().into();
+        }
+        if let (true, Some(n)) = (notes, &e.notes) {
+            obj["notes"] = n.as_str().into();
+        }
+        obj
+    };
+    if !e.tags.is_empty() {
+        obj["tags"] = e.tags.clone().into();
+    }
+    if e.created_at != 0 {
+        obj["created_at"] = e.created_at.into();
+    }
+    if e.modified_at != 0 {
+        obj["modified_at"] = e.modified_at.into();
+    }
+    obj
+}
+
+/// `user:****` for a login, `[note]` for a note, as the listings show them.
+fn entry_summary(e: &Entry) -> String {
+    match e.is_note() {
+        true => "[note]".to_string(),
+        false => format!("->  {}:****", e.user),
+    }
+}
+
+/// Argon2 costs as `verify` and `fsck` print them.
+fn kdf_label(kdf: &KdfParams) -> String {
+    format!("Argon2 m={} t={} p={}", kdf.m_cost, kdf.t_cost, kdf.p_cost)
+}
+
+/// Opens the store with `key`, warning first if other users can read it.
+/// A master key typed at the prompt that turns out wrong is asked for again,
+/// up to `MASTER_KEY_ATTEMPTS` tries; the command's other input was read
+/// already and is kept. A key from stdin, the environment or the keychain
+/// gets one try.
+fn open_vault(path: &std::path::Path, key: StoreKey) -> Result<Vault, RevaultError> {
+    open_checked(path, key, |path, key| Vault::open_with_key(path, key), |v| (v.list().len(), v.trash().len()))
+}
+
+/// `open_vault` for commands that only look entries up, see `StoreIndex`.
+fn open_index(path: &std::path::Path, key: StoreKey) -> Result<StoreIndex, RevaultError> {
+    open_checked(path, key, |path, key| Vault::open_index(path, key), |i| (i.list().len(), i.trash_len()))
+}
+
+/// What `open_vault` and `open_index` share; `counts` gives the entries and
+/// trashed entries loaded.
+fn open_checked<T>(
+    path: &std::path::Path,
+    key: StoreKey,
+    open: impl Fn(&std::path::Path, StoreKey) -> Result<T, RevaultError>,
+    counts: impl Fn(&T) -> (usize, usize),
+) -> Result<T, RevaultError> {
+    if perms::is_exposed(path) {
+        eprintln!("warning: {} is accessible by other users; run 'revaultpass fix-perms'", path.display());
+    }
+    let mut tries = if MASTER_KEY_TYPED.swap(false, Ordering::Relaxed) { MASTER_KEY_ATTEMPTS } else { 1 };
+    let mut key = key;
+    loop {
+        tries -= 1;
+        let started = std::time::Instant::now();
+        let opened = open(path, key.clone());
+        if let StoreKey::Passphrase(..) = key {
+            // Nearly all of it is the Argon2 run for each slot tried.
+            info!("Argon2 and decryption took {} ms", started.elapsed().as_millis());
+        }
+        if let Ok(opened) = &opened {
+            let (entries, trash) = counts(opened);
+            info!("loaded {} entries, {} in the trash", entries, trash);
+        }
+        match (opened, key) {
+            (Err(e), StoreKey::Passphrase(_, kdf, keyfile)) if tries > 0 && matches!(e.root(), RevaultError::WrongPassphrase) => {
+                eprintln!("Wrong master key; try again.");
+                key = StoreKey::Passphrase(read_passphrase("Master key: ")?, kdf, keyfile);
+            }
+            (result, _) => return result,
+        }
+    }
+}
+
+/// The entries `list`, `get`, `names`, `delete` and `askpass` work on: the
+/// store, held under its lock, or those of an agent that serves it (see
+/// `agent::serves`), which takes the lock itself for each request.
+enum Entries {
+    Index { index: StoreIndex, _lock: fs::File },
+    Vault { vault: Box<Vault>, _lock: fs::File },
+    #[cfg(unix)]
+    Agent(Vec<Entry>),
+}
+
+impl Entries {
+    /// The entries of the store at `path`, with their secrets only if
+    /// `secrets` is set (else `load` gets them) and locked with `mode`.
+    fn open(path: &std::path::Path, secrets: bool, mode: Lock) -> Result<Entries, Box<dyn std::error::Error + Send + Sync>> {
+        #[cfg(unix)]
+        if agent::serves(path, true)? {
+            debug!("entries from the agent");
+            return Ok(Entries::Agent(agent::entries(secrets)?));
+        }
+        let key = unlock_key(path)?;
+        let lock = lock_store(path, mode)?;
+        Ok(match secrets || matches!(mode, Lock::Exclusive) {
+            true => Entries::Vault { vault: Box::new(open_vault(path, key)?), _lock: lock },
+            false => Entries::Index { index: open_index(path, key)?, _lock: lock },
+        })
+    }
+
+    fn list(&self) -> &[Entry] {
+        match self {
+            Entries::Index { index, .. } => index.list(),
+            Entries::Vault { vault, .. } => vault.list(),
+            #[cfg(unix)]
+            Entries::Agent(entries) => entries,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&Entry> {
+        self.list().iter().find(|e| revaultpass::names::same(&e.name, name))
+    }
+
+    /// `head`, one of `list`, with its secrets.
+    fn load(&self, head: &Entry) -> Result<Entry, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Entries::Index { index, .. } => Ok(index.load(head)?),
+            Entries::Vault { .. } => Ok(head.clone()),
+            #[cfg(unix)]
+            Entries::Agent(_) => agent::get(&head.name),
+        }
+    }
+
+    /// Moves the entries named `names` to the trash and saves. Only for
+    /// entries opened with `Lock::Exclusive`.
+    fn delete(&mut self, names: &[String]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Entries::Index { .. } => unreachable!("delete opens the store with Lock::Exclusive"),
+            Entries::Vault { vault, .. } => {
+                for name in names {
+                    vault.remove(name)?;
+                }
+                vault.save()?;
+            }
+            #[cfg(unix)]
+            Entries::Agent(_) => agent::delete(names)?,
+        }
+        Ok(())
+    }
+}
+
+/// Adds `entry` to the store at `path` and saves, through the agent if it
+/// serves the store.
+fn add_entry(path: &std::path::Path, entry: Entry) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    #[cfg(unix)]
+    if agent::serves(path, true)? {
+        return agent::add(&entry);
+    }
+    let key = unlock_key(path)?;
+    let _lock = lock_store(path, Lock::Exclusive)?;
+    let mut vault = open_vault(path, key)?;
+    vault.add(entry)?;
+    vault.save()?;
+    Ok(())
+}
+
+/// `--keyfile`, else `$REVAULTPASS_KEYFILE`, else `keyfile` from the config file.
+fn keyfile_path() -> Option<PathBuf> {
+    let flag = KEYFILE.lock().unwrap_or_else(std::sync::PoisonError::into_inner).clone();
+    flag.or_else(|| std::env::var_os("REVAULTPASS_KEYFILE").filter(|p| !p.is_empty()).map(PathBuf::from))
+        .or_else(|| config::get().keyfile.clone())
+}
+
+fn read_keyfile(path: &std::path::Path) -> Result<Keyfile, RevaultError> {
+    Keyfile::read(path).map_err(|e| RevaultError::Io(io::Error::new(e.kind(), format!("keyfile {}: {}", path.display(), e))))
+}
+
+/// The keyfile for an encrypted store: required if its header says every
+/// passphrase needs one, read if given and some slot can use it, else `None`.
+fn store_keyfile(data: &[u8]) -> Result<Option<Keyfile>, RevaultError> {
+    if !accepts_keyfile(data) {
+        return Ok(None);
+    }
+    match keyfile_path() {
+        Some(path) => read_keyfile(&path).map(Some),
+        None if requires_keyfile(data) => Err(RevaultError::KeyMismatch("this store requires a keyfile; pass --keyfile <path>")),
+        None => Ok(None),
+    }
+}
+
+/// The key for an encrypted store from its master key: KDF parameters from
+/// the header, plus the keyfile if the header asks for one.
+fn passphrase_key(data: &[u8], pass: Zeroizing<String>) -> Result<StoreKey, RevaultError> {
+    Ok(StoreKey::Passphrase(pass, KdfParams::from_header(data), store_keyfile(data)?))
+}
+
+/// Prompts for the master key only when the store is actually encrypted.
+fn unlock_key(path: &std::path::Path) -> Result<StoreKey, RevaultError> {
+    match store_kind(path)? {
+        StoreKind::Encrypted => {
+            let data = fs::read(path)?;
+            // Checked first, so a missing keyfile is reported before any prompt.
+            let keyfile = store_keyfile(&data)?;
+            let with = |pass| StoreKey::Passphrase(pass, KdfParams::from_header(&data), keyfile.clone());
+            info!("format {}, {}", format_label(&data).unwrap_or_default(), kdf_label(&KdfParams::from_header(&data)));
+            if let Some(key) = keychain_lookup(path).filter(|_| !master_key_given()) {
+                debug!("master key from the keychain");
+                return Ok(with(key));
+            }
+            MASTER_KEY_TYPED.store(!master_key_given(), Ordering::Relaxed);
+            debug!("master key from {}", master_key_source());
+            Ok(with(read_master_key("Master key: ")?))
+        }
+        StoreKind::Plain => {
+            info!("format RVP0, unencrypted");
+            Ok(StoreKey::Plain)
+        }
+        StoreKind::Missing => Ok(StoreKey::Plain),
+        StoreKind::Truncated => Err(RevaultError::corrupt("store file is truncated").at(path)),
+        StoreKind::Unknown => Err(RevaultError::corrupt("store file is corrupt: unrecognized magic").at(path)),
+    }
+}
+
+/// Subcommands whose first argument is an existing entry name.
+const ENTRY_COMMANDS: [&str; 10] = ["get", "exec", "delete", "rm", "edit", "regenerate", "rename", "duplicate", "history", "otp"];
+
+const BASH_ENTRIES: &str = r#"
+_revaultpass_entries() {
+    local i sub="" store=() cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            --store) store=(--store "${COMP_WORDS[i+1]}"); ((i++)) ;;
+            -*) ;;
+            *) [[ -z "$sub" ]] && sub="${COMP_WORDS[i]}" ;;
+        esac
+    done
+    case "$sub" in
+        get|exec|delete|rm|edit|regenerate|rename|duplicate|history|otp)
+            case "$prev" in
+                --store|--user|--password|--timeout|--length) ;;
+                *)
+                    if [[ "$cur" != -* ]]; then
+                        local IFS=$'\n'
+                        COMPREPLY=( $(compgen -W "$(revaultpass "${store[@]}" _complete-names 2>/dev/null)" -- "$cur") )
+                        return 0
+                    fi
+                    ;;
+            esac
+            ;;
+    esac
+    _revaultpass "$@"
+}
+complete -F _revaultpass_entries -o bashdefault -o default revaultpass
+"#;
+
+const ZSH_ENTRIES: &str = r#"(( $+functions[_revaultpass__entries] )) ||
+_revaultpass__entries() {
+    local -a store names
+    local i
+    for ((i = 1; i < $#words; i++)); do
+        [[ $words[i] == --store ]] && store=(--store "$words[i+1]")
+    done
+    names=(${(f)"$(revaultpass $store _complete-names 2>/dev/null)"})
+    compadd -a names
+}
+
+"#;
+
+const FISH_ENTRIES: &str = r#"
+function __fish_revaultpass_entries
+    set -l tokens (commandline -opc)
+    set -l store
+    for i in (seq (count $tokens))
+        if test "$tokens[$i]" = --store; and test $i -lt (count $tokens)
+            set store --store $tokens[(math $i + 1)]
+        end
+    end
+    revaultpass $store _complete-names 2>/dev/null
+end
+complete -c revaultpass -n "__fish_seen_subcommand_from get exec delete rm edit regenerate rename duplicate history otp" -f -a "(__fish_revaultpass_entries)"
+"#;
+
+const POWERSHELL_STORE_ARGS: &str = r#"
+    $storeArgs = @(for ($i = 1; $i -lt $commandElements.Count - 1; $i++) {
+        if ($commandElements[$i].Extent.Text -eq '--store') { '--store'; $commandElements[$i + 1].Extent.Text }
+    })"#;
+
+const POWERSHELL_ENTRIES: &str = "
+            revaultpass @storeArgs _complete-names 2>$null | ForEach-Object {
+                [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_)
+            }";
+
+/// Adds entry-name completion and install instructions to the script clap
+/// generates. Names come from `_complete-names`, which stays silent for
+/// encrypted stores so <Tab> never prompts.
+fn completion_script(shell: Shell, script: &str) -> String {
+    match shell {
+        Shell::Bash => format!(
+            "# Load in the current shell with: source <(revaultpass completions bash)\n\
+             # or install it: revaultpass completions bash > ~/.local/share/bash-completion/completions/revaultpass\n{}{}",
+            script, BASH_ENTRIES
+        ),
+        Shell::Zsh => {
+            // `#compdef` has to stay on the first line.
+            let (compdef, rest) = script.split_once('\n').unwrap_or((script, ""));
+            let rest = rest
+                .replace("':entry:_default'", "':entry:_revaultpass__entries'")
+                .replace("\nif [ \"$funcstack[1]\" = \"_revaultpass\" ]", &format!("\n{}if [ \"$funcstack[1]\" = \"_revaultpass\" ]", ZSH_ENTRIES));
+            format!(
+                "{}\n# Install: revaultpass completions zsh > ~/.zfunc/_revaultpass\n\
+                 # with fpath=(~/.zfunc $fpath) before compinit in ~/.zshrc.\n{}",
+                compdef, rest
+            )
+        }
+        Shell::Fish => format!(
+            "# Install: revaultpass completions fish > ~/.config/fish/completions/revaultpass.fish\n{}{}",
+            script, FISH_ENTRIES
+        ),
+        Shell::PowerShell => {
+            let mut script = script.replace(
+                "    $commandElements = $commandAst.CommandElements",
+                &format!("    $commandElements = $commandAst.CommandElements{}", POWERSHELL_STORE_ARGS),
+            );
+            for cmd in ENTRY_COMMANDS {
+                let block = format!("        'revaultpass;{}' {{", cmd);
+                script = script.replace(&block, &format!("{}{}", block, POWERSHELL_ENTRIES));
+            }
+            format!(
+                "# Add to your profile ($PROFILE):\n\
+                 #   revaultpass completions powershell | Out-String | Invoke-Expression\n{}",
+                script
+            )
+        }
+        _ => script.to_string(),
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Field {
+    Name,
+    User,
+    Password,
+    Url,
+    Notes,
+}
+
+impl Field {
+    fn of(self, e: &Entry) -> &str {
+        match self {
+            Field::Name => &e.name,
+            Field::User => &e.user,
+            Field::Password => e.password.expose(),
+            Field::Url => e.url.as_deref().unwrap_or_default(),
+            Field::Notes => e.notes.as_deref().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum CredentialAction {
+    Get,
+    Store,
+    Erase,
+}
+
+/// What git sends a credential helper: `key=value` lines up to a blank line.
+fn read_credential_request() -> io::Result<std::collections::BTreeMap<String, Zeroizing<String>>> {
+    let mut request = std::collections::BTreeMap::new();
+    for line in io::stdin().lines() {
+        let line = Zeroizing::new(line?);
+        if line.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            request.insert(key.to_string(), Zeroizing::new(value.to_string()));
+        }
+    }
+    Ok(request)
+}
+
+/// The entry name for a git credential: `pattern` with `{protocol}`, `{host}`,
+/// `{path}` and `{username}` filled in from the request.
+fn credential_entry_name(
+    pattern: &str,
+    request: &std::collections::BTreeMap<String, Zeroizing<String>>,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let mut name = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| format!("unclosed '{{' in pattern '{}'", pattern))? + start;
+        let key = &rest[start + 1..end];
+        if !["protocol", "host", "path", "username"].contains(&key) {
+            return Err(format!("unknown placeholder '{{{}}}' in pattern '{}'", key, pattern).into());
+        }
+        name.push_str(request.get(key).map(|v| v.as_str()).unwrap_or_default());
+        rest = &rest[end + 1..];
+    }
+    name.push_str(rest);
+    Ok(parse_entry_name(name.trim_end_matches('/'))?)
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum SortKey {
+    Name,
+    Created,
+    Modified,
+}
+
+enum TemplatePiece {
+    Text(String),
+    Field(Field),
+}
+
+/// Splits a `get --format` or `type --sequence` template (`flag` names it in
+/// errors) into literal text and `{name}`, `{user}`, `{password}`, `{url}` and
+/// `{notes}` placeholders; `{{` and `}}` are literal braces.
+fn parse_template(template: &str, flag: &str) -> Result<Vec<TemplatePiece>, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("{}: unclosed '{{{}'", flag, name)),
+                    }
+                }
+                let field = match name.as_str() {
+                    "name" => Field::Name,
+                    "user" => Field::User,
+                    "password" => Field::Password,
+                    "url" => Field::Url,
+                    "notes" => Field::Notes,
+                    _ => return Err(format!("{}: unknown placeholder {{{}}} (expected {{name}}, {{user}}, {{password}}, {{url}} or {{notes}})", flag, name)),
+                };
+                pieces.push(TemplatePiece::Text(std::mem::take(&mut text)));
+                pieces.push(TemplatePiece::Field(field));
+            }
+            '}' => return Err(format!("{}: unmatched '}}' (write '}}}}' for a literal brace)", flag)),
+            c => text.push(c),
+        }
+    }
+    pieces.push(TemplatePiece::Text(text));
+    Ok(pieces)
+}
+
+fn render_template(pieces: &[TemplatePiece], e: &Entry) -> Zeroizing<String> {
+    let mut out = Zeroizing::new(String::new());
+    for piece in pieces {
+        match piece {
+            TemplatePiece::Text(t) => out.push_str(t),
+            TemplatePiece::Field(f) => out.push_str(f.of(e)),
+        }
+    }
+    out
+}
+
+/// Checks `--totp` up front so a typo doesn't surface only at `otp` time.
+fn parse_totp(text: &str) -> Result<String, String> {
+    if !text.is_empty() {
+        revaultpass::totp::Totp::parse(text)?;
+    }
+    Ok(text.to_string())
+}
+
+/// Slot labels live in the header with a one-byte length.
+fn parse_slot_label(text: &str) -> Result<String, String> {
+    if text.len() > u8::MAX as usize {
+        return Err(format!("at most {} bytes", u8::MAX));
+    }
+    Ok(text.to_string())
+}
+
+/// Entry names use `/` to form groups such as `work/aws/prod`: repeated
+/// slashes collapse to one, and a trailing slash (a group, not an entry) is refused.
+/// Before that the name is trimmed and put in NFC, and control characters
+/// are refused (see `names::normalize`).
+fn parse_entry_name(text: &str) -> Result<String, String> {
+    let text = &revaultpass::names::normalize(text)?;
+    if text.ends_with('/') {
+        return Err(format!("'{}' ends with '/', which names a group rather than an entry", text));
+    }
+    let mut name = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !(c == '/' && name.ends_with('/')) {
+            name.push(c);
+        }
+    }
+    Ok(name)
+}
+
+/// `exec --env VAR` or `--env VAR=entry`.
+#[derive(Clone)]
+struct EnvMapping {
+    var: String,
+    entry: Option<String>,
+}
+
+fn parse_env_mapping(text: &str) -> Result<EnvMapping, String> {
+    let (var, entry) = match text.split_once('=') {
+        Some((var, entry)) if !entry.is_empty() => (var, Some(entry.to_string())),
+        Some(_) => return Err(format!("'{}' has no entry name after '='", text)),
+        None => (text, None),
+    };
+    let valid = var.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && var.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!("'{}' is not a valid environment variable name", var));
+    }
+    Ok(EnvMapping { var: var.to_string(), entry })
+}
+
+/// Prints entries as an indented tree of their `/`-separated groups, sorted by name.
+fn print_tree(entries: &[&Entry]) {
+    let mut sorted = entries.to_vec();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut groups: Vec<&str> = Vec::new();
+    for e in sorted {
+        let mut parts: Vec<&str> = e.name.split('/').collect();
+        let leaf = parts.pop().unwrap_or_default();
+        let shared = groups.iter().zip(&parts).take_while(|(open, part)| open == part).count();
+        groups.truncate(shared);
+        for group in &parts[shared..] {
+            println!("{}{}/", "  ".repeat(groups.len() + 1), group);
+            groups.push(group);
+        }
+        let indent = "  ".repeat(groups.len() + 1);
+        match e.tags.is_empty() {
+            true => println!("{}{}  {}", indent, leaf, entry_summary(e)),
+            false => println!("{}{}  {}  [{}]", indent, leaf, entry_summary(e), e.tags.join(", ")),
+        }
+    }
+}
+
+/// Set by `--no-color`.
+static NO_COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Whether output may carry ANSI styles: stdout is a terminal, and neither
+/// `--no-color` nor a non-empty `NO_COLOR` says otherwise.
+fn use_color() -> bool {
+    !NO_COLOR.load(Ordering::Relaxed)
+        && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        && io::stdout().is_terminal()
+}
+
+/// `text` in an ANSI style (`1` bold, `2` dim), or unchanged when `on` is false.
+fn ansi(on: bool, style: &str, text: &str) -> String {
+    match on && !text.is_empty() {
+        true => format!("\x1b[{}m{}\x1b[0m", style, text),
+        false => text.to_string(),
+    }
+}
+
+/// Columns `c` takes up in a terminal: two for East Asian wide and fullwidth
+/// characters and most emoji, none for combining marks and joiners.
+fn char_width(c: char) -> usize {
+    const ZERO: [(u32, u32); 9] = [
+        (0x0300, 0x036F), (0x0483, 0x0489), (0x0591, 0x05BD), (0x1AB0, 0x1AFF), (0x1DC0, 0x1DFF),
+        (0x200B, 0x200F), (0x20D0, 0x20FF), (0xFE00, 0xFE0F), (0xFE20, 0xFE2F),
+    ];
+    const WIDE: [(u32, u32); 16] = [
+        (0x1100, 0x115F), (0x2E80, 0x303E), (0x3041, 0x33FF), (0x3400, 0x4DBF), (0x4E00, 0x9FFF),
+        (0xA000, 0xA4CF), (0xAC00, 0xD7A3), (0xF900, 0xFAFF), (0xFE30, 0xFE4F), (0xFF00, 0xFF60),
+        (0xFFE0, 0xFFE6), (0x1F300, 0x1F64F), (0x1F680, 0x1F6FF), (0x1F900, 0x1F9FF), (0x20000, 0x2FFFD),
+        (0x30000, 0x3FFFD),
+    ];
+    let c = c as u32;
+    let within = |ranges: &[(u32, u32)]| ranges.iter().any(|&(lo, hi)| (lo..=hi).contains(&c));
+    if within(&ZERO) {
+        0
+    } else if within(&WIDE) {
+        2
+    } else {
+        1
+    }
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Prints entries as an aligned table: name and user, tags when any entry has
+/// some, and with `long` the times plus URLs and extra field names under each
+/// row. Names are bold with their group dimmed, when `use_color` allows.
+fn print_list_table(entries: &[&Entry], long: bool) {
+    let color = use_color();
+    let tags = entries.iter().any(|e| !e.tags.is_empty());
+    // Stores from before timestamps were kept have 0 for them.
+    let when = |ts: u64| if ts == 0 { "-".to_string() } else { format_local_time(ts) };
+    let mut header = vec!["NAME", "USER"];
+    if tags {
+        header.push("TAGS");
+    }
+    if long {
+        header.extend(["CREATED", "MODIFIED"]);
+    }
+    let rows: Vec<Vec<String>> = entries
+        .iter()
+        .map(|e| {
+            let user = if e.is_note() { "[note]".to_string() } else { e.user.clone() };
+            let mut row = vec![e.name.clone(), user];
+            if tags {
+                row.push(e.tags.join(", "));
+            }
+            if long {
+                row.extend([when(e.created_at), when(e.modified_at)]);
+            }
+            row
+        })
+        .collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| rows.iter().map(|r| display_width(&r[i])).chain([header[i].len()]).max().unwrap_or(0))
+        .collect();
+    let line = |cells: Vec<(String, usize)>| {
+        let mut line = String::from(" ");
+        for ((shown, width), column) in cells.into_iter().zip(&widths) {
+            line.push(' ');
+            line.push_str(&shown);
+            line.extend(std::iter::repeat_n(' ', column.saturating_sub(width) + 1));
+        }
+        line.trim_end().to_string()
+    };
+    println!("{}", line(header.iter().map(|h| (h.to_string(), h.len())).collect()));
+    for (e, row) in entries.iter().zip(rows) {
+        let cells = row
+            .into_iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                let width = display_width(&cell);
+                match (i, cell.rfind('/')) {
+                    (0, Some(slash)) => (format!("{}{}", ansi(color, "2", &cell[..=slash]), ansi(color, "1", &cell[slash + 1..])), width),
+                    (0, None) => (ansi(color, "1", &cell), width),
+                    _ => (cell, width),
+                }
+            })
+            .collect();
+        println!("{}", line(cells));
+        if long {
+            if let Some(url) = &e.url {
+                println!("      url: {}", url);
+            }
+            if !e.fields.is_empty() {
+                println!("      fields: {}", e.fields.keys().cloned().collect::<Vec<_>>().join(", "));
+            }
+        }
+    }
+}
+
+/// What `grep` looks through in an entry, labelled as it prints them: the
+/// name, user, URL, notes, a note's body, each tag and each extra field as
+/// `fields.<name>`. Passwords, earlier passwords and the TOTP seed only with
+/// `secrets`.
+fn grep_targets(e: &Entry, secrets: bool) -> Vec<(String, &str)> {
+    let mut targets = vec![("name".to_string(), e.name.as_str())];
+    if !e.user.is_empty() {
+        targets.push(("user".into(), &e.user));
+    }
+    if let Some(url) = &e.url {
+        targets.push(("url".into(), url));
+    }
+    if let Some(notes) = &e.notes {
+        targets.push(("notes".into(), notes));
+    }
+    if let Some(body) = &e.body {
+        targets.push(("body".into(), body.expose()));
+    }
+    targets.extend(e.tags.iter().map(|t| ("tags".to_string(), t.as_str())));
+    targets.extend(e.fields.iter().map(|(k, v)| (format!("fields.{}", k), v.as_str())));
+    if secrets {
+        if !e.is_note() {
+            targets.push(("password".into(), e.password.expose()));
+        }
+        targets.extend(e.history.iter().map(|h| ("history".to_string(), h.password.expose())));
+        if let Some(totp) = &e.totp {
+            targets.push(("totp".into(), totp.expose()));
+        }
+    }
+    targets
+}
+
+/// `line` with its matches set off in bold red when `color`. Control
+/// characters, as in a note pasted from a binary file, are shown escaped
+/// rather than sent to the terminal.
+fn highlight_matches(line: &str, re: &regex::Regex, color: bool) -> String {
+    let escape = |text: &str| -> String {
+        text.chars()
+            .map(|c| match c {
+                '\t' => c.to_string(),
+                c if c.is_control() => c.escape_unicode().to_string(),
+                c => c.to_string(),
+            })
+            .collect()
+    };
+    let mut out = String::new();
+    let mut last = 0;
+    for m in re.find_iter(line).filter(|m| !m.is_empty()) {
+        out.push_str(&escape(&line[last..m.start()]));
+        out.push_str(&ansi(color, "1;31", &escape(m.as_str())));
+        last = m.end();
+    }
+    out.push_str(&escape(&line[last..]));
+    out
+}
+
+/// Parses `--field NAME=VALUE`; the name can't be empty or contain `=`.
+fn parse_custom_field(text: &str) -> Result<(String, String), String> {
+    match text.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), value.to_string())),
+        Some(_) => Err("field name must not be empty".into()),
+        None => Err(format!("expected NAME=VALUE, got '{}'", text)),
+    }
+}
+
+/// Parses an age like `30d` into seconds; `s`, `m`, `h`, `d` and `w` suffixes.
+fn parse_age(text: &str) -> Result<u64, String> {
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => return Err(format!("expected a number with s, m, h, d or w, e.g. 30d; got '{}'", text)),
+    };
+    let n: u64 = number.parse().map_err(|_| format!("expected a number with s, m, h, d or w, e.g. 30d; got '{}'", text))?;
+    Ok(n.saturating_mul(secs))
+}
+
+/// `audit` and `pwned` exit with this when they found anything, so a cron job
+/// can mail the report.
+const FINDINGS_EXIT: i32 = 7;
+/// `verify`: the store decrypts, but what's inside isn't a valid store.
+const INVALID_CONTENTS_EXIT: i32 = 9;
+/// `self-test`: a check failed.
+const SELF_TEST_EXIT: i32 = 11;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    High,
+    Medium,
+    Low,
+}
+
+impl Severity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Severity::High => "high",
+            Severity::Medium => "medium",
+            Severity::Low => "low",
+        }
+    }
+}
+
+/// One problem found by `audit`, about one entry or (for reuse) several.
+struct Finding {
+    severity: Severity,
+    kind: &'static str,
+    names: Vec<String>,
+    detail: Option<String>,
+}
+
+/// Checks every login's password; notes and entries without a password are
+/// skipped. Findings come out ordered by severity, most severe first.
+fn audit_entries(entries: &[Entry], min_length: usize, max_age_days: u64, now: u64) -> Vec<Finding> {
+    let logins: Vec<&Entry> = entries.iter().filter(|e| !e.is_note() && !e.password.is_empty()).collect();
+    let mut findings = Vec::new();
+    let mut by_password: std::collections::BTreeMap<&str, Vec<String>> = Default::default();
+    for e in &logins {
+        by_password.entry(e.password.expose()).or_default().push(e.name.clone());
+    }
+    for names in by_password.into_values().filter(|n| n.len() > 1) {
+        findings.push(Finding { severity: Severity::High, kind: "reused", names, detail: None });
+    }
+    for e in &logins {
+        let password = e.password.expose();
+        let weak = |kind, detail| Finding { severity: Severity::Medium, kind, names: vec![e.name.clone()], detail };
+        let length = password.chars().count();
+        if length < min_length {
+            findings.push(weak("short", Some(format!("{} characters, minimum {}", length, min_length))));
+        }
+        if password.chars().all(|c| c.is_ascii_digit()) {
+            findings.push(weak("digits-only", None));
+        } else if password.chars().all(char::is_lowercase) {
+            findings.push(weak("lowercase-only", None));
+        }
+        let strength = revaultpass::strength::estimate(password);
+        if strength.score < 2 {
+            let reason = strength.warning.map(|w| format!(": {}", w)).unwrap_or_default();
+            findings.push(weak("low-score", Some(format!("{}/4, {}{}", strength.score, strength.label(), reason))));
+        }
+        // Entries from before timestamps were kept have no known age.
+        let changed = e.password_changed_at();
+        let age_days = now.saturating_sub(changed) / 86400;
+        if changed != 0 && age_days > max_age_days {
+            findings.push(Finding {
+                severity: Severity::Low,
+                kind: "stale",
+                names: vec![e.name.clone()],
+                detail: Some(format!("unchanged for {} days, maximum {}", age_days, max_age_days)),
+            });
+        }
+    }
+    findings.sort_by_key(|f| f.severity);
+    findings
+}
+
+const PWNED_API: &str = "https://api.pwnedpasswords.com/range/";
+/// Pause between API requests when checking every entry.
+const PWNED_DELAY_MS: u64 = 200;
+
+/// The `SUFFIX:COUNT` lines for one five-digit SHA-1 prefix: from
+/// `<dir>/<PREFIX>.txt` (the layout the official downloader writes) when a
+/// directory is given, else from the Pwned Passwords API via `curl`, which
+/// honours `HTTPS_PROXY`. Only the prefix ever leaves the machine.
+fn pwned_range(prefix: &str, dir: Option<&std::path::Path>) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(dir) = dir {
+        let path = dir.join(format!("{}.txt", prefix));
+        return fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e).into());
+    }
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--connect-timeout", "10", "--max-time", "30"])
+        // Padding hides the real number of matches for a prefix from anyone watching the traffic.
+        .args(["--header", "Add-Padding: true", "--user-agent", concat!("revaultpass/", env!("CARGO_PKG_VERSION"))])
+        .arg(format!("{}{}", PWNED_API, prefix))
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("network unavailable: could not run curl ({})", e))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match output.status.code() {
+        Some(0) => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+        // Proxy or host lookup failed, no connection, timed out.
+        Some(5 | 6 | 7 | 28) => Err(format!("network unavailable: {}", stderr.trim().trim_start_matches("curl: ")).into()),
+        _ => Err(format!("Pwned Passwords request failed: {}", stderr.trim().trim_start_matches("curl: ")).into()),
+    }
+}
+
+/// How often `password` appears in the Pwned Passwords data (0 if never).
+/// Ranges already fetched are kept in `ranges`.
+fn pwned_count(
+    password: &str,
+    dir: Option<&std::path::Path>,
+    ranges: &mut std::collections::HashMap<String, String>,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    use sha1::{Digest, Sha1};
+    let hash: String = Sha1::digest(password.as_bytes()).iter().map(|b| format!("{:02X}", b)).collect();
+    let (prefix, suffix) = hash.split_at(5);
+    if !ranges.contains_key(prefix) {
+        if dir.is_none() && !ranges.is_empty() {
+            std::thread::sleep(std::time::Duration::from_millis(PWNED_DELAY_MS));
+        }
+        ranges.insert(prefix.to_string(), pwned_range(prefix, dir)?);
+    }
+    let count = ranges[prefix]
+        .lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(s, _)| s.eq_ignore_ascii_case(suffix))
+        .and_then(|(_, count)| count.trim().parse().ok())
+        .unwrap_or(0);
+    Ok(count)
+}
+
+/// `YYYY-MM-DD HH:MM:SS` in UTC, without pulling in a date library.
+fn format_unix_time(ts: u64) -> String {
+    let (days, secs) = ((ts / 86400) as i64, ts % 86400);
+    // Civil-from-days (Howard Hinnant).
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// `format_unix_time` in the local time zone, or in UTC where that's unknown.
+fn format_local_time(ts: u64) -> String {
+    format_unix_time(ts.saturating_add_signed(local_utc_offset(ts)))
+}
+
+/// Seconds east of UTC at `ts`, from the C library's time zone data.
+#[cfg(unix)]
+fn local_utc_offset(ts: u64) -> i64 {
+    let t = ts as libc::time_t;
+    // SAFETY: `localtime_r` only reads `t` and writes `tm`; an all-zero `tm` is valid.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+#[cfg(not(unix))]
+fn local_utc_offset(_ts: u64) -> i64 {
+    0
+}
+
+/// Shown by `--version`: the crate version and the store formats it reads.
+const VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "\nstore formats: writes RVP2 v12; reads RVP0 (unencrypted), RVP1 (legacy and v1), RVP2 v2-v12"
+);
+
+const MASTER_KEY_HELP: &str = "Master key precedence: --passphrase-stdin, then $REVAULTPASS_PASSPHRASE, then prompt.
+An empty key from any source means the store is unencrypted.";
+
+/// RevaultPass - password manager (user:password)
+#[derive(Parser)]
+#[command(name = "revaultpass", version = VERSION, after_help = MASTER_KEY_HELP)]
+struct Cli {
+    /// Use this store file (default: $REVAULTPASS_STORE, then the data directory)
+    #[arg(long, global = true, value_name = "PATH")]
+    store: Option<std::ffi::OsString>,
+    /// Read the master key from the first line of stdin
+    #[arg(long, global = true)]
+    passphrase_stdin: bool,
+    /// Keyfile for a store that needs one; with init, make the new store need it (default: $REVAULTPASS_KEYFILE)
+    #[arg(long, global = true, value_name = "PATH")]
+    keyfile: Option<PathBuf>,
+    /// Print list and get output, and errors, as JSON
+    #[arg(long, global = true)]
+    json: bool,
+    /// Open a signed store even if it doesn't match its signature
+    #[arg(long, global = true)]
+    ignore_signature: bool,
+    /// Ask for passphrases with pinentry (the program from the config file, else `pinentry`)
+    #[arg(long, global = true)]
+    pinentry: bool,
+    /// Don't color output (also when $NO_COLOR is set or stdout isn't a terminal)
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Don't print status messages such as "Saved."; output, warnings and errors still show
+    #[arg(long, short, global = true)]
+    quiet: bool,
+    /// Refuse every command that would change the store (also when $REVAULTPASS_READONLY is set)
+    #[arg(long, global = true)]
+    read_only: bool,
+    /// Explain on stderr what the command does (-vv for more); never shows secrets
+    #[arg(long, short, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Args)]
+struct KdfArgs {
+    /// Argon2 memory cost in KiB (default 19456)
+    #[arg(long, value_name = "KiB")]
+    kdf_mem: Option<u32>,
+    /// Argon2 iterations (default 2)
+    #[arg(long, value_name = "N")]
+    kdf_iters: Option<u32>,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Create the store and set the master key (recommended)
+    Init {
+        #[command(flatten)]
+        kdf: KdfArgs,
+        /// Also make a recovery key that opens the store without the master key (shown once)
+        #[arg(long)]
+        recovery_key: bool,
+        /// Encrypt the contents with xchacha20poly1305 (default), chacha20poly1305 or aes256gcm
+        #[arg(long, value_name = "CIPHER")]
+        cipher: Option<Cipher>,
+        /// Replace an existing store, which is kept as <store>.replaced-<unix-ts>
+        #[arg(long)]
+        force: bool,
+    },
+    /// Add an entry
+    Add {
+        #[arg(value_parser = parse_entry_name, required_unless_present = "batch")]
+        name: Option<String>,
+        #[arg(required_unless_present = "batch")]
+        user: Option<String>,
+        /// Deprecated: visible in shell history and `ps`; prompted for (twice) when omitted
+        password: Option<String>,
+        /// Read the password from the first line of stdin (the second with --passphrase-stdin)
+        #[arg(long, conflicts_with_all = ["password", "generate"])]
+        password_stdin: bool,
+        /// Accept an empty password
+        #[arg(long, conflicts_with = "generate")]
+        allow_empty: bool,
+        /// Use a random password (printed once), LEN characters long
+        #[arg(long, value_name = "LEN", num_args = 0..=1, require_equals = true)]
+        generate: Option<Option<usize>>,
+        /// Copy the generated password to the clipboard instead of printing it
+        #[arg(long)]
+        copy: bool,
+        /// Free-form notes, e.g. a recovery email or security questions
+        #[arg(long)]
+        notes: Option<String>,
+        /// Login page
+        #[arg(long)]
+        url: Option<String>,
+        /// Tag the entry (repeat for more than one)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Store an extra field (repeat for more than one)
+        #[arg(long = "field", value_name = "NAME=VALUE", value_parser = parse_custom_field)]
+        fields: Vec<(String, String)>,
+        /// TOTP secret (base32) or otpauth:// URI, for the otp command
+        #[arg(long, value_name = "SECRET", value_parser = parse_totp)]
+        totp: Option<String>,
+        /// Refuse a typed or given password scoring below SCORE (0-4, default 3)
+        #[arg(long, value_name = "SCORE", num_args = 0..=1, require_equals = true, value_parser = clap::value_parser!(u8).range(0..=4))]
+        enforce_policy: Option<Option<u8>>,
+        /// Add the entries on stdin instead, one JSON object per line with name, user, password and optionally url, notes, tags, fields and totp
+        #[arg(long, conflicts_with_all = ["name", "user", "password", "password_stdin", "generate", "copy", "notes", "url", "tags", "fields", "totp"])]
+        batch: bool,
+        /// With --batch: skip, overwrite or rename entries whose names are taken (default skip)
+        #[arg(long)]
+        on_conflict: Option<OnConflict>,
+    },
+    /// Add a secure note, read from stdin or written in $EDITOR
+    AddNote {
+        #[arg(value_parser = parse_entry_name)]
+        name: String,
+        /// Tag the note (repeat for more than one)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+    /// List names (user:****)
+    #[command(visible_alias = "ls")]
+    List {
+        /// Only entries in this group, e.g. 'work/' for everything named work/...
+        group: Option<String>,
+        /// Print the `/`-separated groups as an indented tree
+        #[arg(long, conflicts_with = "long")]
+        tree: bool,
+        /// Only entries with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Add creation and modification times to the table, plus URLs and the names of extra fields
+        #[arg(short, long)]
+        long: bool,
+        /// The untabled `  name  ->  user:****` lines of earlier versions, without color
+        #[arg(long, conflicts_with_all = ["tree", "long"])]
+        plain: bool,
+        /// Order by name, or newest first by creation or modification time (default: store order)
+        #[arg(long, value_enum)]
+        sort: Option<SortKey>,
+        /// Include passwords in --json output
+        #[arg(long)]
+        show_passwords: bool,
+    },
+    /// Print every entry name, one per line, for menus such as dmenu, rofi or fzf
+    Names {
+        /// Nothing but the names: no warnings, and never JSON
+        #[arg(long)]
+        plain: bool,
+    },
+    /// Print user:password, or a note's body (falls back to prefix/substring match unless --exact)
+    Get {
+        /// Entry name, or - to read it from stdin; several names print one line each
+        #[arg(required_unless_present = "from_file")]
+        entry: Vec<String>,
+        /// Also get the names in FILE, one per line (- for stdin)
+        #[arg(long, value_name = "FILE")]
+        from_file: Option<PathBuf>,
+        /// With several names, print nothing if any is missing
+        #[arg(long)]
+        strict: bool,
+        /// Copy the password to the clipboard instead
+        #[arg(short = 'c', long, visible_alias = "clip")]
+        copy: bool,
+        /// With --copy, set the terminal's clipboard with OSC 52 (the local one over SSH)
+        #[arg(long, requires = "copy")]
+        osc52: bool,
+        /// Print no prompt or status messages; fail instead of printing when copying fails
+        #[arg(long)]
+        no_prompt_echo: bool,
+        /// Clear the clipboard after this many seconds (0 keeps it; default 30)
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+        /// Only accept an exact name
+        #[arg(long)]
+        exact: bool,
+        /// Also print the notes
+        #[arg(long)]
+        full: bool,
+        /// Print just this field, undecorated
+        #[arg(long, value_enum, conflicts_with_all = ["copy", "format"])]
+        field: Option<Field>,
+        /// Print through a template such as '{user}@{name}: {password}' ({{ and }} for literal braces)
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "copy")]
+        format: Option<String>,
+        /// Print just the value of this extra field
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["copy", "field", "format"])]
+        field_name: Option<String>,
+    },
+    /// Run a command with passwords in its environment
+    #[command(after_help = "Example: revaultpass exec db --env PGPASSWORD -- psql -U app")]
+    Exec {
+        /// Entry for the --env options that don't name one
+        entry: Option<String>,
+        /// Set VAR to the entry's password; VAR=ENTRY takes it from another entry (repeatable)
+        #[arg(long = "env", value_name = "VAR[=ENTRY]", required = true, value_parser = parse_env_mapping)]
+        env: Vec<EnvMapping>,
+        /// Also set VAR_USER to the entry's user name
+        #[arg(long)]
+        with_user: bool,
+        /// The command and its arguments
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<std::ffi::OsString>,
+    },
+    /// Git credential helper: git config credential.helper 'revaultpass git-credential'
+    #[command(name = "git-credential")]
+    GitCredential {
+        action: CredentialAction,
+        /// Entry name for a credential; {protocol}, {host}, {path} and {username} are filled in
+        #[arg(long, value_name = "PATTERN", default_value = "git/{host}")]
+        pattern: String,
+    },
+    /// SSH_ASKPASS/SUDO_ASKPASS helper: print the password for the entry named in the prompt
+    #[command(after_help = "The entry comes from an unlocked agent, else the master key from REVAULTPASS_PASSPHRASE; askpass never prompts.")]
+    Askpass {
+        /// The prompt text ssh or sudo passes
+        prompt: String,
+        /// Pulls the identifier out of the prompt: the first capture group that matched, else the whole match
+        #[arg(long, value_name = "REGEX", default_value = ASKPASS_REGEX)]
+        regex: String,
+        /// Entry name, with {match} replaced by the identifier
+        #[arg(long, value_name = "PATTERN", default_value = "ssh/{match}")]
+        pattern: String,
+    },
+    /// Find entries by name or user (case-insensitive)
+    Search {
+        query: String,
+        /// Treat the query as a regular expression
+        #[arg(long)]
+        regex: bool,
+    },
+    /// Search every field of every entry with a regular expression, notes included
+    Grep {
+        pattern: String,
+        /// Match upper and lower case alike
+        #[arg(short = 'i', long)]
+        ignore_case: bool,
+        /// Take the pattern as plain text rather than a regular expression
+        #[arg(short = 'F', long)]
+        fixed_strings: bool,
+        /// Also search passwords, earlier passwords and TOTP seeds
+        #[arg(long)]
+        include_passwords: bool,
+    },
+    /// Report weak, reused and old passwords (exits 7 if there are any)
+    Audit {
+        /// Passwords shorter than this are weak
+        #[arg(long, value_name = "N", default_value_t = 12)]
+        min_length: usize,
+        /// Passwords unchanged for longer than this many days are stale
+        #[arg(long, value_name = "DAYS", default_value_t = 365)]
+        max_age: u64,
+    },
+    /// Look up passwords in the Have I Been Pwned breach data (only a hash prefix is sent)
+    Pwned {
+        /// Check only this entry (default: all)
+        entry: Option<String>,
+        /// Read ranges from downloaded <PREFIX>.txt files here instead of the network
+        #[arg(long, value_name = "DIR")]
+        range_dir: Option<PathBuf>,
+    },
+    /// Remove an entry
+    #[command(visible_alias = "rm")]
+    Delete {
+        /// An entry name, or a pattern with `*` and `?` such as 'work/*'
+        entry: String,
+        /// Don't ask for confirmation
+        #[arg(long)]
+        force: bool,
+    },
+    /// Bring back the most recently deleted entry of that name
+    Undelete { name: String },
+    /// Print an entry's earlier passwords, newest first
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    History {
+        #[arg(required = true)]
+        entry: Option<String>,
+        #[command(subcommand)]
+        action: Option<HistoryCmd>,
+    },
+    /// Deleted entries, kept until the trash is emptied
+    Trash {
+        #[command(subcommand)]
+        action: TrashCmd,
+    },
+    /// Update an entry (prompts for both fields when no flag is given; reopens a note's body)
+    Edit {
+        entry: String,
+        #[arg(long)]
+        user: Option<String>,
+        #[arg(long)]
+        password: Option<String>,
+        /// Set an extra field (repeat for more than one)
+        #[arg(long = "field", value_name = "NAME=VALUE", value_parser = parse_custom_field)]
+        fields: Vec<(String, String)>,
+        /// Remove an extra field (repeat for more than one)
+        #[arg(long = "unset-field", value_name = "NAME")]
+        unset_fields: Vec<String>,
+        /// Set the TOTP secret or otpauth:// URI ('' removes it)
+        #[arg(long, value_name = "SECRET", value_parser = parse_totp)]
+        totp: Option<String>,
+        /// Refuse a new password scoring below SCORE (0-4, default 3)
+        #[arg(long, value_name = "SCORE", num_args = 0..=1, require_equals = true, value_parser = clap::value_parser!(u8).range(0..=4))]
+        enforce_policy: Option<Option<u8>>,
+    },
+    /// Replace an entry's password with a generated one (the old one goes to history)
+    Regenerate {
+        entry: String,
+        /// Password length (default 20)
+        #[arg(long)]
+        length: Option<usize>,
+        #[arg(long)]
+        no_symbols: bool,
+        #[arg(long)]
+        no_digits: bool,
+        #[arg(long)]
+        no_uppercase: bool,
+        /// Leave out 0O1lI|
+        #[arg(long)]
+        exclude_ambiguous: bool,
+        /// Copy the new password to the clipboard instead of printing it
+        #[arg(short = 'c', long)]
+        copy: bool,
+        /// Clear the clipboard after this many seconds (0 keeps it; default 30)
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+    },
+    /// Print the current TOTP code and how long it stays valid
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Otp {
+        #[arg(required = true)]
+        entry: Option<String>,
+        /// Copy the code to the clipboard instead
+        #[arg(short = 'c', long)]
+        copy: bool,
+        /// Clear the clipboard after this many seconds (0 keeps it; default 30)
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+        #[command(subcommand)]
+        action: Option<OtpCmd>,
+    },
+    /// Show a QR code of the password (or TOTP seed, or Wi-Fi login) to scan with a phone
+    #[cfg(feature = "qr")]
+    Qr {
+        entry: String,
+        /// The TOTP seed as an otpauth:// URI, for an authenticator app
+        #[arg(long, conflicts_with = "wifi")]
+        otp: bool,
+        /// A Wi-Fi login: the network is the `ssid` field, else the entry's name
+        #[arg(long)]
+        wifi: bool,
+        /// Write a PNG image here instead of printing to the terminal
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Type the user name, Tab and password into the focused window after a delay
+    Type {
+        entry: String,
+        /// Seconds to wait first, to focus the login form
+        #[arg(long, value_name = "SECS", default_value_t = TYPE_DELAY_SECS)]
+        delay: u64,
+        /// What to type, e.g. '{user}\t{password}\n' (\t is Tab, \n Enter; placeholders as for get --format)
+        #[arg(long, value_name = "TEMPLATE", default_value = "{user}\\t{password}")]
+        sequence: String,
+        /// Type only the password
+        #[arg(long, conflicts_with = "sequence")]
+        password_only: bool,
+    },
+    /// Edit every entry at once as a TOML document in $EDITOR
+    EditAll {
+        /// Save without asking after showing what changes
+        #[arg(long)]
+        force: bool,
+    },
+    /// Change an entry's name
+    Rename {
+        entry: String,
+        #[arg(value_parser = parse_entry_name)]
+        new: String,
+    },
+    /// Copy an entry under a new name
+    Duplicate {
+        entry: String,
+        #[arg(value_parser = parse_entry_name)]
+        new: String,
+        /// Use this user for the copy
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Print a random password
+    Generate {
+        /// Password length (default 20)
+        length: Option<usize>,
+        #[arg(long)]
+        no_symbols: bool,
+        #[arg(long)]
+        no_digits: bool,
+        #[arg(long)]
+        no_uppercase: bool,
+        /// Leave out 0O1lI|
+        #[arg(long)]
+        exclude_ambiguous: bool,
+    },
+    /// Dump entries in plaintext, or as a backup encrypted with a separate passphrase
+    Export {
+        #[arg(long, default_value = "json", value_parser = ["json", "csv", "keepass-xml", "toml"])]
+        format: String,
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        #[arg(long)]
+        stdout: bool,
+        /// Don't ask before writing plaintext
+        #[arg(long)]
+        force: bool,
+        /// Write an encrypted backup to FILE instead
+        #[arg(long, value_name = "FILE")]
+        encrypted: Option<PathBuf>,
+        /// Encrypt to this age1... or ssh-ed25519 public key, or the keys in this file (repeatable); --output must end in .rvpa
+        #[arg(long, value_name = "KEY", conflicts_with_all = ["encrypted", "stdout"])]
+        age_recipient: Vec<String>,
+        /// With --age-recipient: only entries whose name starts with PREFIX
+        #[arg(long, value_name = "PREFIX", requires = "age_recipient")]
+        filter: Option<String>,
+    },
+    /// Merge entries from another password manager or an encrypted backup
+    Import {
+        file: Option<String>,
+        #[arg(long, default_value = "csv", value_parser = ["csv", "bitwarden", "chrome", "keepass", "lastpass", "pass", "toml"])]
+        format: String,
+        /// Rename the expected CSV columns, e.g. name=Title,user=Login
+        #[arg(long, value_name = "FIELD=COLUMN,...")]
+        map: Option<String>,
+        /// skip, overwrite or rename
+        #[arg(long, default_value = "skip")]
+        on_conflict: OnConflict,
+        /// Show what would be imported without writing the store
+        #[arg(long)]
+        dry_run: bool,
+        /// password-store directory for --format pass
+        #[arg(long, value_name = "PATH")]
+        dir: Option<PathBuf>,
+        /// The pass tree is already decrypted
+        #[arg(long)]
+        plain: bool,
+        /// Merge a backup made with export --encrypted
+        #[arg(long, value_name = "FILE")]
+        encrypted: Option<String>,
+        /// Merge an export --age-recipient file, opened with this age identity or SSH private key
+        #[arg(long, value_name = "KEYFILE", conflicts_with = "encrypted")]
+        age_identity: Option<PathBuf>,
+    },
+    /// Change the master key (or add/remove encryption)
+    Rekey {
+        #[command(flatten)]
+        kdf: KdfArgs,
+        /// Require this keyfile from now on (default: keep the current one, or add --keyfile to a store without one)
+        #[arg(long, value_name = "PATH")]
+        new_keyfile: Option<PathBuf>,
+        /// Stop requiring a keyfile
+        #[arg(long, conflicts_with = "new_keyfile")]
+        no_keyfile: bool,
+        /// Make a new recovery key, replacing any earlier one (shown once)
+        #[arg(long)]
+        recovery_key: bool,
+        /// Switch the contents to xchacha20poly1305, chacha20poly1305 or aes256gcm (default: keep the current one)
+        #[arg(long, value_name = "CIPHER")]
+        cipher: Option<Cipher>,
+    },
+    /// Open the store with its recovery key and set a new master key
+    Recover {
+        #[command(flatten)]
+        kdf: KdfArgs,
+        /// The passphrase slot to replace, if the store has several (see 'slots list')
+        #[arg(long, value_name = "ID")]
+        slot: Option<u8>,
+    },
+    /// Split the recovery key into shares for trusted people, any few of whom can recover the store
+    Shares {
+        #[command(subcommand)]
+        action: SharesCmd,
+    },
+    /// Let more than one passphrase open the store, e.g. one per person
+    Slots {
+        #[command(subcommand)]
+        action: SlotsCmd,
+    },
+    /// Suggest Argon2 costs that take ~500 ms on this machine
+    KdfTune,
+    /// Check the crypto against known test vectors and time the store's key derivation
+    SelfTest,
+    /// Show what the file header says: format, store id, creation and last write, cipher and KDF; no key needed
+    Info,
+    /// Summarize the store: counts, password lengths, dates, file format
+    Stats {
+        /// Only report what the file header shows, without asking for the master key
+        #[arg(long)]
+        no_unlock: bool,
+    },
+    /// Check that the store, or a backup passed with --store, is intact and opens; never writes to it
+    Verify,
+    /// Write the store whole, folding in the journal that saves to a large store append to
+    Compact,
+    /// Look for damage in the store and, if its contents don't parse, salvage the entries that do
+    Fsck {
+        /// Write the salvaged entries as the store, moving the damaged file to <store>.corrupt-<ts>
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Automatic backups taken before each write
+    Backups {
+        #[command(subcommand)]
+        action: BackupsCmd,
+    },
+    /// Make the store and backups readable by you only
+    FixPerms,
+    /// Unlock once and run commands at a prompt until 'exit'
+    Shell {
+        /// Lock again after this many minutes without input (0 never)
+        #[arg(long, value_name = "MINUTES", default_value_t = SHELL_IDLE_MINUTES)]
+        idle: u64,
+    },
+    /// Browse and edit entries in a full-screen terminal view
+    Tui,
+    /// Sign every save so a replaced or edited store file is noticed
+    Sign {
+        #[command(subcommand)]
+        action: SignCmd,
+    },
+    /// A second passphrase that opens a separate set of decoy entries instead
+    Decoy {
+        #[command(subcommand)]
+        action: DecoyCmd,
+    },
+    /// Keep the master key in the OS keychain so commands don't prompt for it
+    Keychain {
+        #[command(subcommand)]
+        action: KeychainCmd,
+    },
+    /// Keep the master key in a background process (used when REVAULTPASS_AGENT_SOCK is set)
+    Agent {
+        #[command(subcommand)]
+        action: AgentCmd,
+    },
+    /// Read or change defaults in the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigCmd,
+    },
+    /// Print a completion script for bash, zsh, fish or powershell
+    Completions { shell: Shell },
+    #[command(name = "_complete-names", hide = true)]
+    CompleteNames,
+    #[command(name = "_clear-clipboard", hide = true)]
+    ClearClipboard {
+        #[arg(default_value_t = CLIPBOARD_TIMEOUT_SECS)]
+        timeout: u64,
+        #[arg(long)]
+        osc52: bool,
+    },
+}
+
+impl Cmd {
+    /// Whether the command can change the store, and so is refused in
+    /// read-only mode. Every command is listed, so a new one has to say.
+    fn writes_store(&self) -> bool {
+        match self {
+            Cmd::Init { .. }
+            | Cmd::Add { .. }
+            | Cmd::AddNote { .. }
+            | Cmd::Delete { .. }
+            | Cmd::Undelete { .. }
+            | Cmd::Edit { .. }
+            | Cmd::Regenerate { .. }
+            | Cmd::EditAll { .. }
+            | Cmd::Rename { .. }
+            | Cmd::Duplicate { .. }
+            | Cmd::Rekey { .. }
+            | Cmd::Recover { .. }
+            | Cmd::Shares { .. }
+            | Cmd::Slots { action: SlotsCmd::Add { .. } | SlotsCmd::Remove { .. } }
+            | Cmd::FixPerms
+            | Cmd::Decoy { .. }
+            | Cmd::Sign { action: SignCmd::Setup | SignCmd::Disable }
+            | Cmd::Backups { action: BackupsCmd::Restore { .. } }
+            | Cmd::History { action: Some(HistoryCmd::Clear { .. }), .. }
+            | Cmd::Trash { action: TrashCmd::Empty { .. } }
+            | Cmd::Otp { action: Some(OtpCmd::Import { .. }), .. }
+            | Cmd::GitCredential { action: CredentialAction::Store | CredentialAction::Erase, .. } => true,
+            Cmd::Compact => true,
+            Cmd::Import { dry_run, .. } => !dry_run,
+            Cmd::Fsck { repair } => *repair,
+            // Their edits are saved like any other, so the store refuses them then.
+            Cmd::Shell { .. } | Cmd::Tui => false,
+            Cmd::List { .. }
+            | Cmd::Names { .. }
+            | Cmd::Get { .. }
+            | Cmd::Exec { .. }
+            | Cmd::GitCredential { .. }
+            | Cmd::Askpass { .. }
+            | Cmd::Search { .. }
+            | Cmd::Grep { .. }
+            | Cmd::Audit { .. }
+            | Cmd::Pwned { .. }
+            | Cmd::History { .. }
+            | Cmd::Trash { .. }
+            | Cmd::Otp { .. }
+            | Cmd::Type { .. }
+            | Cmd::Generate { .. }
+            | Cmd::Export { .. }
+            | Cmd::Slots { .. }
+            | Cmd::KdfTune
+            | Cmd::SelfTest
+            | Cmd::Info
+            | Cmd::Stats { .. }
+            | Cmd::Verify
+            | Cmd::Backups { .. }
+            | Cmd::Sign { .. }
+            | Cmd::Keychain { .. }
+            | Cmd::Agent { .. }
+            | Cmd::Config { .. }
+            | Cmd::Completions { .. }
+            | Cmd::CompleteNames
+            | Cmd::ClearClipboard { .. } => false,
+            #[cfg(feature = "qr")]
+            Cmd::Qr { .. } => false,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum OtpCmd {
+    /// Create an entry from an otpauth:// URI (read from stdin when omitted)
+    Import {
+        uri: Option<String>,
+        /// Add the seed to this existing entry instead
+        #[arg(long, value_name = "NAME")]
+        attach: Option<String>,
+        /// Name for the new entry (default: the issuer, else the account)
+        #[arg(long, conflicts_with = "attach", value_parser = parse_entry_name)]
+        name: Option<String>,
+        /// The URI is a Google Authenticator otpauth-migration:// export
+        #[arg(long, conflicts_with_all = ["attach", "name"])]
+        migration: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCmd {
+    /// Forget an entry's earlier passwords
+    Clear { entry: String },
+}
+
+#[derive(Subcommand)]
+enum TrashCmd {
+    /// Show deleted entries and when they were deleted
+    List,
+    /// Permanently remove deleted entries
+    Empty {
+        /// Only those deleted longer ago than this, e.g. 30d, 12h, 2w
+        #[arg(long, value_name = "AGE", value_parser = parse_age)]
+        older_than: Option<u64>,
+        /// Don't ask for confirmation
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SignCmd {
+    /// Make a signing key (kept in the encrypted store, or in .signing-key next to an unencrypted one) and sign the store; run again to re-sign
+    Setup,
+    /// Check the store, or FILE such as a backup, against its .sig and the store's .pub
+    Verify {
+        file: Option<PathBuf>,
+        /// Check against this public key (a file like the .pub, or its hex) instead of the store's .pub
+        #[arg(long, value_name = "FILE|HEX")]
+        public_key: Option<String>,
+    },
+    /// Stop signing and remove the .pub and .sig files
+    Disable,
+}
+
+#[derive(Subcommand)]
+enum DecoyCmd {
+    /// Set the decoy passphrase, starting an empty decoy (replaces any earlier one)
+    Init,
+}
+
+#[derive(Subcommand)]
+enum ConfigCmd {
+    /// Print a setting, e.g. 'generate.length' (the default when the file doesn't set it)
+    Get { key: String },
+    /// Change a setting, keeping the rest of the file as it is
+    Set { key: String, value: String },
+    /// Print where the config file is read from
+    Path,
+}
+
+#[derive(Subcommand)]
+enum KeychainCmd {
+    /// Check the master key against the store, then save it in the keychain
+    Enable,
+    /// Remove the saved master key from the keychain
+    Disable,
+    /// Show whether the keychain holds the master key for this store
+    Status,
+}
+
+#[derive(Subcommand)]
+enum SharesCmd {
+    /// Make a new recovery key and print it as shares (the old key and shares stop working)
+    Create {
+        /// How many shares it takes to recover the store
+        #[arg(long, value_name = "K", value_parser = clap::value_parser!(u8).range(2..))]
+        threshold: u8,
+        /// How many shares to make
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(2..))]
+        count: u8,
+        /// Write each share to its own file share-<n>.txt in this directory instead of printing them
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+    },
+    /// Open the store with enough shares and set a new master key
+    Recover {
+        #[command(flatten)]
+        kdf: KdfArgs,
+        /// The passphrase slot to replace, if the store has several (see 'slots list')
+        #[arg(long, value_name = "ID")]
+        slot: Option<u8>,
+        /// Read shares from these files (as written by --output-dir); the rest are asked for
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SlotsCmd {
+    /// Show the key slots (read from the header; no master key needed)
+    List,
+    /// Add a passphrase that opens the store too
+    Add {
+        /// A name for the slot in 'slots list'; it is stored unencrypted
+        #[arg(long, value_parser = parse_slot_label)]
+        label: Option<String>,
+        /// Require this keyfile along with the new passphrase
+        #[arg(long, value_name = "PATH")]
+        new_keyfile: Option<PathBuf>,
+        #[command(flatten)]
+        kdf: KdfArgs,
+    },
+    /// Remove a key slot; the last passphrase slot can't be removed
+    Remove {
+        id: u8,
+        /// Don't ask for confirmation
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AgentCmd {
+    /// Start the agent; eval the output to set REVAULTPASS_AGENT_SOCK
+    Start {
+        /// Forget the key after this many minutes without a request (0 never)
+        #[arg(long, value_name = "MINUTES", default_value_t = AGENT_IDLE_MINUTES)]
+        idle: u64,
+        /// Don't detach; serve until stopped
+        #[arg(long)]
+        foreground: bool,
+    },
+    /// Unlock the agent with the master key now instead of at the next command
+    Unlock,
+    /// Make the agent forget the store's key
+    Lock,
+    /// Show whether an agent is running, and for which store
+    Status,
+    /// Stop the agent
+    Stop,
+}
+
+#[derive(Subcommand)]
+enum BackupsCmd {
+    /// Show the backups with timestamps and sizes
+    List,
+    /// Put a backup back in place
+    Restore { timestamp: u64 },
+}
+
+/// Ends a command with this exit status after it has printed what it had to say.
+#[derive(Debug)]
+struct Exit(i32);
+
+impl std::fmt::Display for Exit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "exit status {}", self.0)
+    }
+}
+
+impl std::error::Error for Exit {}
+
+/// Prints a usage line to stderr; return the result to exit with the generic error status.
+fn usage(text: &str) -> Box<dyn std::error::Error + Send + Sync> {
+    if JSON_OUTPUT.load(Ordering::Relaxed) {
+        eprintln!("{}", json_error(text, 1));
+    } else {
+        eprintln!("{}", text);
+    }
+    Box::new(Exit(1))
+}
+
+/// Prints a failed command's error (unless it already did) and returns its exit status.
+fn report_error(e: &(dyn std::error::Error + Send + Sync + 'static)) -> i32 {
+    if let Some(Exit(code)) = e.downcast_ref::<Exit>() {
+        return *code;
+    }
+    let code = e.downcast_ref::<RevaultError>().map_or(1, RevaultError::exit_code);
+    if JSON_OUTPUT.load(Ordering::Relaxed) {
+        eprintln!("{}", json_error(&e.to_string(), code));
+    } else {
+        eprintln!("Error: {}", e);
+    }
+    code
+}
+
+/// Prints a command-line parse error; help and version requests go to stdout.
+fn report_parse_error(e: &clap::Error, json: bool) {
+    if json && e.use_stderr() {
+        let rendered = e.to_string();
+        let message = rendered.lines().next().unwrap_or_default().trim_start_matches("error: ");
+        eprintln!("{}", json_error(message, 1));
+    } else {
+        let _ = e.print();
+    }
+}
+
+/// What `--json` prints on stderr when a command fails.
+fn json_error(message: &str, code: i32) -> serde_json::Value {
+    serde_json::json!({ "error": message, "code": code })
+}
+
+/// `text` as one word for a POSIX shell, quoted only if it needs to be.
+fn shell_quote(text: &str) -> String {
+    if !text.is_empty() && text.chars().all(|c| c.is_ascii_alphanumeric() || "/._-+:@".contains(c)) {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// Splits a `shell` line into words like a POSIX shell would: quotes group,
+/// and a backslash escapes the next character except inside single quotes.
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => word.get_or_insert_with(String::new).push(chars.next().ok_or("trailing backslash")?),
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("unterminated quote".into());
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Checks the master key against the store and keeps it for the session.
+fn unlock_session(path: &std::path::Path) -> Result<(), RevaultError> {
+    let key = unlock_key(path)?;
+    let _lock = lock_store(path, Lock::Shared)?;
+    let vault = open_vault(path, key)?;
+    if let StoreKey::Passphrase(pass, ..) = vault.key() {
+        *session_key() = Some(pass.clone());
+    }
+    Ok(())
+}
+
+/// `revaultpass shell`: reads command lines and runs each like a separate
+/// invocation against `path`, with the master key from the session. Every
+/// command reads the store afresh and saves as usual, so changes made elsewhere
+/// show up and an interrupted session never leaves a half-written store.
+fn run_shell(path: &std::path::Path, idle_minutes: u64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    unlock_session(path)?;
+    let mut unlocked = true;
+    let interactive = io::stdin().is_terminal();
+    let idle = (idle_minutes > 0).then(|| std::time::Duration::from_secs(idle_minutes * 60));
+    let shell_json = JSON_OUTPUT.load(Ordering::Relaxed);
+
+    // Stdin is read on a thread, one line per request, so waiting for input can
+    // time out while prompts inside commands still read stdin themselves.
+    let (want_line, requests) = mpsc::channel::<()>();
+    let (line_sender, lines) = mpsc::channel::<io::Result<Option<String>>>();
+    std::thread::spawn(move || {
+        for () in requests {
+            let mut line = String::new();
+            let read = io::stdin().read_line(&mut line).map(|n| (n > 0).then_some(line));
+            if line_sender.send(read).is_err() {
+                break;
+            }
+        }
+    });
+    status!("Unlocked {}. Type 'help' for commands, 'exit' or Ctrl+D to leave.", path.display());
+    loop {
+        if interactive {
+            eprint!("revaultpass> ");
+            io::stderr().flush()?;
+        }
+        want_line.send(())?;
+        let line = loop {
+            let received = match idle.filter(|_| unlocked) {
+                Some(timeout) => lines.recv_timeout(timeout),
+                None => lines.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(line) => break line?,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    *session_key() = None;
+                    unlocked = false;
+                    eprint!("\n(locked: no input for {} min)\n{}", idle_minutes, if interactive { "revaultpass> " } else { "" });
+                    io::stderr().flush()?;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break None,
+            }
+        };
+        let Some(line) = line else {
+            // The first Ctrl+D locks, the second one leaves.
+            if interactive && unlocked {
+                *session_key() = None;
+                unlocked = false;
+                eprintln!("\n(locked; the next command asks for the master key, Ctrl+D again leaves)");
+                continue;
+            }
+            eprintln!();
+            break;
+        };
+        let words = match split_words(&line) {
+            Ok(words) => words,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                continue;
+            }
+        };
+        match words.first().map(String::as_str) {
+            None => continue,
+            Some("exit" | "quit") => break,
+            _ => {}
+        }
+        let cli = match Cli::try_parse_from(std::iter::once("revaultpass".to_string()).chain(words)) {
+            Ok(cli) => cli,
+            Err(e) => {
+                report_parse_error(&e, shell_json);
+                continue;
+            }
+        };
+        if cli.store.is_some() || cli.passphrase_stdin {
+            eprintln!("Error: the shell works on {}; --store and --passphrase-stdin don't apply here", path.display());
+            continue;
+        }
+        if matches!(cli.command, Cmd::Init { .. } | Cmd::Rekey { .. } | Cmd::Recover { .. } | Cmd::Shares { .. } | Cmd::Decoy { .. } | Cmd::Shell { .. } | Cmd::Tui | Cmd::Exec { .. } | Cmd::GitCredential { .. }) {
+            eprintln!("Error: run this outside the shell");
+            continue;
+        }
+        if !unlocked {
+            if let Err(e) = unlock_session(path) {
+                report_error(&e);
+                continue;
+            }
+            unlocked = true;
+        }
+        JSON_OUTPUT.store(shell_json || cli.json, Ordering::Relaxed);
+        let result = run(Cli { store: Some(path.as_os_str().to_owned()), json: shell_json || cli.json, ..cli });
+        if let Err(e) = result {
+            report_error(&*e);
+        }
+        JSON_OUTPUT.store(shell_json, Ordering::Relaxed);
+        NO_PROMPT_ECHO.store(false, Ordering::Relaxed);
+    }
+    *session_key() = None;
+    Ok(())
+}
+
+/// `config.toml` in the platform config directory (`~/.config/revaultpass`
+/// on Linux), or the file `$REVAULTPASS_CONFIG` names: defaults for flags.
+/// Flags win over environment variables, and both over the file. Only a
+/// small part of TOML is understood: `[section]` headers and `key = value`
+/// lines with a string, integer or boolean. Anything else, including keys
+/// this version doesn't know, is warned about and skipped, so a file written
+/// for a newer revaultpass still works.
+mod config {
+    use super::*;
+    use std::path::Path;
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Kind {
+        Path,
+        Int,
+        Bool,
+    }
+
+    impl Kind {
+        fn name(self) -> &'static str {
+            match self {
+                Kind::Path => "a path (a quoted string)",
+                Kind::Int => "a whole number",
+                Kind::Bool => "true or false",
+            }
+        }
+    }
+
+    /// Every key the file may hold, with its type and default (empty when
+    /// there is none to show).
+    const KEYS: [(&str, Kind, &str); 18] = [
+        ("store", Kind::Path, ""),
+        ("keyfile", Kind::Path, ""),
+        ("mask_input", Kind::Bool, ""),
+        ("askpass", Kind::Path, ""),
+        ("pinentry", Kind::Path, ""),
+        ("case_insensitive_names", Kind::Bool, "false"),
+        ("clipboard_timeout", Kind::Int, "30"),
+        ("backup_keep", Kind::Int, "10"),
+        ("journal", Kind::Bool, "true"),
+        ("generate.length", Kind::Int, "20"),
+        ("generate.uppercase", Kind::Bool, "true"),
+        ("generate.digits", Kind::Bool, "true"),
+        ("generate.symbols", Kind::Bool, "true"),
+        ("generate.exclude_ambiguous", Kind::Bool, "false"),
+        ("confirm.delete", Kind::Bool, "true"),
+        ("confirm.trash_empty", Kind::Bool, "true"),
+        ("confirm.export", Kind::Bool, "true"),
+        ("confirm.slots_remove", Kind::Bool, "true"),
+    ];
+
+    #[derive(Clone, PartialEq)]
+    enum Value {
+        Str(String),
+        Int(i64),
+        Bool(bool),
+    }
+
+    impl std::fmt::Display for Value {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                Value::Str(s) => f.write_str(s),
+                Value::Int(n) => write!(f, "{}", n),
+                Value::Bool(b) => write!(f, "{}", b),
+            }
+        }
+    }
+
+    impl Value {
+        fn to_toml(&self) -> String {
+            match self {
+                Value::Str(s) => serde_json::Value::from(s.as_str()).to_string(),
+                v => v.to_string(),
+            }
+        }
+    }
+
+    /// The settings from the file; `None` wherever it says nothing.
+    #[derive(Default)]
+    pub struct Config {
+        pub store: Option<PathBuf>,
+        pub keyfile: Option<PathBuf>,
+        pub mask_input: Option<bool>,
+        pub askpass: Option<PathBuf>,
+        pub pinentry: Option<PathBuf>,
+        pub case_insensitive_names: Option<bool>,
+        pub clipboard_timeout: Option<u64>,
+        pub backup_keep: Option<usize>,
+        pub journal: Option<bool>,
+        pub generate_length: Option<usize>,
+        pub generate_uppercase: Option<bool>,
+        pub generate_digits: Option<bool>,
+        pub generate_symbols: Option<bool>,
+        pub generate_exclude_ambiguous: Option<bool>,
+        pub confirm_delete: Option<bool>,
+        pub confirm_trash_empty: Option<bool>,
+        pub confirm_export: Option<bool>,
+        pub confirm_slots_remove: Option<bool>,
+    }
+
+    impl Config {
+        /// Reads `path`; a missing file is an empty config. Returns the
+        /// warnings for lines it skipped.
+        fn load(path: &Path) -> (Config, Vec<String>) {
+            let mut config = Config::default();
+            let text = match fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return (config, Vec::new()),
+                Err(e) => return (config, vec![e.to_string()]),
+            };
+            let (lines, mut warnings) = parse(&text);
+            for (line, (key, value)) in lines {
+                if let Err(e) = config.apply(&key, value) {
+                    warnings.push((line, e));
+                }
+            }
+            warnings.sort_by_key(|(line, _)| *line);
+            (config, warnings.into_iter().map(|(line, e)| format!("line {}: {}; ignored", line, e)).collect())
+        }
+
+        fn apply(&mut self, key: &str, value: Value) -> Result<(), String> {
+            let kind = kind_of(key).ok_or_else(|| format!("unknown key '{}'", key))?;
+            let wrong = || format!("'{}' should be {}", key, kind.name());
+            let path = |v: &Value| match v {
+                Value::Str(s) if !s.is_empty() => Ok(expand_home(s)),
+                _ => Err(wrong()),
+            };
+            let int = |v: &Value| match v {
+                Value::Int(n) => u64::try_from(*n).map_err(|_| wrong()),
+                _ => Err(wrong()),
+            };
+            let flag = |v: &Value| match v {
+                Value::Bool(b) => Ok(*b),
+                _ => Err(wrong()),
+            };
+            match key {
+                "store" => self.store = Some(path(&value)?),
+                "keyfile" => self.keyfile = Some(path(&value)?),
+                "mask_input" => self.mask_input = Some(flag(&value)?),
+                "askpass" => self.askpass = Some(path(&value)?),
+                "pinentry" => self.pinentry = Some(path(&value)?),
+                "case_insensitive_names" => self.case_insensitive_names = Some(flag(&value)?),
+                "clipboard_timeout" => self.clipboard_timeout = Some(int(&value)?),
+                "backup_keep" => self.backup_keep = Some(int(&value)? as usize),
+                "journal" => self.journal = Some(flag(&value)?),
+                "generate.length" => self.generate_length = Some(int(&value)? as usize),
+                "generate.uppercase" => self.generate_uppercase = Some(flag(&value)?),
+                "generate.digits" => self.generate_digits = Some(flag(&value)?),
+                "generate.symbols" => self.generate_symbols = Some(flag(&value)?),
+                "generate.exclude_ambiguous" => self.generate_exclude_ambiguous = Some(flag(&value)?),
+                "confirm.delete" => self.confirm_delete = Some(flag(&value)?),
+                "confirm.trash_empty" => self.confirm_trash_empty = Some(flag(&value)?),
+                "confirm.export" => self.confirm_export = Some(flag(&value)?),
+                "confirm.slots_remove" => self.confirm_slots_remove = Some(flag(&value)?),
+                _ => unreachable!("every key in KEYS has a field"),
+            }
+            Ok(())
+        }
+    }
+
+    fn kind_of(key: &str) -> Option<Kind> {
+        KEYS.iter().find(|(name, ..)| *name == key).map(|(_, kind, _)| *kind)
+    }
+
+    /// A leading `~/` means the home directory, as in a shell.
+    fn expand_home(path: &str) -> PathBuf {
+        match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+            (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+            _ => PathBuf::from(path),
+        }
+    }
+
+    /// `$REVAULTPASS_CONFIG`, else `config.toml` in the config directory.
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("REVAULTPASS_CONFIG").filter(|p| !p.is_empty()) {
+            return Some(PathBuf::from(path));
+        }
+        directories::ProjectDirs::from("com", "revaultpass", "revaultpass").map(|d| d.config_dir().join("config.toml"))
+    }
+
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+
+    /// The config, read (and warned about) once per process.
+    pub fn get() -> &'static Config {
+        CONFIG.get_or_init(|| {
+            let Some(path) = path() else { return Config::default() };
+            let (config, warnings) = Config::load(&path);
+            for warning in warnings {
+                eprintln!("warning: {}: {}", path.display(), warning);
+            }
+            config
+        })
+    }
+
+    /// A line number with what that line says or what's wrong with it.
+    type Numbered<T> = Vec<(usize, T)>;
+
+    /// The key each `key = value` line sets, prefixed with its section, as
+    /// (line number, (key, value)); warnings for the lines that don't parse.
+    fn parse(text: &str) -> (Numbered<(String, Value)>, Numbered<String>) {
+        let (mut lines, mut warnings) = (Vec::new(), Vec::new());
+        let mut section = String::new();
+        for (i, raw) in text.lines().enumerate() {
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                match header.strip_suffix(']') {
+                    Some(name) => section = name.trim().to_string(),
+                    None => warnings.push((i + 1, "unterminated section header".to_string())),
+                }
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                warnings.push((i + 1, "expected 'key = value'".to_string()));
+                continue;
+            };
+            let key = key.trim();
+            let key = if section.is_empty() { key.to_string() } else { format!("{}.{}", section, key) };
+            match parse_value(value.trim()) {
+                Ok(value) => lines.push((i + 1, (key, value))),
+                Err(e) => warnings.push((i + 1, e)),
+            }
+        }
+        (lines, warnings)
+    }
+
+    /// `line` up to a `#` that isn't inside a string.
+    fn strip_comment(line: &str) -> &str {
+        let (mut quote, mut escaped) = (None, false);
+        for (i, c) in line.char_indices() {
+            match (quote, c) {
+                (Some('"'), _) if escaped => escaped = false,
+                (Some('"'), '\\') => escaped = true,
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '#') => return &line[..i],
+                (None, _) => {}
+            }
+        }
+        line
+    }
+
+    fn parse_value(text: &str) -> Result<Value, String> {
+        match text {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            _ => {}
+        }
+        if let Some(rest) = text.strip_prefix('\'') {
+            return match rest.strip_suffix('\'') {
+                Some(s) if !s.contains('\'') => Ok(Value::Str(s.to_string())),
+                _ => Err("unterminated string".to_string()),
+            };
+        }
+        if text.starts_with('"') {
+            // TOML basic strings use the same escapes as JSON, plus \U and \e.
+            return serde_json::from_str::<String>(text).map(Value::Str).map_err(|_| "malformed string".to_string());
+        }
+        let digits = text.replace('_', "");
+        digits.parse().map(Value::Int).map_err(|_| format!("unsupported value '{}' (use a string, whole number or true/false)", text))
+    }
+
+    /// `config get`: the file's value, else the default.
+    pub fn show(key: &str) -> Result<String, String> {
+        let (_, _, default) = KEYS.iter().find(|(name, ..)| *name == key).ok_or_else(|| unknown(key))?;
+        let path = path().ok_or("could not determine the config directory")?;
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        let set = parse(&text).0.into_iter().rev().find(|(_, (k, _))| k == key).map(|(_, (_, v))| v.to_string());
+        match (set, default) {
+            (Some(value), _) => Ok(value),
+            (None, default) if !default.is_empty() => Ok(default.to_string()),
+            (None, _) => Err(format!("'{}' is not set in {}", key, path.display())),
+        }
+    }
+
+    fn unknown(key: &str) -> String {
+        let names: Vec<&str> = KEYS.iter().map(|(name, ..)| *name).collect();
+        format!("unknown config key '{}'; known keys: {}", key, names.join(", "))
+    }
+
+    /// `config set`: checks `value` against the key's type, then rewrites the
+    /// line that sets it, or adds one, leaving the rest of the file alone.
+    pub fn set(key: &str, value: &str) -> Result<PathBuf, String> {
+        let kind = kind_of(key).ok_or_else(|| unknown(key))?;
+        let value = match kind {
+            Kind::Path if !value.is_empty() => Value::Str(value.to_string()),
+            Kind::Int => value.parse::<u64>().ok().and_then(|n| i64::try_from(n).ok()).map(Value::Int).ok_or_else(|| format!("'{}' should be a whole number", key))?,
+            Kind::Bool => match value {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => return Err(format!("'{}' should be true or false", key)),
+            },
+            Kind::Path => return Err(format!("'{}' can't be empty", key)),
+        };
+        let path = path().ok_or("could not determine the config directory")?;
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        let (section, name) = key.split_once('.').unwrap_or(("", key));
+        let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+        // The line that sets the key already, and the last key line of its
+        // section, where a new one goes.
+        let (mut current, mut in_section, mut end, mut found) = (String::new(), section.is_empty(), None, None);
+        for (i, raw) in lines.iter().enumerate() {
+            let line = strip_comment(raw).trim();
+            if let Some(header) = line.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+                current = header.trim().to_string();
+                in_section = current == section;
+                continue;
+            }
+            let Some((k, _)) = line.split_once('=') else { continue };
+            let full = if current.is_empty() { k.trim().to_string() } else { format!("{}.{}", current, k.trim()) };
+            if full == key {
+                found = Some((i, k.trim().to_string()));
+            }
+            if in_section {
+                end = Some(i);
+            }
+        }
+        let value = value.to_toml();
+        match (found, end) {
+            (Some((i, written)), _) => {
+                let comment = &lines[i][strip_comment(&lines[i]).trim_end().len()..];
+                lines[i] = format!("{} = {}{}", written, value, comment);
+            }
+            (None, Some(i)) => lines.insert(i + 1, format!("{} = {}", name, value)),
+            (None, None) if section.is_empty() => lines.insert(0, format!("{} = {}", name, value)),
+            (None, None) => {
+                if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                    lines.push(String::new());
+                }
+                lines.push(format!("[{}]", section));
+                lines.push(format!("{} = {}", name, value));
+            }
+        }
+        if let Some(dir) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            perms::create_private_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let mut out = lines.join("\n");
+        out.push('\n');
+        write_atomic(&path, out.as_bytes()).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+/// The part of TOML that `edit-all` and `export --format toml` write:
+/// tables, strings (multi-line ones included), integers and lists of strings.
+mod toml {
+    /// A basic string, or a multi-line one for text with line breaks so notes
+    /// read as they are.
+    pub fn quote(text: &str) -> String {
+        if !text.contains('\n') {
+            return serde_json::Value::from(text).to_string();
+        }
+        let mut out = String::from("\"\"\"\n");
+        for c in text.chars() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '"' => out.push_str("\\\""),
+                '\n' | '\t' => out.push(c),
+                c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push_str("\"\"\"");
+        out
+    }
+
+    /// A field name as a TOML key: bare when it can be, quoted otherwise.
+    pub fn key(name: &str) -> String {
+        match !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            true => name.to_string(),
+            false => quote(name),
+        }
+    }
+
+    /// Reads a string at the start of `text`: basic ("..."), literal ('...'),
+    /// or a multi-line basic one ("""...""") that may go on over the
+    /// following `lines`. Returns it with what follows it on the last line.
+    pub fn string<'a>(text: &'a str, lines: &mut dyn Iterator<Item = &'a str>, line: &mut usize) -> Result<(String, &'a str), String> {
+        if let Some(rest) = text.strip_prefix("\"\"\"") {
+            let mut raw = String::new();
+            let mut current = rest;
+            loop {
+                if let Some(end) = closing(current, true) {
+                    raw.push_str(&current[..end]);
+                    // A newline right after the opening quotes isn't part of the string.
+                    let raw = raw.strip_prefix('\n').unwrap_or(&raw);
+                    return Ok((unescape(raw, true)?, &current[end + 3..]));
+                }
+                raw.push_str(current);
+                raw.push('\n');
+                current = lines.next().ok_or("unterminated \"\"\" string")?;
+                *line += 1;
+            }
+        }
+        if let Some(rest) = text.strip_prefix('\'') {
+            let end = rest.find('\'').ok_or("unterminated string")?;
+            return Ok((rest[..end].to_string(), &rest[end + 1..]));
+        }
+        let rest = text.strip_prefix('"').ok_or("expected a string in quotes")?;
+        let end = closing(rest, false).ok_or("unterminated string")?;
+        Ok((unescape(&rest[..end], false)?, &rest[end + 1..]))
+    }
+
+    /// Where the closing quote (or `"""` when `triple`) is, skipping escapes.
+    fn closing(text: &str, triple: bool) -> Option<usize> {
+        let mut escaped = false;
+        for (i, c) in text.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' if !triple || text[i..].starts_with("\"\"\"") => return Some(i),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn unescape(raw: &str, multiline: bool) -> Result<String, String> {
+        let mut out = String::with_capacity(raw.len());
+        let mut chars = raw.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            let hex = |chars: &mut std::iter::Peekable<std::str::Chars>, n: usize| {
+                let digits: String = chars.take(n).collect();
+                u32::from_str_radix(&digits, 16).ok().filter(|_| digits.len() == n).and_then(char::from_u32).ok_or(format!("invalid escape '\\u{}'", digits))
+            };
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('b') => out.push('\u{8}'),
+                Some('f') => out.push('\u{c}'),
+                Some('e') => out.push('\u{1b}'),
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some('u') => out.push(hex(&mut chars, 4)?),
+                Some('U') => out.push(hex(&mut chars, 8)?),
+                // A backslash at the end of a line joins it with the next.
+                Some(c) if multiline && c.is_whitespace() => {
+                    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                        chars.next();
+                    }
+                }
+                Some(c) => return Err(format!("invalid escape '\\{}'", c)),
+                None => return Err("string ends with a backslash".into()),
+            }
+        }
+        Ok(out)
+    }
+
+    /// Nothing but an optional comment may follow a value or header.
+    pub fn trailing(rest: &str) -> Result<(), String> {
+        let rest = rest.trim_start();
+        match rest.is_empty() || rest.starts_with('#') {
+            true => Ok(()),
+            false => Err(format!("unexpected '{}' after the value", rest)),
+        }
+    }
+
+    /// A bare or quoted key at the start of `text`, with what follows.
+    pub fn read_key<'a>(text: &'a str, lines: &mut dyn Iterator<Item = &'a str>, line: &mut usize) -> Result<(String, &'a str), String> {
+        if text.starts_with(['"', '\'']) {
+            return string(text, lines, line);
+        }
+        let end = text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-')).unwrap_or(text.len());
+        match end {
+            0 => Err(format!("expected a key, found '{}'", text)),
+            _ => Ok((text[..end].to_string(), &text[end..])),
+        }
+    }
+
+    /// `["a", "b"]` on one line.
+    pub fn array<'a>(text: &'a str, lines: &mut dyn Iterator<Item = &'a str>, line: &mut usize) -> Result<(Vec<String>, &'a str), String> {
+        let mut rest = text.strip_prefix('[').ok_or("expected a list like [\"a\", \"b\"]")?.trim_start();
+        let mut items = Vec::new();
+        loop {
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((items, after));
+            }
+            let (item, after) = string(rest, lines, line)?;
+            items.push(item);
+            rest = after.trim_start();
+            match rest.strip_prefix(',') {
+                Some(after) => rest = after.trim_start(),
+                None if rest.starts_with(']') => {}
+                None => return Err("expected ',' or ']' in the list".into()),
+            }
+        }
+    }
+
+    pub enum Value {
+        String(String),
+        Integer(u64),
+        List(Vec<String>),
+    }
+
+    impl Value {
+        fn kind(&self) -> &'static str {
+            match self {
+                Value::String(_) => "a string",
+                Value::Integer(_) => "a number",
+                Value::List(_) => "a list",
+            }
+        }
+
+        pub fn into_string(self) -> Result<String, String> {
+            match self {
+                Value::String(s) => Ok(s),
+                other => Err(format!("expected a string, found {}", other.kind())),
+            }
+        }
+
+        pub fn into_integer(self) -> Result<u64, String> {
+            match self {
+                Value::Integer(n) => Ok(n),
+                other => Err(format!("expected a number, found {}", other.kind())),
+            }
+        }
+
+        pub fn into_list(self) -> Result<Vec<String>, String> {
+            match self {
+                Value::List(items) => Ok(items),
+                other => Err(format!("expected a list like [\"a\", \"b\"], found {}", other.kind())),
+            }
+        }
+    }
+
+    /// A string, a list of strings or a non-negative integer at the start of
+    /// `text`, with what follows it.
+    pub fn value<'a>(text: &'a str, lines: &mut dyn Iterator<Item = &'a str>, line: &mut usize) -> Result<(Value, &'a str), String> {
+        if text.starts_with('[') {
+            return array(text, lines, line).map(|(items, rest)| (Value::List(items), rest));
+        }
+        if text.starts_with(['"', '\'']) {
+            return string(text, lines, line).map(|(s, rest)| (Value::String(s), rest));
+        }
+        let end = text.find(|c: char| !(c.is_ascii_digit() || c == '_')).unwrap_or(text.len());
+        match text[..end].replace('_', "").parse() {
+            Ok(n) => Ok((Value::Integer(n), &text[end..])),
+            Err(_) => Err(format!("expected a string, a list or a number, found '{}'", text)),
+        }
+    }
+
+    /// The dotted key of a `[table]` or `[[array.of.tables]]` header, and
+    /// whether it is the latter; `None` when `text` isn't a header.
+    pub fn header<'a>(text: &'a str, lines: &mut dyn Iterator<Item = &'a str>, line: &mut usize) -> Result<Option<(Vec<String>, bool)>, String> {
+        let Some(inner) = text.strip_prefix('[') else {
+            return Ok(None);
+        };
+        let (mut rest, array) = match inner.strip_prefix('[') {
+            Some(inner) => (inner, true),
+            None => (inner, false),
+        };
+        let mut path = Vec::new();
+        loop {
+            let (part, after) = read_key(rest.trim_start(), lines, line)?;
+            path.push(part);
+            rest = after.trim_start();
+            match rest.strip_prefix('.') {
+                Some(after) => rest = after,
+                None => break,
+            }
+        }
+        let close = if array { "]]" } else { "]" };
+        let rest = rest.strip_prefix(close).ok_or_else(|| format!("expected '{}' to end the table header", close))?;
+        trailing(rest)?;
+        Ok(Some((path, array)))
+    }
+}
+
+/// `edit-all`: the whole store as a TOML document, one `[entry."<name>"]`
+/// table per entry, for editing in `$EDITOR`. Only what the document shows
+/// can change; history and timestamps stay with the entry of the same name.
+mod edit_all {
+    use super::toml::{array, key, quote, read_key, string, trailing};
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+
+    const HEADER: &str = "\
+# revaultpass edit-all: every entry of the store, one [entry.\"<name>\"] table each.
+# Change values, add tables or delete them, then save and quit. Nothing is saved
+# until you confirm. Deleted entries go to the trash; a renamed table is a new
+# entry, so its password history stays with the old name there.
+# Keys: user, password, url, tags = [\"...\"], totp, notes, and an
+# [entry.\"<name>\".fields] table for extra fields. Notes have kind = \"note\"
+# and a body instead of user and password.
+";
+
+    /// Starts the comment that explains why the document didn't parse; it is
+    /// replaced by the next one.
+    const ERROR_MARK: &str = "# ERROR: ";
+
+    /// An entry as the document describes it.
+    pub struct Edited {
+        /// Line of its table header, for errors.
+        line: usize,
+        pub name: String,
+        pub note: bool,
+        pub user: String,
+        pub password: Secret,
+        pub body: Option<Secret>,
+        pub url: Option<String>,
+        pub notes: Option<String>,
+        pub tags: Vec<String>,
+        pub fields: BTreeMap<String, String>,
+        pub totp: Option<Secret>,
+    }
+
+    pub struct ParseError {
+        pub line: usize,
+        pub message: String,
+    }
+
+    fn error(line: usize, message: impl Into<String>) -> ParseError {
+        ParseError { line, message: message.into() }
+    }
+
+    pub fn to_document(entries: &[Entry]) -> Zeroizing<String> {
+        let mut doc = Zeroizing::new(String::from(HEADER));
+        for e in entries {
+            doc.push_str(&format!("\n[entry.{}]\n", quote(&e.name)));
+            let mut push = |key: &str, value: &str| doc.push_str(&format!("{} = {}\n", key, quote(value)));
+            if e.is_note() {
+                push("kind", "note");
+            } else {
+                push("user", &e.user);
+                push("password", e.password.expose());
+            }
+            if let Some(url) = &e.url {
+                push("url", url);
+            }
+            if let Some(totp) = &e.totp {
+                push("totp", totp.expose());
+            }
+            if let Some(notes) = &e.notes {
+                push("notes", notes);
+            }
+            if let Some(body) = &e.body {
+                push("body", body.expose());
+            }
+            if !e.tags.is_empty() {
+                let tags: Vec<String> = e.tags.iter().map(|t| quote(t)).collect();
+                doc.push_str(&format!("tags = [{}]\n", tags.join(", ")));
+            }
+            if !e.fields.is_empty() {
+                doc.push_str(&format!("[entry.{}.fields]\n", quote(&e.name)));
+                for (name, value) in &e.fields {
+                    doc.push_str(&format!("{} = {}\n", key(name), quote(value)));
+                }
+            }
+        }
+        doc
+    }
+
+    /// Puts `message` about `line` at the top of the document, replacing an
+    /// earlier one, so the editor opens on the user's text with the reason.
+    pub fn mark_error(doc: &str, err: &ParseError) -> Zeroizing<String> {
+        let marks_before = doc.lines().take(err.line - 1).filter(|l| l.starts_with(ERROR_MARK)).count();
+        let mut marked = Zeroizing::new(format!("{}line {}: {}\n", ERROR_MARK, err.line - marks_before + 1, err.message));
+        marked.extend(doc.split_inclusive('\n').filter(|l| !l.starts_with(ERROR_MARK)));
+        marked
+    }
+
+    enum Table {
+        None,
+        Entry,
+        Fields,
+    }
+
+    pub fn parse(doc: &str) -> Result<Vec<Edited>, ParseError> {
+        let mut lines = doc.lines();
+        let mut entries: Vec<Edited> = Vec::new();
+        let mut table = Table::None;
+        let mut line = 0;
+        while let Some(raw) = lines.next() {
+            line += 1;
+            let start = line;
+            let text = raw.trim();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+            let at = |message: String| error(start, message);
+            if let Some(header) = text.strip_prefix('[') {
+                let rest = header.trim_start().strip_prefix("entry").and_then(|r| r.trim_start().strip_prefix('.'));
+                let rest = rest.ok_or_else(|| at("expected a table header like [entry.\"<name>\"]".into()))?;
+                let (name, rest) = read_key(rest.trim_start(), &mut lines, &mut line).map_err(at)?;
+                let (fields, rest) = match rest.trim_start().strip_prefix(".fields") {
+                    Some(rest) => (true, rest),
+                    None => (false, rest),
+                };
+                let rest = rest.trim_start().strip_prefix(']').ok_or_else(|| at("expected ']' to end the table header (only .fields can follow the name)".into()))?;
+                trailing(rest).map_err(at)?;
+                let name = parse_entry_name(&name).map_err(at)?;
+                if fields {
+                    match entries.last() {
+                        Some(e) if revaultpass::names::same(&e.name, &name) => table = Table::Fields,
+                        _ => return Err(at(format!("[entry.{}.fields] must follow [entry.{}]", quote(&name), quote(&name)))),
+                    }
+                    continue;
+                }
+                if let Some(earlier) = entries.iter().find(|e| revaultpass::names::same(&e.name, &name)) {
+                    return Err(at(format!("'{}' is already defined on line {}", name, earlier.line)));
+                }
+                entries.push(Edited {
+                    line: start,
+                    name,
+                    note: false,
+                    user: String::new(),
+                    password: Secret::default(),
+                    body: None,
+                    url: None,
+                    notes: None,
+                    tags: Vec::new(),
+                    fields: BTreeMap::new(),
+                    totp: None,
+                });
+                table = Table::Entry;
+                continue;
+            }
+            let (key, rest) = read_key(text, &mut lines, &mut line).map_err(at)?;
+            let rest = rest.trim_start().strip_prefix('=').ok_or_else(|| at(format!("expected '=' after '{}'", key)))?.trim_start();
+            let Some(entry) = entries.last_mut() else {
+                return Err(at(format!("'{}' is outside any [entry.\"<name>\"] table", key)));
+            };
+            let duplicate = || at(format!("'{}' is set twice in this table", key));
+            if let Table::Fields = table {
+                let (value, rest) = string(rest, &mut lines, &mut line).map_err(at)?;
+                trailing(rest).map_err(at)?;
+                if entry.fields.insert(key.clone(), value).is_some() {
+                    return Err(duplicate());
+                }
+                continue;
+            }
+            if key == "tags" {
+                let (tags, rest) = array(rest, &mut lines, &mut line).map_err(at)?;
+                trailing(rest).map_err(at)?;
+                entry.tags = tags;
+                continue;
+            }
+            let (value, rest) = string(rest, &mut lines, &mut line).map_err(at)?;
+            trailing(rest).map_err(at)?;
+            let set = match key.as_str() {
+                "kind" => match value.as_str() {
+                    "note" => !std::mem::replace(&mut entry.note, true),
+                    "login" => !std::mem::replace(&mut entry.note, false),
+                    _ => return Err(at(format!("kind is \"login\" or \"note\", not \"{}\"", value))),
+                },
+                "user" => std::mem::replace(&mut entry.user, value).is_empty(),
+                "password" => std::mem::replace(&mut entry.password, value.into()).is_empty(),
+                "body" => entry.body.replace(value.into()).is_none(),
+                "url" => entry.url.replace(value).is_none(),
+                "notes" => entry.notes.replace(value).is_none(),
+                "totp" => {
+                    parse_totp(&value).map_err(at)?;
+                    entry.totp.replace(value.into()).is_none()
+                }
+                _ => return Err(at(format!("unknown key '{}' (expected kind, user, password, url, tags, totp, notes or body)", key))),
+            };
+            if !set {
+                return Err(duplicate());
+            }
+        }
+        for e in &entries {
+            match (e.note, e.body.is_some()) {
+                (true, false) => return Err(error(e.line, format!("note '{}' has no body", e.name))),
+                (false, true) => return Err(error(e.line, format!("'{}' has a body but no kind = \"note\"", e.name))),
+                (true, true) if !e.user.is_empty() || !e.password.is_empty() => {
+                    return Err(error(e.line, format!("note '{}' can't have a user or password", e.name)))
+                }
+                _ => {}
+            }
+        }
+        Ok(entries)
+    }
+
+    /// What applying the document would do, by name.
+    #[derive(Default)]
+    pub struct Changes {
+        pub added: Vec<String>,
+        pub removed: Vec<String>,
+        /// Names with the parts that changed; passwords only say that they did.
+        pub changed: Vec<(String, Vec<&'static str>)>,
+    }
+
+    impl Changes {
+        pub fn is_empty(&self) -> bool {
+            self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+        }
+    }
+
+    impl std::fmt::Display for Changes {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            for name in &self.added {
+                writeln!(f, "  added    {}", name)?;
+            }
+            for name in &self.removed {
+                writeln!(f, "  removed  {}", name)?;
+            }
+            for (name, parts) in &self.changed {
+                writeln!(f, "  changed  {}  ({})", name, parts.join(", "))?;
+            }
+            Ok(())
+        }
+    }
+
+    fn what_changed(e: &Entry, new: &Edited) -> Vec<&'static str> {
+        let mut parts = Vec::new();
+        let mut check = |differs: bool, part| {
+            if differs {
+                parts.push(part);
+            }
+        };
+        check(e.name != new.name, "name");
+        check(e.is_note() != new.note, "kind");
+        check(e.user != new.user, "user");
+        check(e.password != new.password, "password");
+        check(e.body != new.body, "body");
+        check(e.url != new.url, "url");
+        check(e.notes != new.notes, "notes");
+        check(e.tags != new.tags, "tags");
+        check(e.fields != new.fields, "fields");
+        check(e.totp != new.totp, "totp");
+        parts
+    }
+
+    pub fn diff(entries: &[Entry], edited: &[Edited]) -> Changes {
+        let mut changes = Changes::default();
+        for e in entries {
+            match edited.iter().find(|n| revaultpass::names::same(&n.name, &e.name)) {
+                None => changes.removed.push(e.name.clone()),
+                Some(new) => {
+                    let parts = what_changed(e, new);
+                    if !parts.is_empty() {
+                        changes.changed.push((e.name.clone(), parts));
+                    }
+                }
+            }
+        }
+        for new in edited {
+            if !entries.iter().any(|e| revaultpass::names::same(&e.name, &new.name)) {
+                changes.added.push(new.name.clone());
+            }
+        }
+        changes
+    }
+
+    /// Makes the store match the document: removed entries go to the trash,
+    /// changed ones keep their history and creation time.
+    pub fn apply(vault: &mut Vault, edited: Vec<Edited>) -> Result<(), RevaultError> {
+        let gone: Vec<String> = vault
+            .list()
+            .iter()
+            .filter(|e| !edited.iter().any(|n| revaultpass::names::same(&n.name, &e.name)))
+            .map(|e| e.name.clone())
+            .collect();
+        for name in gone {
+            vault.remove(&name)?;
+        }
+        for new in edited {
+            let Some(e) = vault.get_mut(&new.name) else {
+                let mut e = match new.note {
+                    true => Entry::note(new.name.clone(), Secret::default()),
+                    false => Entry::new(new.name.clone(), String::new(), Secret::default()),
+                };
+                fill(&mut e, new);
+                vault.add(e)?;
+                continue;
+            };
+            if !what_changed(e, &new).is_empty() {
+                fill(e, new);
+                e.touch();
+            }
+        }
+        Ok(())
+    }
+
+    fn fill(e: &mut Entry, new: Edited) {
+        e.name = new.name;
+        e.kind = if new.note { revaultpass::EntryKind::Note } else { revaultpass::EntryKind::Login };
+        e.user = new.user;
+        e.set_password(new.password);
+        e.body = new.body;
+        e.url = new.url;
+        e.notes = new.notes;
+        e.tags = new.tags;
+        e.fields = new.fields;
+        e.totp = new.totp;
+    }
+
+    /// A 0700 directory of its own under the system temp directory, so editor
+    /// swap and backup files are private too. Dropping it overwrites every
+    /// file in it with zeros before removing it all; that is best effort, as
+    /// file systems that copy on write or journal data may keep old blocks.
+    pub struct PrivateDir(PathBuf);
+
+    impl PrivateDir {
+        pub fn new() -> io::Result<PrivateDir> {
+            let mut suffix = [0u8; 8];
+            RngCore::fill_bytes(&mut OsRng, &mut suffix);
+            let suffix: String = suffix.iter().map(|b| format!("{:02x}", b)).collect();
+            let dir = std::env::temp_dir().join(format!("revaultpass-edit-{}", suffix));
+            let mut builder = fs::DirBuilder::new();
+            #[cfg(unix)]
+            std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+            // Not recursive: fails instead of reusing a directory someone made first.
+            builder.create(&dir)?;
+            Ok(PrivateDir(dir))
+        }
+
+        pub fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for PrivateDir {
+        fn drop(&mut self) {
+            if let Ok(files) = fs::read_dir(&self.0) {
+                for file in files.flatten() {
+                    shred(&file.path());
+                }
+            }
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+}
+
+
+/// `revaultpass agent`: a background process that keeps the store's data key
+/// so `list`, `get`, `add` and `delete` don't have to ask for the master key,
+/// reached over a private Unix socket. The master key itself is only used to
+/// unlock, and never kept or handed out; clients get entries, not keys.
+///
+/// Every request and response is one JSON object behind a 4-byte big-endian
+/// length: `{"op": "status" | "unlock" | "lock" | "stop" | "list" | "get" |
+/// "add" | "delete", ...}`, answered with `{"ok": true, ...}` or
+/// `{"ok": false, "error": ..., "code": <exit status>}`. Entries go both ways
+/// in the store's own JSON form.
+#[cfg(unix)]
+mod agent {
+    use super::*;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    /// Big enough for `list` with every secret of a large store.
+    const MAX_FRAME: usize = 1 << 26;
+
+    /// `$REVAULTPASS_AGENT_SOCK`, else `agent.sock` in the runtime directory
+    /// (or a per-user directory under the temp dir where there is none).
+    pub fn socket_path() -> PathBuf {
+        if let Some(path) = std::env::var_os("REVAULTPASS_AGENT_SOCK").filter(|p| !p.is_empty()) {
+            return PathBuf::from(path);
+        }
+        let dir = directories::ProjectDirs::from("com", "revaultpass", "revaultpass")
+            .and_then(|d| d.runtime_dir().map(Path::to_path_buf))
+            // SAFETY: getuid has no preconditions.
+            .unwrap_or_else(|| std::env::temp_dir().join(format!("revaultpass-{}", unsafe { libc::getuid() })));
+        dir.join("agent.sock")
+    }
+
+    fn send(stream: &mut UnixStream, message: &serde_json::Value) -> io::Result<()> {
+        let body = Zeroizing::new(serde_json::to_vec(message)?);
+        let len = u32::try_from(body.len()).map_err(|_| io::Error::other("agent message too large"))?;
+        stream.write_all(&len.to_be_bytes())?;
+        stream.write_all(&body)?;
+        stream.flush()
+    }
+
+    fn recv(stream: &mut UnixStream) -> io::Result<serde_json::Value> {
+        use std::io::Read;
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len > MAX_FRAME {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "agent message too large"));
+        }
+        let mut body = Zeroizing::new(vec![0u8; len]);
+        stream.read_exact(&mut body)?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// One request to the agent at `socket_path()`. Errors it reports come
+    /// back as `RevaultError`s where the exit status says which one.
+    pub fn request(message: serde_json::Value) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+        let path = socket_path();
+        let mut stream =
+            UnixStream::connect(&path).map_err(|e| format!("no agent at {} ({}); run 'revaultpass agent start'", path.display(), e))?;
+        // Unlocking runs the KDF, which can take a few seconds.
+        stream.set_read_timeout(Some(Duration::from_secs(60)))?;
+        send(&mut stream, &message)?;
+        let response = recv(&mut stream)?;
+        if response["ok"].as_bool() == Some(true) {
+            return Ok(response);
+        }
+        let error = response["error"].as_str().unwrap_or("unknown error").to_string();
+        let name = response["name"].as_str().unwrap_or_default().to_string();
+        Err(match response["code"].as_i64() {
+            Some(2) => RevaultError::NotFound(name).into(),
+            Some(3) => RevaultError::NameExists(name).into(),
+            Some(4) => RevaultError::WrongPassphrase.into(),
+            _ => format!("agent: {}", error).into(),
+        })
+    }
+
+    /// Whether the agent named by `REVAULTPASS_AGENT_SOCK` serves the
+    /// encrypted store at `path`, so entries can come from it. A locked agent
+    /// is unlocked with a prompted master key first when `prompt` is set, so
+    /// the next command doesn't ask again. A master key from
+    /// `--passphrase-stdin` or `REVAULTPASS_PASSPHRASE` wins over the agent.
+    pub fn serves(path: &Path, prompt: bool) -> Result<bool, RevaultError> {
+        if master_key_given() || std::env::var_os("REVAULTPASS_AGENT_SOCK").is_none_or(|s| s.is_empty()) {
+            return Ok(false);
+        }
+        if store_kind(path)? != StoreKind::Encrypted {
+            return Ok(false);
+        }
+        let status = match request(serde_json::json!({ "op": "status" })) {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("warning: {}", e);
+                return Ok(false);
+            }
+        };
+        if status["store"].as_str().map(PathBuf::from) != Some(fs::canonicalize(path)?) {
+            return Ok(false);
+        }
+        if status["unlocked"] == true || !prompt {
+            return Ok(status["unlocked"] == true);
+        }
+        let key = read_passphrase("Master key (for the agent): ")?;
+        match request(serde_json::json!({ "op": "unlock", "passphrase": key.as_str() })) {
+            Ok(_) => Ok(true),
+            Err(e) => match e.downcast::<RevaultError>() {
+                Ok(e) => Err(*e),
+                Err(e) => Err(RevaultError::Io(io::Error::other(e.to_string()))),
+            },
+        }
+    }
+
+    /// Every entry, with its secrets only if `secrets` is set.
+    pub fn entries(secrets: bool) -> Result<Vec<Entry>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut response = request(serde_json::json!({ "op": "list", "secrets": secrets }))?;
+        Ok(serde_json::from_value(response["entries"].take())?)
+    }
+
+    /// The entry named exactly `name`, secrets included.
+    pub fn get(name: &str) -> Result<Entry, Box<dyn std::error::Error + Send + Sync>> {
+        let mut response = request(serde_json::json!({ "op": "get", "name": name }))?;
+        Ok(serde_json::from_value(response["entry"].take())?)
+    }
+
+    pub fn add(entry: &Entry) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        request(serde_json::json!({ "op": "add", "entry": entry })).map(drop)
+    }
+
+    /// Moves the entries named `names` to the trash, with one save.
+    pub fn delete(names: &[String]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        request(serde_json::json!({ "op": "delete", "names": names })).map(drop)
+    }
+
+    /// The cached data key, pinned in RAM so it is never swapped out.
+    struct LockedKey(Box<DataKey>);
+
+    impl LockedKey {
+        fn new(key: DataKey) -> Self {
+            let key = Box::new(key);
+            // SAFETY: locks the pages of a live allocation; failure (e.g. RLIMIT_MEMLOCK) just leaves them swappable.
+            unsafe { libc::mlock((&*key as *const DataKey).cast(), std::mem::size_of::<DataKey>()) };
+            LockedKey(key)
+        }
+    }
+
+    impl Drop for LockedKey {
+        fn drop(&mut self) {
+            // SAFETY: the same range `new` locked; `DataKey` wipes it afterwards.
+            unsafe { libc::munlock((&*self.0 as *const DataKey).cast(), std::mem::size_of::<DataKey>()) };
+        }
+    }
+
+    /// `entry` without its secrets, as `StoreIndex` lists entries.
+    fn head(entry: &Entry) -> Entry {
+        Entry {
+            password: Secret::default(),
+            body: None,
+            notes: None,
+            fields: entry.fields.keys().map(|k| (k.clone(), String::new())).collect(),
+            totp: None,
+            history: Vec::new(),
+            ..entry.clone()
+        }
+    }
+
+    struct Agent {
+        store: PathBuf,
+        key: Option<LockedKey>,
+        idle: Option<Duration>,
+        last_used: Instant,
+    }
+
+    impl Agent {
+        fn open(&self, mode: Lock) -> Result<(fs::File, Vault), RevaultError> {
+            let lock = lock_store(&self.store, mode)?;
+            let vault = match store_kind(&self.store)? {
+                StoreKind::Plain => Vault::open_with_key(&self.store, StoreKey::Plain)?,
+                _ => {
+                    let key = self.key.as_ref().ok_or_else(|| RevaultError::Io(io::Error::other("locked; run 'revaultpass agent unlock'")))?;
+                    Vault::open_with_data_key(&self.store, &key.0)?
+                }
+            };
+            Ok((lock, vault))
+        }
+
+        fn status(&self) -> serde_json::Value {
+            let locks_in = match (&self.key, self.idle) {
+                (Some(_), Some(idle)) => serde_json::json!(idle.saturating_sub(self.last_used.elapsed()).as_secs()),
+                _ => serde_json::Value::Null,
+            };
+            serde_json::json!({ "ok": true, "store": self.store, "unlocked": self.key.is_some(), "locks_in": locks_in })
+        }
+
+        fn handle(&mut self, message: &serde_json::Value) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+            let op = message["op"].as_str().unwrap_or_default();
+            let text = |field: &str| message[field].as_str().ok_or_else(|| format!("'{}' needs a '{}' string", op, field));
+            let ok = serde_json::json!({ "ok": true });
+            if !matches!(op, "status" | "lock" | "stop") {
+                self.last_used = Instant::now();
+            }
+            Ok(match op {
+                "status" | "stop" => self.status(),
+                "lock" => {
+                    self.key = None;
+                    ok
+                }
+                "unlock" => {
+                    let pass = Zeroizing::new(text("passphrase")?.to_string());
+                    let _lock = lock_store(&self.store, Lock::Shared)?;
+                    let vault = Vault::open_with_key(&self.store, passphrase_key(&fs::read(&self.store)?, pass)?)?;
+                    let key = vault.data_key().ok_or("this store's format has no key slots to cache a key for; save it once (e.g. with 'revaultpass rekey') to upgrade it")?;
+                    self.key = Some(LockedKey::new(key));
+                    ok
+                }
+                "list" => {
+                    let (_lock, vault) = self.open(Lock::Shared)?;
+                    let entries: Vec<Entry> = match message["secrets"] == true {
+                        true => vault.list().to_vec(),
+                        false => vault.list().iter().map(head).collect(),
+                    };
+                    serde_json::json!({ "ok": true, "entries": entries })
+                }
+                "get" => {
+                    let (_lock, vault) = self.open(Lock::Shared)?;
+                    let name = text("name")?;
+                    let e = vault.get(name).ok_or_else(|| RevaultError::NotFound(name.to_string()))?;
+                    serde_json::json!({ "ok": true, "entry": e })
+                }
+                "add" => {
+                    let mut entry: Entry = serde_json::from_value(message["entry"].clone()).map_err(|e| format!("'add' needs an 'entry': {}", e))?;
+                    entry.name = parse_entry_name(&entry.name)?;
+                    let (_lock, mut vault) = self.open(Lock::Exclusive)?;
+                    vault.add(entry)?;
+                    vault.save()?;
+                    ok
+                }
+                "delete" => {
+                    let names: Vec<String> = serde_json::from_value(message["names"].clone()).map_err(|e| format!("'delete' needs 'names': {}", e))?;
+                    let (_lock, mut vault) = self.open(Lock::Exclusive)?;
+                    for name in &names {
+                        vault.remove(name)?;
+                    }
+                    vault.save()?;
+                    ok
+                }
+                _ => return Err(format!("unknown request '{}'", op).into()),
+            })
+        }
+
+        fn serve(&mut self, stream: &mut UnixStream) -> io::Result<bool> {
+            stream.set_nonblocking(false)?;
+            stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+            let message = recv(stream)?;
+            let response = self.handle(&message).unwrap_or_else(|e| {
+                let code = e.downcast_ref::<RevaultError>().map_or(1, RevaultError::exit_code);
+                // The key stopped working: the store was replaced, e.g. by a new `init`.
+                if code == 4 && message["op"] != "unlock" {
+                    self.key = None;
+                }
+                let name = match e.downcast_ref::<RevaultError>().map(RevaultError::root) {
+                    Some(RevaultError::NotFound(name) | RevaultError::NameExists(name)) => Some(name.clone()),
+                    _ => None,
+                };
+                serde_json::json!({ "ok": false, "error": e.to_string(), "code": code, "name": name })
+            });
+            send(stream, &response)?;
+            Ok(message["op"] == "stop")
+        }
+    }
+
+    /// Runs the agent for the store at `path` until `agent stop`.
+    pub fn serve(path: &Path, idle_minutes: u64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let socket = socket_path();
+        if UnixStream::connect(&socket).is_ok() {
+            return Err(format!("an agent is already running at {}", socket.display()).into());
+        }
+        if let Some(dir) = socket.parent().filter(|d| !d.as_os_str().is_empty()) {
+            perms::create_private_dir(dir)?;
+        }
+        // Left behind by an agent that was killed.
+        let _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket)?;
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&socket, fs::Permissions::from_mode(0o600))?;
+        }
+        // No core dumps or ptrace by other processes of the same user.
+        #[cfg(target_os = "linux")]
+        // SAFETY: prctl(PR_SET_DUMPABLE) only changes this process's flag.
+        unsafe {
+            libc::prctl(libc::PR_SET_DUMPABLE, 0);
+        }
+        listener.set_nonblocking(true)?;
+        let mut agent = Agent {
+            store: fs::canonicalize(path)?,
+            key: None,
+            idle: (idle_minutes > 0).then(|| Duration::from_secs(idle_minutes * 60)),
+            last_used: Instant::now(),
+        };
+        loop {
+            match listener.accept() {
+                Ok((mut stream, _)) => match agent.serve(&mut stream) {
+                    Ok(true) => break,
+                    Ok(false) => {}
+                    Err(e) => eprintln!("warning: agent request failed: {}", e),
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(100)),
+                Err(e) => return Err(e.into()),
+            }
+            if agent.idle.is_some_and(|idle| agent.last_used.elapsed() >= idle) {
+                agent.key = None;
+            }
+        }
+        drop(agent);
+        let _ = fs::remove_file(&socket);
+        Ok(())
+    }
+
+    /// `agent start` without `--foreground`: a detached copy of ourselves does
+    /// the serving; return once it is listening.
+    pub fn spawn(path: &Path, idle_minutes: u64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let socket = socket_path();
+        if UnixStream::connect(&socket).is_ok() {
+            return Err(format!("an agent is already running at {}", socket.display()).into());
+        }
+        let mut cmd = Command::new(std::env::current_exe()?);
+        if let Some(keyfile) = keyfile_path() {
+            cmd.arg("--keyfile").arg(keyfile);
+        }
+        cmd.arg("--store")
+            .arg(path)
+            .args(["agent", "start", "--foreground", "--idle", &idle_minutes.to_string()])
+            .env("REVAULTPASS_AGENT_SOCK", &socket)
+            .env_remove("REVAULTPASS_PASSPHRASE")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+        let mut child = cmd.spawn()?;
+        for _ in 0..50 {
+            if UnixStream::connect(&socket).is_ok() {
+                return Ok(());
+            }
+            if let Some(status) = child.try_wait()? {
+                return Err(format!("the agent exited at startup ({})", status).into());
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        Err(format!("the agent didn't start listening on {}", socket.display()).into())
+    }
+}
+
+/// `revaultpass tui`: a full-screen browser over the vault, drawn with plain
+/// ANSI escapes on a raw-mode terminal.
+#[cfg(unix)]
+mod tui {
+    use super::*;
+
+    const HELP: &str = "↑↓ move  / filter  r reveal  c copy password  u copy user  a add  e edit  d delete  q quit";
+
+    /// Terminal settings from before raw mode, so `restore` can put them back,
+    /// from the panic hook too.
+    static SAVED_TERMIOS: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+    fn saved_termios() -> std::sync::MutexGuard<'static, Option<libc::termios>> {
+        SAVED_TERMIOS.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Raw mode on the alternate screen until dropped.
+    struct Screen;
+
+    impl Screen {
+        fn enter() -> io::Result<Screen> {
+            // SAFETY: tcgetattr, cfmakeraw and tcsetattr only read and write the termios passed in.
+            let mut original: libc::termios = unsafe { std::mem::zeroed() };
+            if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = original;
+            unsafe { libc::cfmakeraw(&mut raw) };
+            // Keep output processing so `\n` still returns to the first column.
+            raw.c_oflag |= libc::OPOST;
+            if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            *saved_termios() = Some(original);
+            print!("\x1b[?1049h\x1b[?25l");
+            io::stdout().flush()?;
+            Ok(Screen)
+        }
+    }
+
+    impl Drop for Screen {
+        fn drop(&mut self) {
+            restore();
+        }
+    }
+
+    /// Leaves raw mode and the alternate screen; a no-op when not in them.
+    fn restore() {
+        if let Some(original) = saved_termios().take() {
+            // SAFETY: `original` is the termios tcgetattr filled in.
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original) };
+            print!("\x1b[?25h\x1b[?1049l");
+            let _ = io::stdout().flush();
+        }
+    }
+
+    fn terminal_size() -> (usize, usize) {
+        // SAFETY: TIOCGWINSZ fills in the winsize passed in.
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+            (size.ws_col as usize, size.ws_row as usize)
+        } else {
+            (80, 24)
+        }
+    }
+
+    enum Key {
+        Char(char),
+        Enter,
+        Backspace,
+        Esc,
+        Up,
+        Down,
+        PageUp,
+        PageDown,
+        Interrupt,
+        Other,
+    }
+
+    /// Reads stdin a byte at a time, bypassing `io::stdin`'s buffer so `ready`
+    /// can tell a lone Esc from the start of an arrow key sequence.
+    fn read_byte() -> io::Result<u8> {
+        let mut byte = 0u8;
+        // SAFETY: reads at most one byte into `byte`.
+        match unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) } {
+            1 => Ok(byte),
+            0 => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of input")),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    fn ready(timeout_ms: i32) -> bool {
+        let mut fd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+        // SAFETY: polls the one pollfd passed in.
+        unsafe { libc::poll(&mut fd, 1, timeout_ms) > 0 }
+    }
+
+    fn read_key() -> io::Result<Key> {
+        let key = match read_byte()? {
+            3 => Key::Interrupt,
+            b'\r' | b'\n' => Key::Enter,
+            0x7f | 0x08 => Key::Backspace,
+            0x1b if !ready(30) => Key::Esc,
+            0x1b => match (read_byte()?, read_byte()?) {
+                (b'[' | b'O', b'A') => Key::Up,
+                (b'[' | b'O', b'B') => Key::Down,
+                (b'[', b'5') if read_byte()? == b'~' => Key::PageUp,
+                (b'[', b'6') if read_byte()? == b'~' => Key::PageDown,
+                _ => Key::Other,
+            },
+            b if b < 0x20 => Key::Other,
+            b => {
+                // Collect the rest of a UTF-8 sequence.
+                let len = match b {
+                    0xf0.. => 4,
+                    0xe0.. => 3,
+                    0xc0.. => 2,
+                    _ => 1,
+                };
+                let mut bytes = vec![b];
+                for _ in 1..len {
+                    bytes.push(read_byte()?);
+                }
+                String::from_utf8(bytes).ok().and_then(|s| s.chars().next()).map_or(Key::Other, Key::Char)
+            }
+        };
+        Ok(key)
+    }
+
+    /// `text` cut or padded to exactly `width` characters.
+    fn fit(text: &str, width: usize) -> String {
+        let mut out: String = text.chars().take(width).collect();
+        let len = out.chars().count();
+        out.extend(std::iter::repeat_n(' ', width - len));
+        out
+    }
+
+    struct App {
+        path: PathBuf,
+        vault: Vault,
+        /// The store file as last read or written, to notice saves by other processes.
+        on_disk: Vec<u8>,
+        filter: String,
+        filtering: bool,
+        selected: usize,
+        scroll: usize,
+        revealed: bool,
+        message: String,
+    }
+
+    impl App {
+        /// Names matching the filter (case-insensitive substring), sorted.
+        fn visible(&self) -> Vec<String> {
+            let filter = self.filter.to_lowercase();
+            let mut names: Vec<String> =
+                self.vault.list().iter().filter(|e| e.name.to_lowercase().contains(&filter)).map(|e| e.name.clone()).collect();
+            names.sort();
+            names
+        }
+
+        fn current(&self) -> Option<&Entry> {
+            self.visible().get(self.selected).and_then(|name| self.vault.get(name))
+        }
+
+        fn select(&mut self, name: &str) {
+            self.selected = self.visible().iter().position(|n| n == name).unwrap_or(0);
+        }
+
+        fn details(&self) -> Vec<String> {
+            let Some(e) = self.current() else {
+                return vec!["(no entries)".to_string()];
+            };
+            let hidden = "•••••••• (r reveals)";
+            let mut lines = vec![format!("Name:     {}", e.name)];
+            if e.is_note() {
+                lines.push("Kind:     secure note".to_string());
+            } else {
+                lines.push(format!("User:     {}", e.user));
+                lines.push(format!("Password: {}", if self.revealed { e.password.expose() } else { hidden }));
+            }
+            if let Some(url) = &e.url {
+                lines.push(format!("URL:      {}", url));
+            }
+            if !e.tags.is_empty() {
+                lines.push(format!("Tags:     {}", e.tags.join(", ")));
+            }
+            for (k, v) in &e.fields {
+                lines.push(format!("{}: {}", k, if self.revealed { v.as_str() } else { "••••" }));
+            }
+            if e.totp.is_some() {
+                lines.push("TOTP:     set ('revaultpass otp' prints codes)".to_string());
+            }
+            if e.modified_at != 0 {
+                lines.push(format!("Changed:  {}", format_local_time(e.modified_at)));
+            }
+            let text = if e.is_note() { e.body.as_ref().map(Secret::expose) } else { e.notes.as_deref() };
+            if let Some(text) = text {
+                lines.push(String::new());
+                if e.is_note() && !self.revealed {
+                    lines.push(format!("Body:     {}", hidden));
+                } else {
+                    lines.push(if e.is_note() { "Body:" } else { "Notes:" }.to_string());
+                    lines.extend(text.lines().map(|l| format!("  {}", l)));
+                }
+            }
+            lines
+        }
+
+        fn draw(&mut self, footer: Option<&str>) -> io::Result<()> {
+            let (width, height) = terminal_size();
+            let names = self.visible();
+            self.selected = self.selected.min(names.len().saturating_sub(1));
+            let rows = height.saturating_sub(2).max(1);
+            if self.selected < self.scroll {
+                self.scroll = self.selected;
+            } else if self.selected >= self.scroll + rows {
+                self.scroll = self.selected + 1 - rows;
+            }
+            let left = (names.iter().map(|n| n.chars().count()).max().unwrap_or(0) + 2).clamp(16, (width / 3).max(16));
+            let right = width.saturating_sub(left + 3);
+            let details = self.details();
+
+            let mut out = String::from("\x1b[H");
+            let filter = match (self.filtering, self.filter.is_empty()) {
+                (true, _) => format!("  filter: {}_", self.filter),
+                (false, false) => format!("  filter: {}", self.filter),
+                (false, true) => String::new(),
+            };
+            let title = format!(" revaultpass  {}  ({} of {}){}", self.path.display(), names.len(), self.vault.list().len(), filter);
+            out.push_str(&format!("\x1b[7m{}\x1b[0m\n", fit(&title, width)));
+            for row in 0..rows {
+                let index = self.scroll + row;
+                let name = names.get(index).map(String::as_str).unwrap_or_default();
+                let cell = fit(&format!(" {}", name), left);
+                if index == self.selected && !names.is_empty() {
+                    out.push_str(&format!("\x1b[7m{}\x1b[0m", cell));
+                } else {
+                    out.push_str(&cell);
+                }
+                out.push_str(&format!(" │ {}\n", fit(details.get(row).map(String::as_str).unwrap_or_default(), right)));
+            }
+            let footer = footer.unwrap_or(if self.message.is_empty() { HELP } else { &self.message });
+            out.push_str(&format!("\x1b[7m{}\x1b[0m", fit(&format!(" {}", footer), width)));
+            print!("{}", out);
+            io::stdout().flush()
+        }
+
+        /// Reads a line in the footer; `None` if Esc cancels it.
+        fn prompt(&mut self, label: &str, masked: bool) -> io::Result<Option<Zeroizing<String>>> {
+            let mut input = Zeroizing::new(String::new());
+            loop {
+                let shown = if masked { "*".repeat(input.chars().count()) } else { input.to_string() };
+                self.draw(Some(&format!("{}{}_", label, shown)))?;
+                match read_key()? {
+                    Key::Enter => return Ok(Some(input)),
+                    Key::Esc | Key::Interrupt => return Ok(None),
+                    Key::Backspace => {
+                        input.pop();
+                    }
+                    Key::Char(c) => input.push(c),
+                    _ => {}
+                }
+            }
+        }
+
+        /// Applies `change` under the store lock and saves. If another process
+        /// saved since we last looked, its version is loaded first so nothing of
+        /// it is lost.
+        fn mutate(&mut self, change: impl FnOnce(&mut Vault) -> Result<String, Box<dyn std::error::Error + Send + Sync>>) {
+            let result = self.try_mutate(change);
+            self.message = match result {
+                Ok(message) => message,
+                Err(e) => {
+                    // Don't keep a change in memory that never reached the disk.
+                    if let Ok(vault) = Vault::open_with_key(&self.path, self.vault.key().clone()) {
+                        self.vault = vault;
+                    }
+                    format!("Error: {}", e)
+                }
+            };
+        }
+
+        fn try_mutate(
+            &mut self,
+            change: impl FnOnce(&mut Vault) -> Result<String, Box<dyn std::error::Error + Send + Sync>>,
+        ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+            let _lock = lock_store(&self.path, Lock::Exclusive)?;
+            if fs::read(&self.path)? != self.on_disk {
+                self.vault = Vault::open_with_key(&self.path, self.vault.key().clone())?;
+            }
+            let message = change(&mut self.vault)?;
+            self.vault.save()?;
+            self.on_disk = fs::read(&self.path)?;
+            Ok(message)
+        }
+
+        fn copy(&mut self, what: &str, value: Option<String>) {
+            let Some(value) = value.map(Zeroizing::new) else { return };
+            let timeout = clipboard_timeout(None);
+            self.message = match copy_with_clear(&value, timeout) {
+                Ok(()) if timeout > 0 => format!("Copied the {}, clearing in {}s", what, timeout),
+                Ok(()) => format!("Copied the {}", what),
+                Err(e) => format!("Error: {}", e),
+            };
+        }
+
+        fn add(&mut self) -> io::Result<()> {
+            let Some(name) = self.prompt("Name: ", false)? else { return Ok(()) };
+            let name = match parse_entry_name(name.trim()) {
+                Ok(name) if !name.is_empty() => name,
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    self.message = format!("Error: {}", e);
+                    return Ok(());
+                }
+            };
+            let Some(user) = self.prompt("User: ", false)? else { return Ok(()) };
+            let Some(password) = self.prompt("Password (Enter generates one): ", true)? else { return Ok(()) };
+            let password: Secret = match password.is_empty() {
+                true => match generate_password(&GenOptions::default()) {
+                    Ok(p) => p.into(),
+                    Err(e) => {
+                        self.message = format!("Error: {}", e);
+                        return Ok(());
+                    }
+                },
+                false => password.into(),
+            };
+            let strength = revaultpass::strength::estimate(password.expose());
+            let entry = Entry::new(name.clone(), user.to_string(), password);
+            self.mutate(|vault| {
+                vault.add(entry)?;
+                Ok(format!("Saved '{}' (strength {}/4, {})", name, strength.score, strength.label()))
+            });
+            self.filter.clear();
+            self.select(&name);
+            Ok(())
+        }
+
+        fn edit(&mut self, screen: &mut Option<Screen>) -> io::Result<()> {
+            let Some(e) = self.current() else { return Ok(()) };
+            let (name, user, note) = (e.name.clone(), e.user.clone(), e.is_note());
+            if note {
+                // The editor needs the terminal back for a while.
+                let current = Zeroizing::new(e.body.as_ref().map(Secret::expose).unwrap_or_default().to_string());
+                drop(screen.take());
+                let body = read_note_body(&current);
+                *screen = Some(Screen::enter()?);
+                print!("\x1b[2J");
+                match body {
+                    Ok(body) if body.expose().trim().is_empty() => self.message = "The note is empty; nothing saved".to_string(),
+                    Ok(body) => self.mutate(|vault| {
+                        let entry = vault.get_mut(&name).ok_or_else(|| RevaultError::NotFound(name.clone()))?;
+                        entry.body = Some(body);
+                        entry.touch();
+                        Ok(format!("Saved '{}'", name))
+                    }),
+                    Err(e) => self.message = format!("Error: {}", e),
+                }
+                return Ok(());
+            }
+            let Some(new_user) = self.prompt(&format!("User [{}]: ", user), false)? else { return Ok(()) };
+            let Some(password) = self.prompt("Password (Enter keeps current): ", true)? else { return Ok(()) };
+            let password: Secret = password.into();
+            self.mutate(|vault| {
+                let entry = vault.get_mut(&name).ok_or_else(|| RevaultError::NotFound(name.clone()))?;
+                if !new_user.is_empty() {
+                    entry.user = new_user.to_string();
+                }
+                if !password.is_empty() {
+                    entry.set_password(password);
+                }
+                entry.touch();
+                Ok(format!("Saved '{}'", name))
+            });
+            Ok(())
+        }
+
+        fn delete(&mut self) -> io::Result<()> {
+            let Some(name) = self.current().map(|e| e.name.clone()) else { return Ok(()) };
+            self.draw(Some(&format!("Delete '{}'? [y/N]", name)))?;
+            if matches!(read_key()?, Key::Char('y' | 'Y')) {
+                self.mutate(|vault| {
+                    vault.remove(&name)?;
+                    Ok(format!("Moved '{}' to the trash; 'revaultpass undelete' brings it back", name))
+                });
+            } else {
+                self.message.clear();
+            }
+            Ok(())
+        }
+    }
+
+    pub fn run(path: &std::path::Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return Err("the TUI needs an interactive terminal".into());
+        }
+        let key = unlock_key(path)?;
+        let (vault, on_disk) = {
+            let _lock = lock_store(path, Lock::Shared)?;
+            (open_vault(path, key)?, fs::read(path)?)
+        };
+        let mut app = App {
+            path: path.to_path_buf(),
+            vault,
+            on_disk,
+            filter: String::new(),
+            filtering: false,
+            selected: 0,
+            scroll: 0,
+            revealed: false,
+            message: String::new(),
+        };
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore();
+            default_hook(info);
+        }));
+        let mut screen = Some(Screen::enter()?);
+        print!("\x1b[2J");
+        loop {
+            app.draw(None)?;
+            let key = read_key()?;
+            let before = app.selected;
+            app.message.clear();
+            match key {
+                Key::Up => app.selected = app.selected.saturating_sub(1),
+                Key::Down => app.selected += 1,
+                Key::PageUp => app.selected = app.selected.saturating_sub(terminal_size().1.saturating_sub(2)),
+                Key::PageDown => app.selected += terminal_size().1.saturating_sub(2),
+                Key::Interrupt => break,
+                Key::Esc | Key::Enter if app.filtering => {
+                    if matches!(key, Key::Esc) {
+                        app.filter.clear();
+                    }
+                    app.filtering = false;
+                }
+                Key::Backspace if app.filtering => {
+                    app.filter.pop();
+                    app.selected = 0;
+                }
+                Key::Char(c) if app.filtering => {
+                    app.filter.push(c);
+                    app.selected = 0;
+                }
+                Key::Esc if !app.filter.is_empty() => app.filter.clear(),
+                Key::Esc | Key::Char('q') => break,
+                Key::Char('/') => app.filtering = true,
+                Key::Char('k') => app.selected = app.selected.saturating_sub(1),
+                Key::Char('j') => app.selected += 1,
+                Key::Char('r') => app.revealed = !app.revealed,
+                Key::Char('c') => {
+                    let value = app.current().map(|e| match e.is_note() {
+                        true => e.body.as_ref().map(Secret::expose).unwrap_or_default().to_string(),
+                        false => e.password.expose().to_string(),
+                    });
+                    app.copy("password", value);
+                }
+                Key::Char('u') => {
+                    let value = app.current().filter(|e| !e.is_note()).map(|e| e.user.clone());
+                    app.copy("user", value);
+                }
+                Key::Char('a') => app.add()?,
+                Key::Char('e') => app.edit(&mut screen)?,
+                Key::Char('d') => app.delete()?,
+                _ => {}
+            }
+            if app.selected != before {
+                app.revealed = false;
+            }
+        }
+        drop(screen);
+        Ok(())
+    }
+}
+
+fn main() {
+    // clap exits with 2 on bad arguments, which is "not found" here.
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => {
+            report_parse_error(&e, std::env::args().any(|a| a == "--json"));
+            std::process::exit(1);
+        }
+    };
+    JSON_OUTPUT.store(cli.json, Ordering::Relaxed);
+    if let Err(e) = run(cli) {
+        std::process::exit(report_error(&*e));
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if cli.passphrase_stdin {
+        MASTER_KEY_FROM_STDIN.store(true, Ordering::Relaxed);
+    }
+    if cli.pinentry {
+        PINENTRY.store(true, Ordering::Relaxed);
+    }
+    if cli.quiet {
+        QUIET.store(true, Ordering::Relaxed);
+    }
+    if cli.no_color {
+        NO_COLOR.store(true, Ordering::Relaxed);
+    }
+    if cli.verbose > 0 {
+        VERBOSITY.store(cli.verbose, Ordering::Relaxed);
+    }
+    if cli.ignore_signature {
+        ignore_signatures();
+    }
+    if cli.read_only || std::env::var_os("REVAULTPASS_READONLY").is_some_and(|v| !v.is_empty() && v != "0") {
+        revaultpass::set_read_only();
+    }
+    if let Some(keyfile) = &cli.keyfile {
+        *KEYFILE.lock().unwrap_or_else(std::sync::PoisonError::into_inner) = Some(keyfile.clone());
+    }
+    let json = cli.json;
+    // `--store <path>` works with every command, ahead of REVAULTPASS_STORE, the config file and the default location.
+    let (path, source) = match cli.store.filter(|p| !p.is_empty()) {
+        Some(p) => (PathBuf::from(p), "--store"),
+        None => match std::env::var_os("REVAULTPASS_STORE").filter(|p| !p.is_empty()) {
+            Some(p) => (PathBuf::from(p), "REVAULTPASS_STORE"),
+            None => match config::get().store.clone() {
+                Some(p) => (p, "the config file"),
+                None => (store_path().ok_or("could not determine data directory")?, "the default location"),
+            },
+        },
+    };
+    info!("store {} (from {})", path.display(), source);
+    if let Some(keep) = config::get().backup_keep {
+        revaultpass::set_backup_keep(keep);
+    }
+    if config::get().journal == Some(false) {
+        revaultpass::disable_journal();
+    }
+    if config::get().case_insensitive_names == Some(true) {
+        revaultpass::names::set_case_insensitive(true);
+    }
+
+    // Before anything is asked for, so nothing is typed in vain.
+    if revaultpass::read_only() && cli.command.writes_store() {
+        return Err(revaultpass::READ_ONLY_MODE.at(&path).into());
+    }
+    match cli.command {
+        Cmd::Init { kdf, recovery_key, cipher, force } => {
+            let exists = || fs::symlink_metadata(&path).is_ok();
+            let already = || format!("store already exists at {}; use --force to overwrite or 'rekey' to change the key", path.display());
+            if exists() && !force {
+                return Err(already().into());
+            }
+            let kdf = kdf_from_args(&kdf, KdfParams::default())?;
+            let keyfile = keyfile_path().map(|p| read_keyfile(&p)).transpose()?;
+            status!("RevaultPass init. Encryption is recommended.");
+            let prompt = "Set master key (or leave empty for no encryption): ";
+            let pass = match master_key_given() {
+                true => read_master_key(prompt)?,
+                false => read_new_passphrase(prompt, "Repeat master key: ").map_err(no_terminal_hint)?.ok_or("keys do not match; nothing written")?,
+            };
+            if pass.is_empty() && keyfile.is_some() {
+                return Err("a keyfile only works together with a master key".into());
+            }
+            if pass.is_empty() && recovery_key {
+                return Err("a recovery key only works together with a master key".into());
+            }
+            if pass.is_empty() && cipher.is_some() {
+                return Err("a cipher only works together with a master key".into());
+            }
+            let key = StoreKey::from_passphrase(pass, kdf).with_keyfile(keyfile);
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            // Checked again under the lock: another process may have created it meanwhile.
+            if exists() {
+                if !force {
+                    return Err(already().into());
+                }
+                let aside = move_store_aside(&path)?;
+                status!("Moved the existing store to {}.", aside.display());
+            }
+            let mut vault = Vault::create_with_cipher(&path, key, cipher.unwrap_or_default())?;
+            let recovery = if recovery_key {
+                let recovery = vault.add_recovery_key()?;
+                vault.save()?;
+                Some(recovery)
+            } else {
+                None
+            };
+            match vault.key() {
+                StoreKey::Plain => status!("Store created (unencrypted). Use 'revaultpass rekey' to set a key."),
+                StoreKey::Passphrase(_, _, Some(_)) => {
+                    status!("Store created. Your data is encrypted with your key and the keyfile; both are needed to open it.")
+                }
+                StoreKey::Passphrase(..) => status!("Store created. Your data is encrypted with your key."),
+            }
+            if let Some(recovery) = recovery {
+                show_recovery_key(&recovery);
+            }
+        }
+        Cmd::Add { name, user, password: pass_entry, password_stdin, allow_empty, generate, copy, notes, url, tags, fields, totp, enforce_policy, batch, on_conflict } => {
+            if on_conflict.is_some() && !batch {
+                return Err(usage("usage: revaultpass add --batch [--on-conflict skip|overwrite|rename] < entries.ndjson"));
+            }
+            require_store(&path)?;
+            let min_score = enforce_policy.map(|min| min.unwrap_or(POLICY_MIN_SCORE));
+            if batch {
+                // With --passphrase-stdin the key is the first line and the entries the rest.
+                if MASTER_KEY_FROM_STDIN.load(Ordering::Relaxed) {
+                    read_master_key("")?;
+                }
+                let mut text = Zeroizing::new(String::new());
+                io::Read::read_to_string(&mut io::stdin(), &mut text)?;
+                let entries = parse_batch(&text, allow_empty, min_score).map_err(|errors| {
+                    for e in &errors {
+                        eprintln!("{}", e);
+                    }
+                    format!("{} invalid line(s); nothing saved", errors.len())
+                })?;
+                let key = unlock_key(&path)?;
+                let _lock = lock_store(&path, Lock::Exclusive)?;
+                let mut vault = open_vault(&path, key)?;
+                let summary = merge_entries(vault.entries_mut(), entries, on_conflict.unwrap_or(OnConflict::Skip));
+                if summary.imported == 0 {
+                    status!("Nothing saved: {}", summary);
+                    return Ok(());
+                }
+                vault.save()?;
+                status!("Saved {} entries: {}", summary.imported, summary);
+                return Ok(());
+            }
+            let (Some(name), Some(user)) = (name, user) else {
+                unreachable!("name and user are required without --batch");
+            };
+            let generated = generate.is_some();
+            let password: Secret = match (generate, pass_entry) {
+                (Some(_), Some(_)) => return Err("--generate cannot be combined with a password argument".into()),
+                (Some(len), None) => {
+                    let defaults = GenOptions::default();
+                    generate_password(&GenOptions { length: len.unwrap_or(defaults.length), ..defaults })?.into()
+                }
+                (None, Some(p)) => {
+                    eprintln!("warning: a password given as an argument can end up in shell history and 'ps' output; use --password-stdin or the prompt");
+                    p.into()
+                }
+                (None, None) if password_stdin => stdin_line()?.into(),
+                (None, None) => {
+                    let typed = read_passphrase("Password: ").map_err(|e| match e.kind() {
+                        io::ErrorKind::NotConnected => format!("{}; pipe it in with --password-stdin, or use --generate", e),
+                        _ => e.to_string(),
+                    })?;
+                    if !typed.is_empty() && read_passphrase("Repeat password: ")? != typed {
+                        return Err("passwords do not match; nothing saved".into());
+                    }
+                    typed.into()
+                }
+            };
+            if password.is_empty() && !allow_empty {
+                return Err("the password is empty; pass --allow-empty if that is intended".into());
+            }
+            // Generated passwords are strong by construction.
+            if !generated && !password.is_empty() {
+                check_strength(password.expose(), min_score)?;
+            }
+            let fields = fields.into_iter().collect();
+            let totp = totp.map(Secret::from);
+            add_entry(&path, Entry { url, notes, tags, fields, totp, ..Entry::new(name, user, password.clone()) })?;
+            status!("Saved.");
+            if generated {
+                if copy {
+                    let timeout = clipboard_timeout(None);
+                    match copy_with_clear(password.expose(), timeout) {
+                        Ok(()) if timeout > 0 => status!("Generated password copied to clipboard, clearing in {}s", timeout),
+                        Ok(()) => status!("Generated password copied to clipboard."),
+                        Err(e) => {
+                            eprintln!("warning: {}; printing instead", e);
+                            println!("{}", password.expose());
+                        }
+                    }
+                } else {
+                    println!("{}", password.expose());
+                }
+            }
+        }
+        Cmd::AddNote { name, tags } => {
+            require_store(&path)?;
+            // The key comes first, so `--passphrase-stdin` takes the first line and the note the rest.
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            // Checked before the editor opens rather than after the note is written.
+            if vault.get(&name).is_some() {
+                return Err(RevaultError::NameExists(name).into());
+            }
+            let body = read_note_body("")?;
+            if body.expose().trim().is_empty() {
+                return Err("the note is empty; nothing saved".into());
+            }
+            vault.add(Entry { tags, ..Entry::note(name, body) })?;
+            vault.save()?;
+            status!("Saved.");
+        }
+        Cmd::List { group, tree, tag, long, plain, sort, show_passwords } => {
+            if show_passwords && !json {
+                return Err(usage("usage: revaultpass list --json --show-passwords"));
+            }
+            if tree && json {
+                return Err(usage("usage: revaultpass list --tree (not with --json)"));
+            }
+            // `work` and `work/` both mean the group.
+            let prefix = group.map(|g| format!("{}/", g.trim_end_matches('/')));
+            let all = Entries::open(&path, show_passwords, Lock::Shared)?;
+            let mut entries: Vec<&Entry> = all
+                .list()
+                .iter()
+                .filter(|e| tag.as_ref().is_none_or(|t| e.tags.contains(t)))
+                .filter(|e| prefix.as_ref().is_none_or(|p| e.name.starts_with(p.as_str())))
+                .collect();
+            match sort {
+                Some(SortKey::Name) => entries.sort_by(|a, b| a.name.cmp(&b.name)),
+                Some(SortKey::Created) => entries.sort_by_key(|e| std::cmp::Reverse(e.created_at)),
+                Some(SortKey::Modified) => entries.sort_by_key(|e| std::cmp::Reverse(e.modified_at)),
+                None => {}
+            }
+            if json {
+                let items: Vec<serde_json::Value> = entries.iter().map(|e| entry_json(e, show_passwords, false)).collect();
+                println!("{}", serde_json::Value::from(items));
+            } else if entries.is_empty() {
+                println!("(none)");
+            } else if tree {
+                print_tree(&entries);
+            } else if !plain {
+                print_list_table(&entries, long);
+            } else {
+                for e in entries {
+                    match e.tags.is_empty() {
+                        true => println!("  {}  {}", e.name, entry_summary(e)),
+                        false => println!("  {}  {}  [{}]", e.name, entry_summary(e), e.tags.join(", ")),
+                    }
+                }
+            }
+        }
+        Cmd::Names { plain } => {
+            let (index, entries);
+            let all = match plain {
+                true => {
+                    let key = unlock_key(&path)?;
+                    let _lock = lock_store(&path, Lock::Shared)?;
+                    index = Vault::open_index(&path, key)?;
+                    index.list()
+                }
+                false => {
+                    entries = Entries::open(&path, false, Lock::Shared)?;
+                    entries.list()
+                }
+            };
+            let mut names: Vec<&str> = all.iter().map(|e| e.name.as_str()).collect();
+            names.sort_unstable();
+            if json && !plain {
+                println!("{}", serde_json::json!(names));
+            } else {
+                for name in names {
+                    println!("{}", name);
+                }
+            }
+        }
+        Cmd::Get { entry: mut names, from_file, strict, copy, osc52, no_prompt_echo, timeout, exact, full, field, format, field_name } => {
+            if names.len() > 1 || from_file.is_some() {
+                if copy || field.is_some() || format.is_some() || field_name.is_some() {
+                    return Err(usage("usage: revaultpass get <name>... [--from-file <file>] [--strict] [--json] (--copy, --field, --format and --field-name take one name)"));
+                }
+                if let Some(file) = from_file {
+                    names.extend(read_names(&file)?);
+                }
+                let index = Entries::open(&path, false, Lock::Shared)?;
+                // Exact names only: a script asking for several secrets shouldn't get a near miss.
+                let mut found: Vec<Entry> = Vec::new();
+                let mut failed = false;
+                for name in &names {
+                    match index.get(name) {
+                        // A body has line breaks, which don't fit the one line per entry.
+                        Some(e) if e.is_note() && !json => {
+                            let err: Box<dyn std::error::Error + Send + Sync> = format!("'{}' is a note; get it on its own or use --json", e.name).into();
+                            report_error(&*err);
+                            failed = true;
+                        }
+                        Some(e) => found.push(index.load(e)?),
+                        None => {
+                            report_error(&RevaultError::NotFound(name.clone()));
+                            failed = true;
+                        }
+                    }
+                }
+                // The exit code of a missing entry, as for one name.
+                let code = RevaultError::NotFound(String::new()).exit_code();
+                if failed && strict {
+                    return Err(Exit(code).into());
+                }
+                if json {
+                    let map: serde_json::Map<String, serde_json::Value> = found.iter().map(|e| (e.name.clone(), entry_json(e, true, full))).collect();
+                    println!("{}", serde_json::Value::from(map));
+                } else {
+                    for e in found {
+                        println!("{}\t{}\t{}", e.name, e.user, e.password.expose());
+                    }
+                }
+                if failed {
+                    return Err(Exit(code).into());
+                }
+                return Ok(());
+            }
+            let timeout = clipboard_timeout(timeout);
+            OSC52.store(osc52, Ordering::Relaxed);
+            NO_PROMPT_ECHO.store(no_prompt_echo, Ordering::Relaxed);
+            let name = entry_arg(names.remove(0))?;
+            if json && (field.is_some() || format.is_some() || field_name.is_some()) {
+                return Err(usage("usage: revaultpass get <name> [--field <field> | --format <template> | --field-name <name>] (not with --json)"));
+            }
+            let template = format.as_deref().map(|f| parse_template(f, "--format")).transpose()?;
+            let index = Entries::open(&path, false, Lock::Shared)?;
+            let found = match revaultpass::names::resolve(index.list(), &name) {
+                Resolved::Exact(e) => Some(e),
+                Resolved::Fuzzy(e) if !exact => {
+                    if !no_prompt_echo {
+                        status!("(resolved to '{}')", e.name);
+                    }
+                    Some(e)
+                }
+                Resolved::Ambiguous(candidates) if !exact && (json || no_prompt_echo) => {
+                    let names: Vec<&str> = candidates.iter().map(|e| e.name.as_str()).collect();
+                    return Err(format!("'{}' matches several entries: {}", name, names.join(", ")).into());
+                }
+                Resolved::Ambiguous(candidates) if !exact => {
+                    eprintln!("Multiple matches:");
+                    for e in candidates {
+                        eprintln!("  {}", e.name);
+                    }
+                    return Err(Exit(1).into());
+                }
+                _ => None,
+            };
+            if let Some(e) = found {
+                let e = &index.load(e)?;
+                let body = e.body.as_ref().map(Secret::expose).unwrap_or_default();
+                if copy {
+                    let secret = if e.is_note() { body } else { e.password.expose() };
+                    match copy_with_clear(secret, timeout) {
+                        Ok(()) if no_prompt_echo => {}
+                        Ok(()) if timeout > 0 => status!("Copied to clipboard, clearing in {}s", timeout),
+                        Ok(()) => status!("Copied to clipboard."),
+                        Err(err) if json || no_prompt_echo => return Err(err),
+                        Err(err) if e.is_note() => {
+                            eprintln!("warning: {}; printing instead", err);
+                            print!("{}", body);
+                        }
+                        Err(err) => {
+                            eprintln!("warning: {}; printing instead", err);
+                            println!("{}:{}", e.user, e.password.expose());
+                        }
+                    }
+                    if json {
+                        println!("{}", entry_json(e, false, full));
+                    }
+                } else if let Some(field) = field {
+                    println!("{}", field.of(e));
+                } else if let Some(field) = field_name {
+                    let value = e.fields.get(&field).ok_or_else(|| format!("'{}' has no field '{}'", e.name, field))?;
+                    println!("{}", value);
+                } else if let Some(pieces) = &template {
+                    println!("{}", *render_template(pieces, e));
+                } else if json {
+                    println!("{}", entry_json(e, true, full));
+                } else if e.is_note() {
+                    print!("{}", body);
+                } else {
+                    println!("{}:{}", e.user, e.password.expose());
+                    if let Some(url) = &e.url {
+                        println!("url: {}", url);
+                    }
+                    if !e.tags.is_empty() {
+                        println!("tags: {}", e.tags.join(", "));
+                    }
+                    for (k, v) in &e.fields {
+                        println!("{}: {}", k, v);
+                    }
+                    if let (true, Some(notes)) = (full, &e.notes) {
+                        println!("notes:");
+                        for line in notes.lines() {
+                            println!("  {}", line);
+                        }
+                    }
+                }
+            } else {
+                return Err(RevaultError::NotFound(name).into());
+            }
+        }
+        Cmd::Exec { entry, env, with_user, command } => {
+            if entry.is_none() && env.iter().any(|m| m.entry.is_none()) {
+                return Err(usage("usage: revaultpass exec <name> --env VAR -- <command> (or --env VAR=<name>)"));
+            }
+            let key = unlock_key(&path)?;
+            let mut vars: Vec<(String, Zeroizing<String>)> = Vec::new();
+            {
+                let _lock = lock_store(&path, Lock::Shared)?;
+                let vault = open_vault(&path, key)?;
+                for mapping in &env {
+                    let name = mapping.entry.as_ref().or(entry.as_ref()).map(String::as_str).unwrap_or_default();
+                    let e = vault.get(name).ok_or_else(|| RevaultError::NotFound(name.to_string()))?;
+                    let secret = if e.is_note() { e.body.as_ref().map(Secret::expose).unwrap_or_default() } else { e.password.expose() };
+                    vars.push((mapping.var.clone(), Zeroizing::new(secret.to_string())));
+                    if with_user && !e.is_note() {
+                        vars.push((format!("{}_USER", mapping.var), Zeroizing::new(e.user.clone())));
+                    }
+                }
+            }
+            let mut child = Command::new(&command[0]);
+            child.args(&command[1..]).env_remove("REVAULTPASS_PASSPHRASE");
+            for (var, value) in &vars {
+                child.env(var, value.as_str());
+            }
+            #[cfg(unix)]
+            {
+                use std::os::unix::process::CommandExt;
+                // Only returns if the command couldn't be started.
+                let err = child.exec();
+                return Err(format!("cannot run {}: {}", command[0].to_string_lossy(), err).into());
+            }
+            #[cfg(not(unix))]
+            {
+                let status = child.status().map_err(|e| format!("cannot run {}: {}", command[0].to_string_lossy(), e))?;
+                drop(vars);
+                return Err(Exit(status.code().unwrap_or(1)).into());
+            }
+        }
+        Cmd::GitCredential { action, pattern } => {
+            // stdin carries git's request, so the key has to come from elsewhere.
+            if MASTER_KEY_FROM_STDIN.load(Ordering::Relaxed) {
+                return Err(usage("usage: revaultpass git-credential (not with --passphrase-stdin; set REVAULTPASS_PASSPHRASE)"));
+            }
+            let request = read_credential_request()?;
+            if !request.contains_key("host") {
+                return Ok(());
+            }
+            let name = credential_entry_name(&pattern, &request)?;
+            let field = |key: &str| request.get(key).map(|v| v.as_str()).unwrap_or_default();
+            match action {
+                CredentialAction::Get => {
+                    let entries = Entries::open(&path, false, Lock::Shared)?;
+                    // Nothing printed for an unknown host, so git asks the user.
+                    if let Some(e) = entries.get(&name).filter(|e| !e.is_note()) {
+                        if field("username").is_empty() || field("username") == e.user {
+                            let e = entries.load(e)?;
+                            println!("username={}", e.user);
+                            println!("password={}", e.password.expose());
+                        }
+                    }
+                }
+                CredentialAction::Store => {
+                    if field("password").is_empty() {
+                        return Ok(());
+                    }
+                    let key = unlock_key(&path)?;
+                    let _lock = lock_store(&path, Lock::Exclusive)?;
+                    let mut vault = open_vault(&path, key)?;
+                    let password = Secret::from(field("password").to_string());
+                    match vault.get_mut(&name) {
+                        Some(e) if e.user == field("username") && e.password == password => return Ok(()),
+                        Some(e) => {
+                            e.user = field("username").to_string();
+                            e.set_password(password);
+                            e.touch();
+                        }
+                        None => vault.add(Entry::new(name, field("username").to_string(), password))?,
+                    }
+                    vault.save()?;
+                }
+                CredentialAction::Erase => {
+                    let key = unlock_key(&path)?;
+                    let _lock = lock_store(&path, Lock::Exclusive)?;
+                    let mut vault = open_vault(&path, key)?;
+                    // Only the credential git rejected, not one stored since.
+                    let matches = vault.get(&name).is_some_and(|e| {
+                        (field("username").is_empty() || field("username") == e.user)
+                            && (field("password").is_empty() || field("password") == e.password.expose())
+                    });
+                    if matches {
+                        vault.remove(&name)?;
+                        vault.save()?;
+                    }
+                }
+            }
+        }
+        Cmd::Askpass { prompt, regex, pattern } => {
+            let re = regex::Regex::new(&regex)?;
+            let caps = re.captures(&prompt).ok_or_else(|| format!("nothing in the prompt matches '{}'", regex))?;
+            let identifier = caps.iter().skip(1).flatten().next().or_else(|| caps.get(0)).map_or("", |m| m.as_str());
+            let name = parse_entry_name(&pattern.replace("{match}", identifier))?;
+            // There is no terminal to prompt on, and ssh falls back to its own prompt on failure.
+            #[cfg(unix)]
+            if agent::serves(&path, false)? {
+                let e = Some(agent::get(&name)?).filter(|e| !e.is_note()).ok_or_else(|| RevaultError::NotFound(name.clone()))?;
+                println!("{}", e.password.expose());
+                return Ok(());
+            }
+            let key = match store_kind(&path)? {
+                StoreKind::Encrypted => {
+                    let pass = std::env::var("REVAULTPASS_PASSPHRASE")
+                        .map(Zeroizing::new)
+                        .map_err(|_| "askpass needs an unlocked agent or REVAULTPASS_PASSPHRASE for an encrypted store")?;
+                    passphrase_key(&fs::read(&path)?, pass)?
+                }
+                _ => unlock_key(&path)?,
+            };
+            let _lock = lock_store(&path, Lock::Shared)?;
+            let vault = open_vault(&path, key)?;
+            let e = vault.get(&name).filter(|e| !e.is_note()).ok_or_else(|| RevaultError::NotFound(name.clone()))?;
+            println!("{}", e.password.expose());
+        }
+        Cmd::Search { query, regex: use_regex } => {
+            let matcher: Box<dyn Fn(&str) -> bool> = if use_regex {
+                let re = regex::RegexBuilder::new(&query).case_insensitive(true).build()?;
+                Box::new(move |s| re.is_match(s))
+            } else {
+                let needle = query.to_lowercase();
+                Box::new(move |s| s.to_lowercase().contains(&needle))
+            };
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Shared)?;
+            let index = open_index(&path, key)?;
+            let found: Vec<&Entry> = index.list().iter().filter(|e| matcher(&e.name) || matcher(&e.user)).collect();
+            if found.is_empty() {
+                println!("(no matches)");
+                return Err(Exit(RevaultError::NotFound(query).exit_code()).into());
+            }
+            for e in found {
+                println!("  {}  {}", e.name, entry_summary(e));
+            }
+        }
+        Cmd::Grep { pattern, ignore_case, fixed_strings, include_passwords } => {
+            let source = if fixed_strings { regex::escape(&pattern) } else { pattern.clone() };
+            let re = regex::RegexBuilder::new(&source).case_insensitive(ignore_case).build()?;
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Shared)?;
+            let vault = open_vault(&path, key)?;
+            let color = use_color();
+            let mut found = 0;
+            let mut items = Vec::new();
+            for e in vault.list() {
+                for (field, text) in grep_targets(e, include_passwords) {
+                    for line in text.lines().filter(|l| re.is_match(l)) {
+                        found += 1;
+                        if json {
+                            items.push(serde_json::json!({ "name": e.name, "field": field, "line": line }));
+                        } else {
+                            println!("{}: {}: {}", ansi(color, "1", &e.name), ansi(color, "2", &field), highlight_matches(line, &re, color));
+                        }
+                    }
+                }
+            }
+            if json {
+                println!("{}", serde_json::Value::from(items));
+            }
+            if found == 0 {
+                if !json {
+                    println!("(no matches)");
+                }
+                return Err(Exit(RevaultError::NotFound(pattern).exit_code()).into());
+            }
+        }
+        Cmd::Audit { min_length, max_age } => {
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Shared)?;
+            let vault = open_vault(&path, key)?;
+            let findings = audit_entries(vault.list(), min_length, max_age, unix_now());
+            if json {
+                let items: Vec<serde_json::Value> = findings
+                    .iter()
+                    .map(|f| {
+                        let mut obj = serde_json::json!({ "severity": f.severity.as_str(), "kind": f.kind, "entries": f.names });
+                        if let Some(detail) = &f.detail {
+                            obj["detail"] = detail.as_str().into();
+                        }
+                        obj
+                    })
+                    .collect();
+                println!("{}", serde_json::Value::from(items));
+            } else if findings.is_empty() {
+                println!("No problems found.");
+            } else {
+                let mut severity = None;
+                for f in &findings {
+                    if severity != Some(f.severity) {
+                        severity = Some(f.severity);
+                        println!("{}:", f.severity.as_str());
+                    }
+                    match &f.detail {
+                        Some(detail) => println!("  {}: {} ({})", f.kind, f.names.join(", "), detail),
+                        None => println!("  {}: {}", f.kind, f.names.join(", ")),
+                    }
+                }
+            }
+            if !findings.is_empty() {
+                return Err(Exit(FINDINGS_EXIT).into());
+            }
+        }
+        Cmd::Pwned { entry, range_dir } => {
+            let key = unlock_key(&path)?;
+            // Network lookups can take a while; don't hold the lock through them.
+            let vault = {
+                let _lock = lock_store(&path, Lock::Shared)?;
+                open_vault(&path, key)?
+            };
+            let targets: Vec<&Entry> = match &entry {
+                Some(name) => vec![vault.get(name).ok_or_else(|| RevaultError::NotFound(name.clone()))?],
+                None => vault.list().iter().filter(|e| !e.is_note() && !e.password.is_empty()).collect(),
+            };
+            if let [e] = targets.as_slice() {
+                if e.is_note() || e.password.is_empty() {
+                    return Err(format!("'{}' has no password to check", e.name).into());
+                }
+            }
+            let progress = targets.len() > 1 && io::stderr().is_terminal();
+            let mut ranges = std::collections::HashMap::new();
+            let mut results = Vec::new();
+            for (i, e) in targets.iter().enumerate() {
+                if progress {
+                    eprint!("\rChecking {}/{}...", i + 1, targets.len());
+                }
+                let count = pwned_count(e.password.expose(), range_dir.as_deref(), &mut ranges);
+                if progress {
+                    eprint!("\r\x1b[K");
+                }
+                results.push((e.name.as_str(), count?));
+            }
+            let breached = results.iter().filter(|(_, count)| *count > 0).count();
+            if json {
+                let items: Vec<serde_json::Value> =
+                    results.iter().map(|(name, count)| serde_json::json!({ "name": name, "count": count })).collect();
+                println!("{}", serde_json::Value::from(items));
+            } else if entry.is_some() {
+                match results[0] {
+                    (name, 0) => println!("{}: not found in any known breach", name),
+                    (name, count) => println!("{}: seen {} times in breaches; change it", name, count),
+                }
+            } else {
+                for (name, count) in results.iter().filter(|(_, count)| *count > 0) {
+                    println!("  {}  seen {} times", name, count);
+                }
+                println!("{} of {} passwords found in known breaches.", breached, results.len());
+            }
+            if breached > 0 {
+                return Err(Exit(FINDINGS_EXIT).into());
+            }
+        }
+        Cmd::Delete { entry: name, force } => {
+            let mut vault = Entries::open(&path, false, Lock::Exclusive)?;
+            // A name that exists is taken literally, even if it contains `*` or `?`.
+            let targets: Vec<(String, String)> = match vault.get(&name) {
+                Some(e) => vec![(e.name.clone(), entry_summary(e))],
+                None if name.contains(['*', '?']) => vault
+                    .list()
+                    .iter()
+                    .filter(|e| glob_match(&name, &e.name))
+                    .map(|e| (e.name.clone(), entry_summary(e)))
+                    .collect(),
+                None => Vec::new(),
+            };
+            if targets.is_empty() {
+                return Err(RevaultError::NotFound(name).into());
+            }
+            if !force && config::get().confirm_delete != Some(false) {
+                let question = match targets.as_slice() {
+                    [(name, _)] => match vault.get(name).filter(|e| !e.is_note()) {
+                        Some(e) => format!("Delete entry '{}' (user {})? [y/N] ", name, e.user),
+                        None => format!("Delete note '{}'? [y/N] ", name),
+                    },
+                    _ => {
+                        for (name, summary) in &targets {
+                            eprintln!("  {}  {}", name, summary);
+                        }
+                        format!("Delete these {} entries? [y/N] ", targets.len())
+                    }
+                };
+                if !confirm(&question)? {
+                    status!("Aborted.");
+                    return Ok(());
+                }
+            }
+            vault.delete(&targets.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>())?;
+            match targets.as_slice() {
+                [(name, _)] => status!("Moved to trash; 'revaultpass undelete {}' brings it back.", name),
+                _ => status!("Moved {} entries to trash.", targets.len()),
+            }
+        }
+        Cmd::Undelete { name } => {
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            vault.undelete(&name)?;
+            vault.save()?;
+            status!("Restored '{}'.", name);
+        }
+        Cmd::History { action: Some(HistoryCmd::Clear { entry: name }), .. } => {
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            let entry = vault.get_mut(&name).ok_or_else(|| RevaultError::NotFound(name.clone()))?;
+            let count = entry.history.len();
+            entry.history.clear();
+            vault.save()?;
+            status!("Removed {} earlier passwords of '{}'.", count, name);
+        }
+        Cmd::History { entry, .. } => {
+            let name = entry.unwrap_or_default();
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Shared)?;
+            let vault = open_vault(&path, key)?;
+            let entry = vault.get(&name).ok_or_else(|| RevaultError::NotFound(name.clone()))?;
+            if entry.history.is_empty() {
+                println!("(none)");
+            }
+            for old in entry.history.iter().rev() {
+                println!("  {} UTC  {}", format_unix_time(old.replaced_at), old.password.expose());
+            }
+        }
+        Cmd::Trash { action: TrashCmd::List } => {
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Shared)?;
+            let vault = open_vault(&path, key)?;
+            if vault.trash().is_empty() {
+                println!("(none)");
+            }
+            for t in vault.trash() {
+                println!("  {}  {}  (deleted {} UTC)", t.entry.name, entry_summary(&t.entry), format_unix_time(t.deleted_at));
+            }
+        }
+        Cmd::Trash { action: TrashCmd::Empty { older_than, force } } => {
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            let cutoff = older_than.map(|age| unix_now().saturating_sub(age));
+            let count = vault.trash().iter().filter(|t| cutoff.is_none_or(|c| t.deleted_at <= c)).count();
+            if count == 0 {
+                status!("Nothing to remove.");
+                return Ok(());
+            }
+            if !force && config::get().confirm_trash_empty != Some(false) && !confirm(&format!("Permanently remove {} deleted entries? [y/N] ", count))? {
+                status!("Aborted.");
+                return Ok(());
+            }
+            vault.empty_trash(cutoff);
+            vault.save()?;
+            status!("Removed {} entries from the trash.", count);
+        }
+        Cmd::Edit { entry: name, user: new_user, password: new_password, fields, unset_fields, totp, enforce_policy } => {
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            let Some(entry) = vault.get_mut(&name) else {
+                return Err(RevaultError::NotFound(name).into());
+            };
+            // With flags given, only touch those fields and don't prompt for the rest.
+            let interactive = new_user.is_none() && new_password.is_none() && fields.is_empty() && unset_fields.is_empty() && totp.is_none();
+            if entry.is_note() {
+                if !interactive {
+                    return Err(format!("'{}' is a note; 'revaultpass edit {}' without flags changes its body", name, name).into());
+                }
+                let body = read_note_body(entry.body.as_ref().map(Secret::expose).unwrap_or_default())?;
+                if body.expose().trim().is_empty() {
+                    return Err("the note is empty; nothing saved ('revaultpass delete' removes it)".into());
+                }
+                entry.body = Some(body);
+                entry.touch();
+                vault.save()?;
+                status!("Saved.");
+                return Ok(());
+            }
+            for field in &unset_fields {
+                if entry.fields.remove(field).is_none() {
+                    return Err(format!("'{}' has no field '{}'", name, field).into());
+                }
+            }
+            entry.fields.extend(fields);
+            if let Some(totp) = totp {
+                entry.totp = Some(totp).filter(|t| !t.is_empty()).map(Secret::from);
+            }
+            let user = match new_user {
+                Some(u) => u,
+                None if interactive => read_line(&format!("User [{}]: ", entry.user))?,
+                None => String::new(),
+            };
+            let password: Secret = match new_password {
+                Some(p) => p.into(),
+                None if interactive => read_passphrase("Password (Enter to keep current): ")?.into(),
+                None => Secret::default(),
+            };
+            if !user.is_empty() {
+                entry.user = user;
+            }
+            if !password.is_empty() {
+                check_strength(password.expose(), enforce_policy.map(|min| min.unwrap_or(POLICY_MIN_SCORE)))?;
+                entry.set_password(password);
+            }
+            entry.touch();
+            vault.save()?;
+            status!("Saved.");
+        }
+        Cmd::Regenerate { entry: name, length, no_symbols, no_digits, no_uppercase, exclude_ambiguous, copy, timeout } => {
+            let timeout = clipboard_timeout(timeout);
+            let opts = GenOptions::from_flags(length, no_uppercase, no_digits, no_symbols, exclude_ambiguous);
+            let password: Secret = generate_password(&opts)?.into();
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            let entry = vault.get_mut(&name).ok_or_else(|| RevaultError::NotFound(name.clone()))?;
+            if entry.is_note() {
+                return Err(format!("'{}' is a note and has no password", name).into());
+            }
+            // Show the new password before saving, so the store never holds one the user hasn't seen.
+            let copied = copy
+                && match copy_with_clear(password.expose(), timeout) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        eprintln!("warning: {}; printing instead", err);
+                        false
+                    }
+                };
+            if json {
+                let mut obj = serde_json::json!({ "name": name });
+                if !copied {
+                    obj["password"] = password.expose().into();
+                }
+                println!("{}", obj);
+            } else if !copied {
+                println!("{}", password.expose());
+            }
+            entry.set_password(password);
+            vault.save()?;
+            match (copied, timeout) {
+                (true, 0) => status!("Saved; the new password is on the clipboard."),
+                (true, _) => status!("Saved; the new password is on the clipboard, clearing in {}s.", timeout),
+                (false, _) => status!("Saved; 'revaultpass history {}' has the old password.", name),
+            }
+        }
+        Cmd::Otp { action: Some(OtpCmd::Import { uri, attach, name, migration }), .. } => {
+            require_store(&path)?;
+            let uri = match uri.filter(|u| u != "-") {
+                Some(u) => u,
+                None => read_line("")?,
+            };
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            if migration {
+                let (accounts, skipped) = revaultpass::totp::parse_migration(&uri)?;
+                for reason in &skipped {
+                    eprintln!("warning: {}, skipped", reason);
+                }
+                let entries = accounts
+                    .into_iter()
+                    .map(|a| Entry { totp: Some(a.uri.into()), ..Entry::new(a.issuer.unwrap_or_else(|| a.account.clone()), a.account, Secret::default()) })
+                    .collect();
+                let (entries, refused) = normalize_names(entries);
+                let mut summary = merge_entries(vault.entries_mut(), entries, OnConflict::Skip);
+                summary.skipped += skipped.len() + refused;
+                vault.save()?;
+                status!("{}", summary);
+                return Ok(());
+            }
+            revaultpass::totp::Totp::parse(&uri)?;
+            let label = revaultpass::totp::parse_label(&uri)?;
+            if let Some(target) = attach {
+                let entry = vault.get_mut(&target).ok_or_else(|| RevaultError::NotFound(target.clone()))?;
+                entry.totp = Some(uri.into());
+                entry.touch();
+                vault.save()?;
+                status!("Added the TOTP seed to '{}'.", target);
+            } else {
+                let name = match name {
+                    Some(name) => name,
+                    None => parse_entry_name(&label.issuer.unwrap_or_else(|| label.account.clone()))?,
+                };
+                vault.add(Entry { totp: Some(uri.into()), ..Entry::new(name.clone(), label.account, Secret::default()) })?;
+                vault.save()?;
+                status!("Created '{}' without a password; 'revaultpass edit' can set one.", name);
+            }
+        }
+        Cmd::Otp { entry, copy, timeout, .. } => {
+            let timeout = clipboard_timeout(timeout);
+            let name = entry.unwrap_or_default();
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Shared)?;
+            let vault = open_vault(&path, key)?;
+            let entry = vault.get(&name).ok_or_else(|| RevaultError::NotFound(name.clone()))?;
+            let seed = entry.totp.as_ref().ok_or_else(|| format!("'{}' has no TOTP secret; add one with 'revaultpass edit {} --totp <secret>'", name, name))?;
+            let totp = revaultpass::totp::Totp::parse(seed.expose())?;
+            let now = unix_now();
+            let (code, left) = (totp.code_at(now), totp.remaining(now));
+            if copy {
+                match copy_with_clear(&code, timeout) {
+                    Ok(()) => status!("Copied to clipboard, valid for {}s", left),
+                    Err(err) if json => return Err(err),
+                    Err(err) => {
+                        eprintln!("warning: {}; printing instead", err);
+                        println!("{}", code);
+                        status!("valid for {}s", left);
+                    }
+                }
+            } else if json {
+                println!("{}", serde_json::json!({ "code": code, "remaining": left }));
+            } else {
+                // Just the code on stdout, for `revaultpass otp site | xclip`.
+                println!("{}", code);
+                status!("valid for {}s", left);
+            }
+        }
+        #[cfg(feature = "qr")]
+        Cmd::Qr { entry: name, otp, wifi, output } => {
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Shared)?;
+            let vault = open_vault(&path, key)?;
+            let entry = vault.get(&name).ok_or_else(|| RevaultError::NotFound(name.clone()))?;
+            let payload = if otp {
+                let seed = entry.totp.as_ref().ok_or_else(|| format!("'{}' has no TOTP secret; add one with 'revaultpass edit {} --totp <secret>'", name, name))?;
+                match seed.expose().trim() {
+                    uri if uri.starts_with("otpauth://") => Zeroizing::new(uri.to_string()),
+                    secret => revaultpass::totp::Totp::parse(secret)?.uri(&entry.name),
+                }
+            } else if wifi {
+                wifi_payload(entry)
+            } else if entry.is_note() {
+                return Err(format!("'{}' is a note; it has no password to show", name).into());
+            } else {
+                Zeroizing::new(entry.password.expose().to_string())
+            };
+            let code = revaultpass::qr::QrCode::encode(payload.as_bytes())?;
+            match output {
+                Some(file) => {
+                    perms::write_private(&file, &code.png(QR_PNG_SCALE))?;
+                    status!("Wrote the QR code to {}; delete it once it's scanned.", file.display());
+                }
+                None => print_qr(&code),
+            }
+        }
+        Cmd::Type { entry: name, delay, sequence, password_only } => {
+            let sequence = if password_only { "{password}".to_string() } else { unescape_sequence(&sequence) };
+            let pieces = parse_template(&sequence, "--sequence")?;
+            let typist = typist()?;
+            let key = unlock_key(&path)?;
+            let text = {
+                let _lock = lock_store(&path, Lock::Shared)?;
+                let vault = open_vault(&path, key)?;
+                let entry = vault.get(&name).ok_or_else(|| RevaultError::NotFound(name.clone()))?;
+                if entry.is_note() {
+                    return Err(format!("'{}' is a note; there is no login to type", name).into());
+                }
+                render_template(&pieces, entry)
+            };
+            for left in (1..=delay).rev() {
+                if !QUIET.load(Ordering::Relaxed) {
+                    eprint!("\rTyping into the focused window in {}s... ", left);
+                    io::stderr().flush()?;
+                }
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            }
+            if delay > 0 {
+                status!();
+            }
+            type_text(typist, &text)?;
+        }
+        Cmd::EditAll { force } => {
+            if !has_terminal() {
+                return Err("edit-all opens an editor, and there is no terminal".into());
+            }
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            let dir = edit_all::PrivateDir::new()?;
+            let file = dir.path().join("store.toml");
+            let mut doc = edit_all::to_document(vault.list());
+            let edited = loop {
+                perms::write_private(&file, doc.as_bytes())?;
+                run_editor(&file)?;
+                doc = Zeroizing::new(fs::read_to_string(&file)?);
+                match edit_all::parse(&doc) {
+                    Ok(edited) => break edited,
+                    Err(err) => {
+                        eprintln!("line {}: {}", err.line, err.message);
+                        let answer = read_line("Edit again to fix it? [Y/n] ")?;
+                        if answer.eq_ignore_ascii_case("n") || answer.eq_ignore_ascii_case("no") {
+                            return Err("nothing saved".into());
+                        }
+                        doc = edit_all::mark_error(&doc, &err);
+                    }
+                }
+            };
+            drop(dir);
+            let changes = edit_all::diff(vault.list(), &edited);
+            if changes.is_empty() {
+                status!("No changes.");
+                return Ok(());
+            }
+            eprint!("{}", changes);
+            if !force && !confirm("Save these changes? [y/N] ")? {
+                status!("Aborted.");
+                return Ok(());
+            }
+            edit_all::apply(&mut vault, edited)?;
+            vault.save()?;
+            status!("Saved.");
+        }
+        Cmd::Rename { entry: old, new } => {
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            vault.rename(&old, &new)?;
+            vault.save()?;
+            status!("Renamed.");
+        }
+        Cmd::Duplicate { entry: source, new, user } => {
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            let original = vault.get(&source).ok_or_else(|| RevaultError::NotFound(source.clone()))?;
+            // A new entry: fresh timestamps and no password history of its own yet.
+            let now = unix_now();
+            let copy = Entry {
+                name: new,
+                user: user.unwrap_or_else(|| original.user.clone()),
+                history: Vec::new(),
+                created_at: now,
+                modified_at: now,
+                ..original.clone()
+            };
+            vault.add(copy)?;
+            vault.save()?;
+            status!("Saved.");
+        }
+        Cmd::Generate { length, no_symbols, no_digits, no_uppercase, exclude_ambiguous } => {
+            let opts = GenOptions::from_flags(length, no_uppercase, no_digits, no_symbols, exclude_ambiguous);
+            println!("{}", generate_password(&opts)?);
+        }
+        Cmd::Export { format, output, stdout: to_stdout, force, encrypted, age_recipient, filter } => {
+            if !age_recipient.is_empty() {
+                let recipients = age_recipients(&age_recipient)?;
+                let file = match output {
+                    Some(file) if file.extension().is_some_and(|e| e == "rvpa") => file,
+                    Some(_) => return Err("name the file <name>.rvpa, so it isn't mistaken for a store".into()),
+                    None => return Err(usage("usage: revaultpass export --age-recipient <key> [--age-recipient <key>...] [--filter <prefix>] --output <file>.rvpa")),
+                };
+                let key = unlock_key(&path)?;
+                let _lock = lock_store(&path, Lock::Shared)?;
+                let vault = open_vault(&path, key)?;
+                let entries: Vec<&Entry> = vault.list().iter().filter(|e| filter.as_ref().is_none_or(|p| e.name.starts_with(p.as_str()))).collect();
+                if entries.is_empty() {
+                    return Err(format!("no entries start with '{}'; nothing written", filter.unwrap_or_default()).into());
+                }
+                let mut data = MAGIC_AGE.to_vec();
+                data.extend(age::encrypt(&recipients, &Zeroizing::new(serde_json::to_vec(&entries)?))?);
+                perms::write_private(&file, &data)?;
+                status!("Exported {} entries to {} for {} recipient(s).", entries.len(), file.display(), recipients.len());
+                return Ok(());
+            }
+            if let Some(file) = encrypted {
+                let key = unlock_key(&path)?;
+                let _lock = lock_store(&path, Lock::Shared)?;
+                let vault = open_vault(&path, key)?;
+                let entries = vault.list();
+                let backup_pass = read_new_passphrase("Backup passphrase: ", "Repeat backup passphrase: ")?.ok_or("passphrases do not match; nothing written")?;
+                if backup_pass.is_empty() {
+                    return Err("backup passphrase must not be empty".into());
+                }
+                let data = encrypt_as(MAGIC_BACKUP, &Zeroizing::new(serde_json::to_vec(entries)?), &backup_pass)?;
+                perms::write_private(&file, &data)?;
+                status!("Exported {} entries to {} (encrypted with the backup passphrase).", entries.len(), file.display());
+                return Ok(());
+            }
+            if output.is_none() && !to_stdout {
+                return Err(usage("usage: revaultpass export --format json|csv|keepass-xml|toml (--output <file> | --stdout) [--force]"));
+            }
+            if !force && config::get().confirm_export != Some(false) {
+                let target = output.as_ref().map(|p| p.display().to_string()).unwrap_or_else(|| "stdout".into());
+                if !confirm(&format!("This writes all passwords UNENCRYPTED to {}. Continue? [y/N] ", target))? {
+                    status!("Aborted.");
+                    return Ok(());
+                }
+            }
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Shared)?;
+            let vault = open_vault(&path, key)?;
+            let entries = vault.list();
+            let data = match format.as_str() {
+                "csv" => entries_to_csv(entries),
+                "keepass-xml" => entries_to_keepass_xml(entries),
+                "toml" => entries_to_toml(entries),
+                _ => serde_json::to_string_pretty(entries)? + "\n",
+            };
+            match output {
+                Some(file) => {
+                    perms::write_private(&file, data.as_bytes())?;
+                    status!("Exported {} entries to {}.", entries.len(), file.display());
+                }
+                None => print!("{}", data),
+            }
+        }
+        Cmd::Import { file, format, map, on_conflict, dry_run, dir, plain, encrypted, age_identity } => {
+            if !dry_run {
+                require_store(&path)?;
+            }
+            let batch = if let Some(file) = encrypted {
+                let data = fs::read(&file).map_err(|e| format!("{}: {}", file, e))?;
+                if !data.starts_with(MAGIC_BACKUP) {
+                    return Err(format!("{}: not a revaultpass encrypted backup", file).into());
+                }
+                let backup_pass = read_passphrase("Backup passphrase: ")?;
+                let plain = decrypt_as(MAGIC_BACKUP, &data, &backup_pass, None)?;
+                ImportBatch { entries: serde_json::from_slice(&plain)?, ..ImportBatch::default() }
+            } else if let Some(identity) = age_identity {
+                let Some(file) = file else {
+                    return Err(usage("usage: revaultpass import --age-identity <keyfile> <file>.rvpa"));
+                };
+                let text = Zeroizing::new(fs::read_to_string(&identity).map_err(|e| format!("{}: {}", identity.display(), e))?);
+                let identities = age::Identity::parse_file(&text).map_err(|e| format!("{}: {}", identity.display(), e))?;
+                let data = fs::read(&file).map_err(|e| format!("{}: {}", file, e))?;
+                let Some(sealed) = data.strip_prefix(MAGIC_AGE.as_slice()) else {
+                    return Err(format!("{}: not a revaultpass age export", file).into());
+                };
+                let plain = age::decrypt(&identities, sealed).map_err(|e| format!("{}: {}", file, e))?;
+                ImportBatch { entries: serde_json::from_slice(&plain)?, ..ImportBatch::default() }
+            } else if format == "pass" {
+                let dir = dir
+                    .or_else(|| std::env::var_os("PASSWORD_STORE_DIR").map(PathBuf::from))
+                    .or_else(|| directories::BaseDirs::new().map(|b| b.home_dir().join(".password-store")))
+                    .ok_or("could not determine password-store directory; use --dir")?;
+                import_pass(&dir, plain)?
+            } else {
+                let Some(file) = file else {
+                    return Err(usage("usage: revaultpass import --format csv|bitwarden|chrome|keepass|lastpass|toml <file> (see import --help)"));
+                };
+                let text = fs::read_to_string(&file).map_err(|e| format!("{}: {}", file, e))?;
+                match format.as_str() {
+                    "csv" => import_csv(&text, map.as_deref())?,
+                    "bitwarden" => import_bitwarden(&text)?,
+                    "chrome" => import_chrome(&text)?,
+                    "keepass" => import_keepass(&text)?,
+                    "lastpass" => import_lastpass(&text)?,
+                    "toml" => import_toml(&text)?,
+                    _ => unreachable!("format is checked by the argument parser"),
+                }
+            };
+            info!("read {} entries, {} skipped by the importer", batch.entries.len(), batch.skipped);
+            for w in &batch.warnings {
+                eprintln!("warning: {}", w);
+            }
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            let (entries, refused) = normalize_names(batch.entries);
+            if dry_run {
+                for e in &entries {
+                    println!("  {}  ->  {}:****", e.name, e.user);
+                }
+            }
+            let mut summary = merge_entries(vault.entries_mut(), entries, on_conflict);
+            summary.skipped += batch.skipped + refused;
+            if dry_run {
+                status!("dry run, nothing written: {}", summary);
+                return Ok(());
+            }
+            vault.save()?;
+            status!("{}", summary);
+        }
+        Cmd::Rekey { kdf, new_keyfile, no_keyfile, recovery_key, cipher } => {
+            let current = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, current)?;
+            // Keep the cost and keyfile of the slot we opened unless asked to change them.
+            let (base, current_keyfile, was_encrypted) = match vault.key() {
+                StoreKey::Passphrase(_, kdf, keyfile) => (*kdf, keyfile.clone(), true),
+                StoreKey::Plain => (KdfParams::default(), None, false),
+            };
+            let had_keyfile = current_keyfile.is_some();
+            let keyfile = match (new_keyfile, no_keyfile) {
+                (_, true) => None,
+                (Some(p), false) => Some(read_keyfile(&p)?),
+                (None, false) => match current_keyfile {
+                    Some(keyfile) => Some(keyfile),
+                    None => cli.keyfile.as_deref().map(read_keyfile).transpose()?,
+                },
+            };
+            let kdf = kdf_from_args(&kdf, base)?;
+            let new = read_new_passphrase("New master key (or leave empty for no encryption): ", "Repeat new master key: ")?
+                .ok_or("keys do not match; store left unchanged")?;
+            if new.is_empty() && was_encrypted {
+                status!("WARNING: this will store all your passwords UNENCRYPTED on disk.");
+                status!("Anyone with access to the file will be able to read them.");
+                let others = vault.key_slots().iter().filter(|s| matches!(s.kind, KeySlotKind::Passphrase { .. })).count().saturating_sub(1);
+                if others > 0 {
+                    status!("This also removes {} other passphrase slot{}.", others, if others == 1 { "" } else { "s" });
+                }
+                if vault.is_signed() {
+                    status!("The signing key moves from the store to {}.", signing_key_path(&path).display());
+                }
+                if read_line("Type 'yes' to continue: ")? != "yes" {
+                    status!("Aborted.");
+                    return Ok(());
+                }
+            }
+            if new.is_empty() && keyfile.is_some() && !had_keyfile {
+                return Err("a keyfile only works together with a master key; store left unchanged".into());
+            }
+            if new.is_empty() && recovery_key {
+                return Err("a recovery key only works together with a master key; store left unchanged".into());
+            }
+            if new.is_empty() && cipher.is_some() {
+                return Err("a cipher only works together with a master key; store left unchanged".into());
+            }
+            let had_recovery = vault.has_recovery_key();
+            let was_signed = vault.is_signed();
+            let old_cipher = vault.cipher();
+            vault.set_key(StoreKey::from_passphrase(new.clone(), kdf).with_keyfile(keyfile.clone()))?;
+            if let Some(cipher) = cipher {
+                vault.set_cipher(cipher)?;
+            }
+            let recovery = recovery_key.then(|| vault.add_recovery_key()).transpose()?;
+            vault.save()?;
+            if was_signed && !vault.is_signed() {
+                remove_signature_files(&path)?;
+            }
+            keychain_follow(&path, &new);
+            if new.is_empty() {
+                status!("Store is now unencrypted.");
+            } else {
+                status!("Master key changed. Your data is encrypted with the new key.");
+                match (had_keyfile, keyfile.is_some()) {
+                    (false, true) => status!("The keyfile is now needed as well to open the store."),
+                    (true, false) => status!("The store no longer needs a keyfile."),
+                    _ => {}
+                }
+                if let Some(cipher) = vault.cipher().filter(|c| old_cipher.is_some_and(|old| old != *c)) {
+                    status!("The contents are now encrypted with {}.", cipher.label());
+                }
+                match &recovery {
+                    Some(recovery) => show_recovery_key(recovery),
+                    None if had_recovery => status!("The recovery key still works."),
+                    None => {}
+                }
+            }
+        }
+        Cmd::Recover { kdf, slot } => {
+            recover_store(&path, &kdf, slot, cli.keyfile.as_deref(), "wrong recovery key", || {
+                let text = read_passphrase("Recovery key: ")?;
+                if Share::parse(&text).is_ok() {
+                    return Err("that is a share, not a recovery key; use 'revaultpass shares recover'".into());
+                }
+                Ok(RecoveryKey::parse(&text).ok_or("not a recovery key (expected 13 groups of 4 letters and digits)")?)
+            })?;
+        }
+        Cmd::Shares { action: SharesCmd::Create { threshold, count, output_dir } } => {
+            if threshold > count {
+                return Err(usage("--threshold can't be more than --count"));
+            }
+            if store_kind(&path)? != StoreKind::Encrypted {
+                require_store(&path)?;
+                return Err("shares need an encrypted store; set a master key with 'revaultpass rekey' first".into());
+            }
+            if let Some(dir) = &output_dir {
+                if let Some(file) = (1..=count).map(|i| dir.join(format!("share-{}.txt", i))).find(|f| f.exists()) {
+                    return Err(format!("{} already exists; nothing written", file.display()).into());
+                }
+            }
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            let had_recovery = vault.has_recovery_key();
+            let shares = vault.add_recovery_key()?.split(threshold, count)?;
+            vault.save()?;
+            if had_recovery {
+                status!("The earlier recovery key and any shares of it no longer work.");
+            }
+            if let Some(dir) = output_dir {
+                fs::create_dir_all(&dir)?;
+                for share in &shares {
+                    let file = dir.join(format!("share-{}.txt", share.index));
+                    let text = Zeroizing::new(format!(
+                        "revaultpass recovery share {} of {} (any {} of them open {})\n\n{}\n",
+                        share.index,
+                        count,
+                        threshold,
+                        path.display(),
+                        share.encode().as_str()
+                    ));
+                    perms::write_private(&file, text.as_bytes())?;
+                }
+                status!("Wrote {} shares to {}; give each to a different person and delete the files here.", count, dir.display());
+            } else if JSON_OUTPUT.load(Ordering::Relaxed) {
+                let list: Vec<_> = shares.iter().map(|s| serde_json::json!({ "index": s.index, "share": s.encode().as_str() })).collect();
+                println!("{}", serde_json::json!({ "threshold": threshold, "shares": list }));
+            } else {
+                eprintln!("{} shares (shown only this once; give each to a different person):", count);
+                for share in &shares {
+                    println!("{}  {}", share.index, share.encode().as_str());
+                }
+                status!("Any {} of them open the store with 'revaultpass shares recover'; fewer reveal nothing.", threshold);
+            }
+        }
+        Cmd::Shares { action: SharesCmd::Recover { kdf, slot, files } } => {
+            recover_store(&path, &kdf, slot, cli.keyfile.as_deref(), "these shares don't open this store", || {
+                let mut shares = Vec::new();
+                for file in &files {
+                    let text = Zeroizing::new(fs::read_to_string(file).map_err(|e| format!("{}: {}", file.display(), e))?);
+                    let line = text.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("");
+                    let share = Share::parse(line).map_err(|e| format!("{}: {}", file.display(), e))?;
+                    add_share(&mut shares, share)?;
+                }
+                while shares.first().is_none_or(|s: &Share| shares.len() < s.threshold as usize) {
+                    let prompt = match shares.first() {
+                        Some(first) => format!("Share {} of {}: ", shares.len() + 1, first.threshold),
+                        None => "Share 1: ".to_string(),
+                    };
+                    let text = read_passphrase(&prompt)?;
+                    if text.trim().is_empty() {
+                        return Err("no share given; store left unchanged".into());
+                    }
+                    match Share::parse(&text).map_err(Into::into).and_then(|share| add_share(&mut shares, share)) {
+                        Ok(()) => {}
+                        Err(e) => eprintln!("{}; try again", e),
+                    }
+                }
+                Ok(RecoveryKey::combine(&shares)?)
+            })?;
+        }
+        Cmd::KdfTune => {
+            let target = std::time::Duration::from_millis(KDF_TUNE_TARGET_MS);
+            status!("Benchmarking Argon2id (target ~{} ms)...", KDF_TUNE_TARGET_MS);
+            let (kdf, took) = tune_kdf(target)?;
+            println!("m_cost = {} KiB ({} MiB), t_cost = {}: {} ms", kdf.m_cost, kdf.m_cost / 1024, kdf.t_cost, took.as_millis());
+            status!("Apply with: revaultpass rekey --kdf-mem {} --kdf-iters {}", kdf.m_cost, kdf.t_cost);
+        }
+        Cmd::SelfTest => {
+            let mut checks = self_test();
+            // The store's own costs when there is an encrypted store, since
+            // that's the wait every unlock has; the defaults otherwise.
+            let encrypted = matches!(store_kind(&path), Ok(StoreKind::Encrypted));
+            let kdf = if encrypted { fs::read(&path).map(|d| KdfParams::from_header(&d)).unwrap_or_default() } else { KdfParams::default() };
+            let took = time_kdf(&kdf);
+            let whose = if encrypted { "the store's costs" } else { "the default costs" };
+            checks.push(SelfCheck {
+                name: "key derivation",
+                ok: took.is_ok(),
+                detail: match &took {
+                    Ok(took) => format!("{}: {} ms ({})", kdf_label(&kdf), took.as_millis(), whose),
+                    Err(e) => format!("{}: {}", kdf_label(&kdf), e),
+                },
+            });
+            let failed = checks.iter().filter(|c| !c.ok).count();
+            if json {
+                let list: Vec<_> = checks.iter().map(|c| serde_json::json!({ "name": c.name, "ok": c.ok, "detail": c.detail })).collect();
+                let ms = took.as_ref().ok().map(|t| t.as_millis() as u64);
+                println!(
+                    "{}",
+                    serde_json::json!({ "ok": failed == 0, "checks": list, "kdf_mem": kdf.m_cost, "kdf_iters": kdf.t_cost, "kdf_ms": ms })
+                );
+            } else {
+                for c in &checks {
+                    println!("{}  {:<18} {}", if c.ok { "PASS" } else { "FAIL" }, c.name, c.detail);
+                }
+                match failed {
+                    0 => status!("All {} checks passed.", checks.len()),
+                    n => eprintln!("{} of {} checks failed: don't trust this build with a store.", n, checks.len()),
+                }
+            }
+            if failed > 0 {
+                return Err(Exit(SELF_TEST_EXIT).into());
+            }
+        }
+        Cmd::Info => {
+            require_store(&path)?;
+            let data = fs::read(&path)?;
+            check_layout(&data).map_err(|e| e.at(&path))?;
+            let format = format_label(&data).unwrap_or_else(|| "unknown".to_string());
+            let meta = store_meta(&data);
+            let encrypted = store_kind(&path)? == StoreKind::Encrypted;
+            let cipher = store_cipher(&data);
+            let kdfs = if encrypted { header_kdfs(&data) } else { Vec::new() };
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "path": path.display().to_string(), "format": format, "id": meta.map(|m| m.id_string()),
+                        "created_at": meta.map(|m| m.created_at), "modified_at": meta.map(|m| m.modified_at),
+                        "cipher": cipher.map(Cipher::name), "kdf": kdfs,
+                    })
+                );
+            } else {
+                println!("Store:    {}", path.display());
+                println!("Format:   {}", format);
+                match meta {
+                    Some(meta) => {
+                        println!("Id:       {}", meta.id_string());
+                        println!("Created:  {}", format_local_time(meta.created_at));
+                        println!("Modified: {}", format_local_time(meta.modified_at));
+                    }
+                    None if !encrypted => println!("Metadata: none (older unencrypted store; added on the next save)"),
+                    None => println!("Metadata: none (older format; added on the next full write)"),
+                }
+                if let Some(cipher) = cipher {
+                    println!("Cipher:   {}", cipher.label());
+                }
+                for kdf in &kdfs {
+                    println!("Key:      {}", kdf);
+                }
+            }
+        }
+        Cmd::Stats { no_unlock } => {
+            require_store(&path)?;
+            let data = fs::read(&path)?;
+            let kind = store_kind(&path)?;
+            let format = format_label(&data).unwrap_or_else(|| "unknown".to_string());
+            let mut stats = serde_json::json!({
+                "path": path.display().to_string(),
+                "size": data.len(),
+                "encrypted": kind == StoreKind::Encrypted,
+                "format": format,
+                "recovery_key": has_recovery_slot(&data),
+                "key_slots": key_slots(&data).len(),
+            });
+            if !json {
+                println!("Store:      {}", path.display());
+                println!("Size:       {} bytes", data.len());
+                println!("Format:     {}{}", format, if kind == StoreKind::Encrypted { " (encrypted)" } else { " (unencrypted)" });
+            }
+            // The file details above need no key; declining to unlock (an empty
+            // master key or --no-unlock) ends the report there.
+            let key = match kind {
+                _ if no_unlock => None,
+                StoreKind::Encrypted => {
+                    let pass = read_master_key("Master key (Enter to skip): ")?;
+                    Some(pass).filter(|p| !p.is_empty()).map(|p| passphrase_key(&data, p)).transpose()?
+                }
+                _ => Some(unlock_key(&path)?),
+            };
+            let Some(key) = key else {
+                stats["unlocked"] = false.into();
+                if json {
+                    println!("{}", stats);
+                }
+                return Ok(());
+            };
+            let vault = {
+                let _lock = lock_store(&path, Lock::Shared)?;
+                open_vault(&path, key)?
+            };
+            let entries = vault.list();
+            let logins: Vec<&Entry> = entries.iter().filter(|e| !e.is_note()).collect();
+            let users: std::collections::BTreeSet<&str> = logins.iter().map(|e| e.user.as_str()).filter(|u| !u.is_empty()).collect();
+            let lengths: Vec<usize> = logins.iter().filter(|e| !e.password.is_empty()).map(|e| e.password.expose().chars().count()).collect();
+            let count = |f: fn(&Entry) -> bool| entries.iter().filter(|e| f(e)).count();
+            let dated = || entries.iter().filter(|e| e.created_at != 0);
+            let (oldest, newest) = (dated().min_by_key(|e| e.created_at), dated().max_by_key(|e| e.created_at));
+            let average = (!lengths.is_empty()).then(|| lengths.iter().sum::<usize>() as f64 / lengths.len() as f64);
+            stats["unlocked"] = true.into();
+            stats["entries"] = entries.len().into();
+            stats["secure_notes"] = (entries.len() - logins.len()).into();
+            stats["trash"] = vault.trash().len().into();
+            stats["users"] = users.len().into();
+            stats["with_notes"] = count(|e| e.notes.is_some()).into();
+            stats["with_totp"] = count(|e| e.totp.is_some()).into();
+            stats["with_tags"] = count(|e| !e.tags.is_empty()).into();
+            stats["password_length"] = serde_json::json!({ "average": average, "min": lengths.iter().min() });
+            let dated_json = |e: Option<&Entry>| e.map(|e| serde_json::json!({ "name": e.name, "created_at": e.created_at }));
+            stats["oldest"] = dated_json(oldest).into();
+            stats["newest"] = dated_json(newest).into();
+            if json {
+                println!("{}", stats);
+                return Ok(());
+            }
+            println!("Entries:    {} ({} secure notes, {} in the trash)", entries.len(), entries.len() - logins.len(), vault.trash().len());
+            println!("Users:      {} distinct", users.len());
+            println!("With notes: {}", stats["with_notes"]);
+            println!("With TOTP:  {}", stats["with_totp"]);
+            println!("With tags:  {}", stats["with_tags"]);
+            match (average, lengths.iter().min()) {
+                (Some(average), Some(min)) => println!("Passwords:  {:.1} characters on average, shortest {}", average, min),
+                _ => println!("Passwords:  (none)"),
+            }
+            let when = |e: Option<&Entry>| e.map_or("-".to_string(), |e| format!("{} ({})", e.name, format_local_time(e.created_at)));
+            println!("Oldest:     {}", when(oldest));
+            println!("Newest:     {}", when(newest));
+        }
+        Cmd::Backups { action } => match action {
+            BackupsCmd::List => {
+                let backups = list_backups(&path)?;
+                if backups.is_empty() {
+                    println!("(none)");
+                }
+                let mut plaintext = false;
+                for (ts, file) in &backups {
+                    let size = fs::metadata(file)?.len();
+                    let plain = fs::read(file).map(|d| d.starts_with(MAGIC_PLAIN)).unwrap_or(false);
+                    plaintext |= plain;
+                    println!("  {}  {} UTC  {:>8} bytes{}", ts, format_unix_time(*ts), size, if plain { "  (unencrypted)" } else { "" });
+                }
+                if plaintext {
+                    eprintln!("warning: some backups are unencrypted; anyone who can read {} can read them", backups_dir(&path).display());
+                }
+            }
+            BackupsCmd::Restore { timestamp: ts } => {
+                let Some((_, file)) = list_backups(&path)?.into_iter().find(|(t, _)| *t == ts) else {
+                    return Err(format!("no backup with timestamp {}", ts).into());
+                };
+                if !confirm(&format!("Replace {} with the backup from {} UTC? [y/N] ", path.display(), format_unix_time(ts)))? {
+                    status!("Aborted.");
+                    return Ok(());
+                }
+                let data = fs::read(&file)?;
+                let _lock = lock_store(&path, Lock::Exclusive)?;
+                backup_store(&path)?;
+                write_atomic(&path, &data)?;
+                // The current journal belongs to the store just replaced.
+                match fs::read(journal_path(&file)) {
+                    Ok(journal) => write_atomic(&journal_path(&path), &journal)?,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => match fs::remove_file(journal_path(&path)) {
+                        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                        _ => {}
+                    },
+                    Err(e) => return Err(e.into()),
+                }
+                if signature_path(&file).exists() {
+                    write_atomic(&signature_path(&path), &fs::read(signature_path(&file))?)?;
+                } else if public_key_path(&path).exists() {
+                    eprintln!("warning: the backup has no signature; run 'revaultpass --ignore-signature sign setup' to sign it if you trust it");
+                }
+                status!("Restored.");
+            }
+        },
+        Cmd::Verify => {
+            // No lock: it would create <store>.lock, and the file may sit on read-only media.
+            require_store(&path)?;
+            let data = fs::read(&path)?;
+            check_layout(&data).map_err(|e| e.at(&path))?;
+            let format = format_label(&data).unwrap_or_default();
+            let vault = match Vault::open_with_key(&path, unlock_key(&path)?) {
+                Err(e) if matches!(e.root(), RevaultError::Serde(_)) => {
+                    if json {
+                        eprintln!("{}", json_error(&e.to_string(), INVALID_CONTENTS_EXIT));
+                    } else {
+                        eprintln!("Error: {}", e);
+                    }
+                    return Err(Exit(INVALID_CONTENTS_EXIT).into());
+                }
+                vault => vault?,
+            };
+            let kdf = match vault.key() {
+                StoreKey::Passphrase(_, kdf, _) => Some(*kdf),
+                StoreKey::Plain => None,
+            };
+            let signed = !cli.ignore_signature && verify_signature(&path, &path, &data)?;
+            let count = vault.list().len();
+            let journal = vault.journal_records();
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "ok": true, "entries": count, "trash": vault.trash().len(), "format": format, "signed": signed,
+                        "kdf_mem": kdf.map(|k| k.m_cost), "kdf_iters": kdf.map(|k| k.t_cost), "journal_records": journal,
+                    })
+                );
+            } else {
+                let mut line = format!("OK: {} entr{}, format {}", count, if count == 1 { "y" } else { "ies" }, format);
+                match kdf {
+                    Some(kdf) => line.push_str(&format!(", {}", kdf_label(&kdf))),
+                    None => line.push_str(", unencrypted"),
+                }
+                if signed {
+                    line.push_str(", signature OK");
+                }
+                if journal > 0 {
+                    line.push_str(&format!(", {} journal record{}", journal, if journal == 1 { "" } else { "s" }));
+                }
+                println!("{}", line);
+            }
+        }
+        Cmd::Compact => {
+            require_store(&path)?;
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            match vault.journal_records() {
+                0 => status!("Nothing to compact: the store has no journal."),
+                _ => {
+                    let n = vault.compact()?;
+                    status!("Folded {} journal record{} into the store.", n, if n == 1 { "" } else { "s" });
+                }
+            }
+        }
+        Cmd::Fsck { repair } => {
+            require_store(&path)?;
+            let data = fs::read(&path)?;
+            let kind = store_kind(&path)?;
+            let format = format_label(&data).unwrap_or_else(|| "unknown".to_string());
+            let layout = check_layout(&data);
+            // The header report needs no key, so it is there even when unlocking fails.
+            let kdfs = match kind {
+                StoreKind::Encrypted if layout.is_ok() => header_kdfs(&data),
+                _ => Vec::new(),
+            };
+            if json {
+                let report = serde_json::json!({ "path": path.display().to_string(), "format": format, "header_ok": layout.is_ok(), "kdf": kdfs });
+                if layout.is_err() {
+                    println!("{}", report);
+                }
+            } else {
+                println!("Store:    {}", path.display());
+                println!("Format:   {}", format);
+                println!("Header:   {}", if layout.is_ok() { "intact" } else { "damaged" });
+                for kdf in &kdfs {
+                    println!("Key:      {}", kdf);
+                }
+            }
+            layout.map_err(|e| e.at(&path))?;
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, if repair { Lock::Exclusive } else { Lock::Shared })?;
+            let (mut vault, damage) = Vault::open_lenient(&path, key)?;
+            let Some((error, salvage)) = damage else {
+                // Names from before new ones were checked still load; they are only reported.
+                let odd: Vec<(&str, &str)> = vault.list().iter().filter_map(|e| revaultpass::names::problem(&e.name).map(|p| (e.name.as_str(), p))).collect();
+                if json {
+                    let names: Vec<_> = odd.iter().map(|(name, problem)| serde_json::json!({ "name": name, "problem": problem })).collect();
+                    println!("{}", serde_json::json!({ "path": path.display().to_string(), "format": format, "header_ok": true, "kdf": kdfs, "intact": true, "entries": vault.list().len(), "name_problems": names }));
+                } else {
+                    println!("Contents: OK, {} entries", vault.list().len());
+                    if !odd.is_empty() {
+                        println!("Names:    {} that new entries couldn't have; 'revaultpass rename' fixes them", odd.len());
+                        for (name, problem) in &odd {
+                            println!("  {:?}: {}", name, problem);
+                        }
+                    }
+                }
+                if repair {
+                    status!("Nothing to repair.");
+                }
+                return Ok(());
+            };
+            // Offsets into the file for an unencrypted store; into the decrypted JSON otherwise.
+            let base = if kind == StoreKind::Plain { MAGIC_PLAIN.len() } else { 0 };
+            let skipped: Vec<(usize, usize)> = salvage.skipped.iter().map(|r| (r.start + base, r.end + base)).collect();
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "path": path.display().to_string(), "format": format, "header_ok": true, "kdf": kdfs, "intact": false,
+                        "error": error.to_string(), "entries": vault.list().len(), "trash": vault.trash().len(),
+                        "salvaged": vault.list().iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), "skipped": skipped,
+                    })
+                );
+            } else {
+                println!("Contents: damaged: {}", error);
+                println!("Salvaged: {} entries, {} in the trash", vault.list().len(), vault.trash().len());
+                for entry in vault.list() {
+                    println!("  {}", entry.name);
+                }
+                let of = if base > 0 { "" } else { " of the decrypted contents" };
+                for (start, end) in &skipped {
+                    println!("Skipped:  bytes {}-{}{} ({} bytes)", start, end, of, end - start);
+                }
+            }
+            if !repair {
+                status!("Nothing was changed. 'revaultpass fsck --repair' makes the salvaged entries the store and keeps the damaged file next to it.");
+                return Err(Exit(RevaultError::corrupt("").exit_code()).into());
+            }
+            let aside = vault.save_repaired()?;
+            status!("Wrote the salvaged entries to {}; the damaged file is now {}.", path.display(), aside.display());
+            if public_key_path(&path).exists() && !vault.is_signed() {
+                status!("The signing key was lost with the damage; turn signing off with 'revaultpass --ignore-signature sign disable'.");
+            }
+        }
+        Cmd::FixPerms => {
+            let mut targets = vec![path.clone(), path.with_extension("lock"), backups_dir(&path)];
+            targets.extend(list_backups(&path)?.into_iter().map(|(_, p)| p));
+            // Only tighten the directory when it's our own data dir, never e.g. /tmp for --store /tmp/x.dat.
+            if let Some(dir) = path.parent().filter(|d| store_path().is_some_and(|p| p.parent() == Some(*d))) {
+                targets.push(dir.to_path_buf());
+            }
+            for target in targets.iter().filter(|t| t.exists()) {
+                perms::restrict(target)?;
+                println!("  {}", target.display());
+            }
+            status!("Permissions restricted to the owner.");
+        }
+        Cmd::Shell { idle } => {
+            require_store(&path)?;
+            run_shell(&path, idle)?;
+        }
+        Cmd::Tui => {
+            require_store(&path)?;
+            #[cfg(unix)]
+            tui::run(&path)?;
+            #[cfg(not(unix))]
+            return Err("the TUI is only available on Unix terminals".into());
+        }
+        Cmd::Slots { action: SlotsCmd::List } => {
+            require_store(&path)?;
+            let slots = key_slots(&fs::read(&path)?);
+            if json {
+                let list: Vec<serde_json::Value> = slots
+                    .iter()
+                    .map(|s| match s.kind {
+                        KeySlotKind::Passphrase { kdf, keyfile } => serde_json::json!({
+                            "id": s.id, "kind": "passphrase", "label": s.label, "keyfile": keyfile,
+                            "kdf_mem": kdf.m_cost, "kdf_iters": kdf.t_cost,
+                        }),
+                        KeySlotKind::Recovery => serde_json::json!({ "id": s.id, "kind": "recovery", "label": s.label }),
+                    })
+                    .collect();
+                println!("{}", serde_json::Value::from(list));
+            } else if slots.is_empty() {
+                match store_kind(&path)? {
+                    StoreKind::Encrypted => println!("(one master key; the store gets key slots on its next save)"),
+                    _ => println!("(none; the store isn't encrypted)"),
+                }
+            }
+            for s in slots.iter().filter(|_| !json) {
+                let label = if s.label.is_empty() { String::new() } else { format!("  '{}'", s.label) };
+                match s.kind {
+                    KeySlotKind::Passphrase { kdf, keyfile } => println!(
+                        "  {:>3}  passphrase{}  ({} KiB, {} passes{})",
+                        s.id, label, kdf.m_cost, kdf.t_cost, if keyfile { ", keyfile" } else { "" }
+                    ),
+                    KeySlotKind::Recovery => println!("  {:>3}  recovery key{}", s.id, label),
+                }
+            }
+        }
+        Cmd::Slots { action: SlotsCmd::Add { label, new_keyfile, kdf } } => {
+            if store_kind(&path)? != StoreKind::Encrypted {
+                require_store(&path)?;
+                return Err("the store isn't encrypted; set a master key with 'revaultpass rekey' first".into());
+            }
+            let new_keyfile = new_keyfile.as_deref().map(read_keyfile).transpose()?;
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            let base = match vault.key() {
+                StoreKey::Passphrase(_, kdf, _) => *kdf,
+                StoreKey::Plain => KdfParams::default(),
+            };
+            let kdf = kdf_from_args(&kdf, base)?;
+            let new = read_new_passphrase("Passphrase for the new slot: ", "Repeat passphrase: ")?.ok_or("passphrases do not match; store left unchanged")?;
+            if new.is_empty() {
+                return Err("the passphrase can't be empty".into());
+            }
+            let id = vault.add_key_slot(&StoreKey::Passphrase(new, kdf, new_keyfile), label.as_deref().unwrap_or(""))?;
+            vault.save()?;
+            status!("Added key slot {}; that passphrase opens the store too.", id);
+        }
+        Cmd::Slots { action: SlotsCmd::Remove { id, force } } => {
+            require_store(&path)?;
+            let Some(slot) = key_slots(&fs::read(&path)?).into_iter().find(|s| s.id == id) else {
+                return Err(format!("no key slot {}; see 'revaultpass slots list'", id).into());
+            };
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            let own = vault.own_slot() == Some(id);
+            // Refusals (the last passphrase) come before the question; nothing is written until the answer.
+            vault.remove_key_slot(id)?;
+            let what = match (slot.kind, slot.label.as_str()) {
+                (KeySlotKind::Recovery, _) => "the recovery key".to_string(),
+                (_, "") => "a passphrase".to_string(),
+                (_, label) => format!("passphrase '{}'", label),
+            };
+            let mine = if own { ", the one you just unlocked with" } else { "" };
+            if !force && config::get().confirm_slots_remove != Some(false) && !confirm(&format!("Remove key slot {} ({}{})? [y/N] ", id, what, mine))? {
+                status!("Aborted.");
+                return Ok(());
+            }
+            vault.save()?;
+            if own {
+                // A saved copy of this passphrase no longer opens anything.
+                keychain_follow(&path, "");
+            }
+            status!("Key slot {} removed.", id);
+        }
+        Cmd::Sign { action: SignCmd::Setup } => {
+            require_store(&path)?;
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            if vault.is_signed() {
+                vault.save()?;
+                status!("Signed {} again with its existing key.", path.display());
+                return Ok(());
+            }
+            vault.enable_signing()?;
+            vault.save()?;
+            status!("Signed {}; every save is signed from now on.", path.display());
+            if vault.cipher().is_none() {
+                status!("The store isn't encrypted, so the signing key is in {}. Whoever can read that file can sign changes.", signing_key_path(&path).display());
+            }
+            status!("The public key is in {}. Keep a copy elsewhere: whoever can replace the store can replace that file too.", public_key_path(&path).display());
+        }
+        Cmd::Sign { action: SignCmd::Verify { file, public_key } } => {
+            require_store(&path)?;
+            let file = file.unwrap_or_else(|| path.clone());
+            let data = fs::read(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+            if let Some(pinned) = public_key {
+                let text = match fs::read_to_string(&pinned) {
+                    Ok(text) => text,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => pinned.clone(),
+                    Err(e) => return Err(format!("{}: {}", pinned, e).into()),
+                };
+                let public = parse_public_key(&text).ok_or_else(|| usage(&format!("--public-key: '{}' is neither a .pub file nor 64 hex digits", pinned)))?;
+                verify_signature_with(&public, &file, &data).map_err(|e| e.at(&file))?;
+                status!("{}: signature OK (pinned key)", file.display());
+                return Ok(());
+            }
+            if !verify_signature(&path, &file, &data).map_err(|e| e.at(&file))? {
+                return Err(format!("{} isn't signed (no {}); see 'revaultpass sign setup'", path.display(), public_key_path(&path).display()).into());
+            }
+            status!("{}: signature OK", file.display());
+        }
+        Cmd::Sign { action: SignCmd::Disable } => {
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            if !vault.is_signed() && !public_key_path(&path).exists() {
+                status!("{} isn't signed.", path.display());
+                return Ok(());
+            }
+            let key_file = signing_key_path(&path);
+            let had_key_file = key_file.exists();
+            vault.disable_signing()?;
+            vault.save()?;
+            remove_signature_files(&path)?;
+            if had_key_file {
+                status!(
+                    "Signing turned off; removed {}, {} and {}.",
+                    public_key_path(&path).display(),
+                    signature_path(&path).display(),
+                    key_file.display()
+                );
+            } else {
+                status!("Signing turned off; removed {} and {}.", public_key_path(&path).display(), signature_path(&path).display());
+            }
+        }
+        Cmd::Decoy { action: DecoyCmd::Init } => {
+            if store_kind(&path)? != StoreKind::Encrypted {
+                require_store(&path)?;
+                return Err("a decoy needs an encrypted store; set a master key with 'revaultpass rekey' first".into());
+            }
+            let key = unlock_key(&path)?;
+            let _lock = lock_store(&path, Lock::Exclusive)?;
+            let mut vault = open_vault(&path, key)?;
+            let new = read_new_passphrase("Decoy passphrase: ", "Repeat decoy passphrase: ")?.ok_or("passphrases do not match; store left unchanged")?;
+            if new.is_empty() {
+                return Err("the passphrase can't be empty".into());
+            }
+            vault.set_decoy(&new)?;
+            vault.save()?;
+            status!("Decoy set up: that passphrase now opens an empty store of its own. Unlock with it to add decoy entries.");
+        }
+        Cmd::Keychain { action: KeychainCmd::Enable } => {
+            keychain().ok_or(keychain_missing())?;
+            if store_kind(&path)? != StoreKind::Encrypted {
+                require_store(&path)?;
+                return Err("the store isn't encrypted, so there is no master key to save".into());
+            }
+            let key = read_master_key("Master key: ")?;
+            {
+                let _lock = lock_store(&path, Lock::Shared)?;
+                Vault::open_with_key(&path, passphrase_key(&fs::read(&path)?, key.clone())?)?;
+            }
+            keychain_save(&path, &key)?;
+            status!("Master key saved in the keychain; commands on this store won't ask for it.");
+        }
+        Cmd::Keychain { action: KeychainCmd::Disable } => {
+            if keychain_lookup(&path).is_none() {
+                status!("The keychain has no master key for this store.");
+                return Ok(());
+            }
+            keychain_delete(&path)?;
+            status!("Master key removed from the keychain.");
+        }
+        Cmd::Keychain { action: KeychainCmd::Status } => {
+            let available = keychain().is_some();
+            let saved = available && keychain_lookup(&path).is_some();
+            if json {
+                let mut status = serde_json::json!({ "available": available, "saved": saved });
+                if !available {
+                    status["reason"] = keychain_missing().into();
+                }
+                println!("{}", status);
+            } else if cfg!(windows) {
+                println!("The keychain isn't supported on Windows.");
+            } else if !available {
+                println!("No keychain available (needs macOS, or secret-tool from libsecret).");
+            } else if saved {
+                println!("The keychain holds the master key for {}.", path.display());
+            } else {
+                println!("The keychain has no master key for {}.", path.display());
+            }
+        }
+        #[cfg(unix)]
+        Cmd::Agent { action } => match action {
+            AgentCmd::Start { idle, foreground } => {
+                require_store(&path)?;
+                let socket = agent::socket_path();
+                let export = format!(
+                    "REVAULTPASS_AGENT_SOCK={}; export REVAULTPASS_AGENT_SOCK;",
+                    shell_quote(&socket.to_string_lossy())
+                );
+                if foreground {
+                    println!("{}", export);
+                    agent::serve(&path, idle)?;
+                } else {
+                    agent::spawn(&path, idle)?;
+                    println!("{}", export);
+                }
+            }
+            AgentCmd::Unlock => {
+                let key = read_master_key("Master key: ")?;
+                agent::request(serde_json::json!({ "op": "unlock", "passphrase": key.as_str() }))?;
+                status!("Agent unlocked.");
+            }
+            AgentCmd::Lock => {
+                agent::request(serde_json::json!({ "op": "lock" }))?;
+                status!("Agent locked.");
+            }
+            AgentCmd::Stop => {
+                agent::request(serde_json::json!({ "op": "stop" }))?;
+                status!("Agent stopped.");
+            }
+            AgentCmd::Status => {
+                let status = agent::request(serde_json::json!({ "op": "status" }))?;
+                if json {
+                    println!("{}", status);
+                } else {
+                    println!("Agent:  {}", agent::socket_path().display());
+                    println!("Store:  {}", status["store"].as_str().unwrap_or_default());
+                    match status["locks_in"].as_u64() {
+                        Some(secs) => println!("State:  unlocked, locks after {}s idle", secs),
+                        None if status["unlocked"] == true => println!("State:  unlocked"),
+                        None => println!("State:  locked"),
+                    }
+                }
+            }
+        },
+        #[cfg(not(unix))]
+        Cmd::Agent { .. } => return Err("the agent needs Unix domain sockets and isn't available on this platform".into()),
+        Cmd::Config { action: ConfigCmd::Get { key } } => {
+            let value = config::show(&key)?;
+            if json {
+                println!("{}", serde_json::json!({ "key": key, "value": value }));
+            } else {
+                println!("{}", value);
+            }
+        }
+        Cmd::Config { action: ConfigCmd::Set { key, value } } => {
+            let file = config::set(&key, &value)?;
+            status!("Set {} in {}.", key, file.display());
+        }
+        Cmd::Config { action: ConfigCmd::Path } => {
+            let file = config::path().ok_or("could not determine the config directory")?;
+            if json {
+                println!("{}", serde_json::json!({ "path": file }));
+            } else {
+                println!("{}", file.display());
+            }
+        }
+        Cmd::Completions { shell } => {
+            let mut cmd = Cli::command();
+            let mut script = Vec::new();
+            clap_complete::generate(shell, &mut cmd, "revaultpass", &mut script);
+            print!("{}", completion_script(shell, &String::from_utf8(script)?));
+        }
+        Cmd::CompleteNames => {
+            // Called from the shell on every <Tab>: never prompt, never complain.
+            if let Ok(StoreKind::Plain) = store_kind(&path) {
+                if let Ok(vault) = Vault::open_with_key(&path, StoreKey::Plain) {
+                    for e in vault.list() {
+                        println!("{}", e.name);
+                    }
+                }
             }
-            save_entries(&path, &entries, key_opt)?;
-            println!("Deleted.");
         }
-        "help" | _ => {
-            println!("RevaultPass - password manager (user:password)");
-            println!("  init              create store, set master key (recommended)");
-            println!("  add <name> <user> [password]   add entry");
-            println!("  list              list names (user:****)");
-            println!("  get <name>        print user:password");
-            println!("  delete <name>     remove entry");
+        Cmd::ClearClipboard { timeout, osc52 } => {
+            clear_clipboard_after(timeout, osc52)?;
         }
     }
     Ok(())
@@ -0,0 +1,127 @@
+//! Runs `revaultpass agent start --foreground` with its runtime directory in a
+//! temp dir and drives it with the CLI and with raw socket requests.
+#![cfg(unix)]
+
+pub mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+
+use common::TempDir;
+
+const MASTER_KEY: &str = "agent test master key";
+
+/// The agent process, killed if a test fails before `agent stop`.
+struct Agent {
+    child: Child,
+    socket: PathBuf,
+}
+
+impl Drop for Agent {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn command(dir: &Path) -> Command {
+    let mut cmd = common::command(&dir.join("store.dat"));
+    cmd.stdin(Stdio::null());
+    cmd
+}
+
+/// A client command, talking to `agent` and with no master key of its own.
+fn client(dir: &Path, agent: &Agent, args: &[&str], stdin: &str) -> Output {
+    common::output(command(dir).args(args).env("REVAULTPASS_AGENT_SOCK", &agent.socket), stdin)
+}
+
+/// An encrypted store and a running, unlocked agent for it.
+fn start(dir: &Path) -> Agent {
+    let init = command(dir).args(["init", "--kdf-mem", "8192", "--kdf-iters", "1"]).env("REVAULTPASS_PASSPHRASE", MASTER_KEY).output().unwrap();
+    assert!(init.status.success(), "{}", String::from_utf8_lossy(&init.stderr));
+    let mut child = command(dir).args(["agent", "start", "--foreground", "--idle", "0"]).stdout(Stdio::piped()).stderr(Stdio::null()).spawn().unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap()).read_line(&mut line).unwrap();
+    let socket = line.strip_prefix("REVAULTPASS_AGENT_SOCK=").and_then(|l| l.split(';').next()).unwrap().trim_matches('\'');
+    let agent = Agent { child, socket: PathBuf::from(socket) };
+    assert!(agent.socket.starts_with(dir.join("run")), "{}", agent.socket.display());
+    for _ in 0..50 {
+        if UnixStream::connect(&agent.socket).is_ok() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    let unlock = command(dir).args(["agent", "unlock"]).env("REVAULTPASS_AGENT_SOCK", &agent.socket).env("REVAULTPASS_PASSPHRASE", MASTER_KEY).output().unwrap();
+    assert!(unlock.status.success(), "{}", String::from_utf8_lossy(&unlock.stderr));
+    agent
+}
+
+/// One raw request, framed as the agent expects.
+fn request(socket: &Path, message: &str) -> String {
+    let mut stream = UnixStream::connect(socket).unwrap();
+    stream.write_all(&(message.len() as u32).to_be_bytes()).unwrap();
+    stream.write_all(message.as_bytes()).unwrap();
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).unwrap();
+    let mut body = vec![0u8; u32::from_be_bytes(len) as usize];
+    stream.read_exact(&mut body).unwrap();
+    String::from_utf8(body).unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn commands_get_entries_from_the_agent_without_a_master_key() {
+    let dir = TempDir::new("agent-cli");
+    let agent = start(&dir.0);
+    stdout(&client(&dir.0, &agent, &["add", "site", "alice", "--password-stdin"], "password one\n"));
+    assert_eq!(stdout(&client(&dir.0, &agent, &["get", "site"], "")), "alice:password one\n");
+    assert!(stdout(&client(&dir.0, &agent, &["list"], "")).contains("site"));
+    assert_eq!(stdout(&client(&dir.0, &agent, &["names"], "")), "site\n");
+    assert_eq!(stdout(&client(&dir.0, &agent, &["askpass", "site", "--regex", "(.*)", "--pattern", "{match}"], "")), "password one\n");
+
+    assert_eq!(client(&dir.0, &agent, &["get", "--exact", "missing"], "").status.code(), Some(2));
+    assert_eq!(client(&dir.0, &agent, &["add", "site", "bob", "--password-stdin"], "password two\n").status.code(), Some(3));
+    stdout(&client(&dir.0, &agent, &["delete", "site", "--force"], ""));
+    assert_eq!(client(&dir.0, &agent, &["get", "--exact", "site"], "").status.code(), Some(2));
+
+    // What the agent wrote opens with the master key as usual.
+    let trash = command(&dir.0).args(["trash", "list"]).env("REVAULTPASS_PASSPHRASE", MASTER_KEY).output().unwrap();
+    assert!(stdout(&trash).contains("site"));
+    stdout(&client(&dir.0, &agent, &["agent", "stop"], ""));
+}
+
+#[test]
+fn agent_hands_out_entries_but_never_the_master_key() {
+    let dir = TempDir::new("agent-protocol");
+    let agent = start(&dir.0);
+    stdout(&client(&dir.0, &agent, &["add", "site", "alice", "--password-stdin"], "password one\n"));
+
+    let key = request(&agent.socket, r#"{"op":"key"}"#);
+    assert!(key.contains(r#""ok":false"#) && key.contains("unknown request"), "{}", key);
+    let status = request(&agent.socket, r#"{"op":"status"}"#);
+    assert!(status.contains(r#""unlocked":true"#), "{}", status);
+    let heads = request(&agent.socket, r#"{"op":"list"}"#);
+    assert!(heads.contains(r#""name":"site""#) && !heads.contains("password one"), "{}", heads);
+    let full = request(&agent.socket, r#"{"op":"list","secrets":true}"#);
+    assert!(full.contains("password one"), "{}", full);
+    let added = request(&agent.socket, r#"{"op":"add","entry":{"name":" raw ","user":"bob","password":"password two"}}"#);
+    assert!(added.contains(r#""ok":true"#), "{}", added);
+    let got = request(&agent.socket, r#"{"op":"get","name":"raw"}"#);
+    assert!(got.contains("password two"), "{}", got);
+    let missing = request(&agent.socket, r#"{"op":"get","name":"missing"}"#);
+    assert!(missing.contains(r#""code":2"#) && missing.contains(r#""name":"missing""#), "{}", missing);
+    for response in [key, status, heads, full, added, got, missing] {
+        assert!(!response.contains(MASTER_KEY), "{}", response);
+    }
+
+    request(&agent.socket, r#"{"op":"lock"}"#);
+    let locked = request(&agent.socket, r#"{"op":"get","name":"raw"}"#);
+    assert!(locked.contains(r#""ok":false"#) && locked.contains("locked"), "{}", locked);
+    request(&agent.socket, r#"{"op":"stop"}"#);
+}
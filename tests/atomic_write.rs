@@ -0,0 +1,51 @@
+//! A save that fails halfway through writing leaves the old store as it
+//! was and no `store.dat.tmp` behind.
+
+#![cfg(unix)]
+
+pub mod common;
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+use common::TempDir;
+
+const MASTER_KEY: &str = "atomic write test master key";
+
+/// Runs `revaultpass --store <store> <args>` through `sh`, after `shell`
+/// (e.g. a `ulimit`), without backups so only the store itself is written.
+fn run(store: &Path, shell: &str, args: &[&str], stdin: &str) -> Output {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(format!("{} exec \"$0\" \"$@\"", shell))
+        .arg(env!("CARGO_BIN_EXE_revaultpass"))
+        .arg("--store")
+        .arg(store)
+        .args(args);
+    common::isolate(&mut cmd, store.parent().unwrap()).env("REVAULTPASS_PASSPHRASE", MASTER_KEY).env("REVAULTPASS_BACKUP_KEEP", "0");
+    common::output(&mut cmd, stdin)
+}
+
+#[test]
+fn failed_write_leaves_the_old_store() {
+    let dir = TempDir::new("atomic-write");
+    let store = dir.join("store.dat");
+    for (args, stdin) in [(&["init", "--kdf-mem", "8192", "--kdf-iters", "1"][..], ""), (&["add", "site", "alice", "--password-stdin"], "Xq7!vR2#pL9@wZ4$\n")] {
+        let output = run(&store, "", args, stdin);
+        assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    }
+    let before = std::fs::read(&store).unwrap();
+    assert!(before.len() > 8 * 512);
+
+    // A file size limit of 8 blocks, well under the store's size, fails the
+    // write of the temp file with EFBIG (SIGXFSZ ignored) the way a full disk would.
+    let add = run(&store, "trap '' XFSZ; ulimit -f 8;", &["add", "other", "bob", "--password-stdin"], "Xq7!vR2#pL9@wZ4$\n");
+    assert!(!add.status.success());
+    assert!(String::from_utf8_lossy(&add.stderr).contains("too large"), "{}", String::from_utf8_lossy(&add.stderr));
+    assert!(std::fs::read(&store).unwrap() == before, "the store changed");
+    assert!(!dir.join("store.dat.tmp").exists(), "store.dat.tmp was left behind");
+
+    let get = run(&store, "", &["get", "--exact", "site"], "");
+    assert_eq!(String::from_utf8_lossy(&get.stdout), "alice:Xq7!vR2#pL9@wZ4$\n");
+    assert_eq!(run(&store, "", &["get", "--exact", "other"], "").status.code(), Some(2));
+}
@@ -0,0 +1,83 @@
+//! Setup shared by the integration tests: a temp dir per test, and the
+//! binary run against a store in it, cut off from the user's own config,
+//! agent and key file. Test files declare it `pub mod common;`, so the
+//! helpers a file doesn't use aren't reported as dead code.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// A directory under the system temp dir, removed when dropped.
+pub struct TempDir(pub PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("revaultpass-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Gives `cmd` `home` as its home, config and runtime dirs, and clears the
+/// variables that would reach outside it.
+pub fn isolate<'a>(cmd: &'a mut Command, home: &Path) -> &'a mut Command {
+    cmd.env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_RUNTIME_DIR", home.join("run"))
+        .env_remove("REVAULTPASS_STORE")
+        .env_remove("REVAULTPASS_PASSPHRASE")
+        .env_remove("REVAULTPASS_AGENT_SOCK")
+        .env_remove("REVAULTPASS_ASKPASS")
+        .env_remove("REVAULTPASS_KEYFILE")
+        .env_remove("REVAULTPASS_READONLY")
+}
+
+/// `revaultpass --store <store>`, isolated in the store's directory.
+pub fn command(store: &Path) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_revaultpass"));
+    cmd.arg("--store").arg(store);
+    isolate(&mut cmd, store.parent().unwrap());
+    cmd
+}
+
+/// Runs `cmd` with `stdin` on its standard input.
+pub fn output(cmd: &mut Command, stdin: &str) -> Output {
+    let mut child = cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Runs `revaultpass --store <store> <args>` with `passphrase` as the master
+/// key in the environment, unless `--passphrase-stdin` is among `args`.
+pub fn run(store: &Path, passphrase: &str, args: &[&str], stdin: &str) -> Output {
+    let mut cmd = command(store);
+    cmd.args(args);
+    if !args.contains(&"--passphrase-stdin") {
+        cmd.env("REVAULTPASS_PASSPHRASE", passphrase);
+    }
+    output(&mut cmd, stdin)
+}
+
+/// Runs a command that has to succeed and returns its stdout.
+pub fn ok(store: &Path, passphrase: &str, args: &[&str], stdin: &str) -> String {
+    let output = run(store, passphrase, args, stdin);
+    assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Creates an encrypted store at `store`, with a KDF cheap enough for tests,
+/// and returns what `init` printed on stdout.
+pub fn init(store: &Path, passphrase: &str) -> String {
+    ok(store, passphrase, &["init", "--kdf-mem", "8192", "--kdf-iters", "1"], "")
+}
@@ -0,0 +1,109 @@
+//! The exit statuses documented in the README's "Exit codes" table, one
+//! command at a time against a store in a temp dir.
+
+pub mod common;
+
+use std::path::{Path, PathBuf};
+
+use common::{run, TempDir};
+
+const MASTER_KEY: &str = "exit code test master key";
+
+fn code(store: &Path, args: &[&str]) -> Option<i32> {
+    run(store, MASTER_KEY, args, "").status.code()
+}
+
+/// An encrypted store holding "site" and "other".
+fn store(dir: &TempDir) -> PathBuf {
+    let store = dir.join("store.dat");
+    common::init(&store, MASTER_KEY);
+    for name in ["site", "other"] {
+        let add = run(&store, MASTER_KEY, &["add", name, "alice", "--password-stdin"], "Xq7!vR2#pL9@wZ4$\n");
+        assert!(add.status.success(), "{}", String::from_utf8_lossy(&add.stderr));
+    }
+    store
+}
+
+#[test]
+fn success_is_0() {
+    let dir = TempDir::new("exit-0");
+    let store = store(&dir);
+    let get = run(&store, MASTER_KEY, &["get", "--exact", "site"], "");
+    assert_eq!(get.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&get.stdout), "alice:Xq7!vR2#pL9@wZ4$\n");
+    assert_eq!(code(&store, &["list"]), Some(0));
+    assert_eq!(code(&store, &["delete", "other", "--force"]), Some(0));
+}
+
+#[test]
+fn usage_errors_are_1() {
+    let dir = TempDir::new("exit-1");
+    let store = store(&dir);
+    assert_eq!(code(&store, &["frobnicate"]), Some(1));
+    assert_eq!(code(&store, &["get", "--no-such-flag", "site"]), Some(1));
+    assert_eq!(code(&store, &["rename", "site"]), Some(1));
+    assert_eq!(code(&store, &["--help"]), Some(0));
+}
+
+#[test]
+fn missing_entry_is_2() {
+    let dir = TempDir::new("exit-2");
+    let store = store(&dir);
+    for args in [&["get", "--exact", "missing"][..], &["delete", "missing", "--force"], &["rename", "missing", "new"], &["history", "missing"], &["search", "nothing-matches-this"]] {
+        assert_eq!(code(&store, args), Some(2), "{:?}", args);
+    }
+}
+
+#[test]
+fn name_conflict_is_3() {
+    let dir = TempDir::new("exit-3");
+    let store = store(&dir);
+    assert_eq!(run(&store, MASTER_KEY, &["add", "site", "bob", "--password-stdin"], "Xq7!vR2#pL9@wZ4$\n").status.code(), Some(3));
+    assert_eq!(code(&store, &["rename", "site", "other"]), Some(3));
+    assert_eq!(code(&store, &["duplicate", "site", "other"]), Some(3));
+}
+
+#[test]
+fn wrong_passphrase_is_4() {
+    let dir = TempDir::new("exit-4");
+    let store = store(&dir);
+    let get = run(&store, "not the master key", &["get", "site"], "");
+    assert_eq!(get.status.code(), Some(4));
+    assert!(get.stdout.is_empty());
+}
+
+#[test]
+fn corrupt_store_is_5() {
+    let dir = TempDir::new("exit-5");
+    let store = store(&dir);
+    let data = std::fs::read(&store).unwrap();
+    std::fs::write(&store, &data[..20]).unwrap();
+    assert_eq!(code(&store, &["get", "site"]), Some(5));
+    std::fs::write(&store, b"RVP0{\"version\":2,\"entries\":[").unwrap();
+    assert_eq!(run(&store, "", &["list"], "").status.code(), Some(5));
+    std::fs::write(&store, b"not a store at all").unwrap();
+    assert_eq!(code(&store, &["list"]), Some(5));
+}
+
+#[test]
+fn missing_store_is_6() {
+    let dir = TempDir::new("exit-6");
+    let store = dir.join("nowhere.dat");
+    assert_eq!(run(&store, MASTER_KEY, &["add", "site", "bob", "--password-stdin"], "Xq7!vR2#pL9@wZ4$\n").status.code(), Some(6));
+    for args in [&["stats"][..], &["verify"], &["compact"]] {
+        assert_eq!(code(&store, args), Some(6), "{:?}", args);
+    }
+    // Lookups see a missing store as an empty one.
+    assert_eq!(code(&store, &["get", "site"]), Some(2));
+    assert!(!store.exists());
+}
+
+#[test]
+fn audit_findings_are_7_and_read_only_is_10() {
+    let dir = TempDir::new("exit-7-10");
+    let store = store(&dir);
+    // Both entries have the same password.
+    assert_eq!(code(&store, &["audit"]), Some(7));
+    assert_eq!(run(&store, MASTER_KEY, &["--read-only", "add", "new", "bob", "--password-stdin"], "Xq7!vR2#pL9@wZ4$\n").status.code(), Some(10));
+    assert_eq!(code(&store, &["--read-only", "get", "site"]), Some(0));
+}
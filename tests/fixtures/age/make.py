@@ -0,0 +1,117 @@
+#!/usr/bin/env python3
+"""Writes the age fixtures from the spec (age-encryption.org/v1) with pyca/cryptography.
+
+This is independent of the Rust code in src/lib.rs, so a mistake there
+can't hide in the fixtures. Run it from this directory after
+`ssh-keygen -t ed25519 -N "" -f ssh_ed25519`; it writes x25519.key,
+x25519.age and ssh_ed25519.age.
+"""
+import base64
+import hashlib
+import hmac
+import os
+
+from cryptography.hazmat.primitives import hashes
+from cryptography.hazmat.primitives.asymmetric.x25519 import X25519PrivateKey, X25519PublicKey
+from cryptography.hazmat.primitives.ciphers.aead import ChaCha20Poly1305
+from cryptography.hazmat.primitives.kdf.hkdf import HKDF
+
+PLAIN = "revaultpass age fixture: café ✓\n".encode()
+CHARSET = "qpzry9x8gf2tvdw0s3jn54khce6mua7l"
+P = 2**255 - 19
+
+
+def b64(data):
+    return base64.b64encode(data).decode().rstrip("=")
+
+
+def hkdf(salt, ikm, info):
+    return HKDF(algorithm=hashes.SHA256(), length=32, salt=salt, info=info).derive(ikm)
+
+
+def x25519(scalar, u):
+    return X25519PrivateKey.from_private_bytes(scalar).exchange(X25519PublicKey.from_public_bytes(u))
+
+
+def public(scalar):
+    return X25519PrivateKey.from_private_bytes(scalar).public_key().public_bytes_raw()
+
+
+def bech32(hrp, data):
+    def polymod(values):
+        gen = [0x3B6A57B2, 0x26508E6D, 0x1EA119FA, 0x3D4233DD, 0x2A1462B3]
+        chk = 1
+        for v in values:
+            top = chk >> 25
+            chk = (chk & 0x1FFFFFF) << 5 ^ v
+            for i in range(5):
+                chk ^= gen[i] if (top >> i) & 1 else 0
+        return chk
+
+    groups, acc, bits = [], 0, 0
+    for b in data:
+        acc, bits = acc << 8 | b, bits + 8
+        while bits >= 5:
+            bits -= 5
+            groups.append(acc >> bits & 31)
+    if bits:
+        groups.append(acc << (5 - bits) & 31)
+    expand = [ord(c) >> 5 for c in hrp] + [0] + [ord(c) & 31 for c in hrp]
+    mod = polymod(expand + groups + [0] * 6) ^ 1
+    groups += [mod >> 5 * (5 - i) & 31 for i in range(6)]
+    return hrp + "1" + "".join(CHARSET[g] for g in groups)
+
+
+def wrap(file_key, kind, share, their_key, shared, tag=None):
+    label = b"age-encryption.org/v1/" + kind.encode()
+    body = ChaCha20Poly1305(hkdf(share + their_key, shared, label)).encrypt(bytes(12), file_key, None)
+    args = [tag, b64(share)] if tag else [b64(share)]
+    text = b64(body)
+    lines = [text[i:i + 64] for i in range(0, len(text), 64)]
+    if len(text) % 64 == 0:
+        lines.append("")
+    return "-> %s %s\n%s\n" % (kind, " ".join(args), "\n".join(lines))
+
+
+def seal(stanza_for):
+    file_key = os.urandom(16)
+    header = "age-encryption.org/v1\n" + stanza_for(file_key) + "---"
+    mac = hmac.new(hkdf(b"", file_key, b"header"), header.encode(), hashlib.sha256).digest()
+    nonce = os.urandom(16)
+    payload = ChaCha20Poly1305(hkdf(nonce, file_key, b"payload")).encrypt(bytes(11) + b"\x01", PLAIN, None)
+    return (header + " " + b64(mac) + "\n").encode() + nonce + payload
+
+
+def x25519_fixture():
+    secret = os.urandom(32)
+    recipient = public(secret)
+    with open("x25519.key", "w") as f:
+        f.write("# public key: %s\n%s\n" % (bech32("age", recipient), bech32("age-secret-key-", secret).upper()))
+
+    def stanza(file_key):
+        ephemeral = os.urandom(32)
+        return wrap(file_key, "X25519", public(ephemeral), recipient, x25519(ephemeral, recipient))
+
+    with open("x25519.age", "wb") as f:
+        f.write(seal(stanza))
+
+
+def ssh_fixture():
+    blob = base64.b64decode(open("ssh_ed25519.pub").read().split()[1])
+    y = int.from_bytes(blob[-32:], "little") & (2**255 - 1)
+    montgomery = ((1 + y) * pow(1 - y, P - 2, P) % P).to_bytes(32, "little")
+    tag = b64(hashlib.sha256(blob).digest()[:4])
+    tweak = hkdf(blob, b"", b"age-encryption.org/v1/ssh-ed25519")
+
+    def stanza(file_key):
+        ephemeral = os.urandom(32)
+        shared = x25519(tweak, x25519(ephemeral, montgomery))
+        return wrap(file_key, "ssh-ed25519", public(ephemeral), montgomery, shared, tag)
+
+    with open("ssh_ed25519.age", "wb") as f:
+        f.write(seal(stanza))
+
+
+if __name__ == "__main__":
+    x25519_fixture()
+    ssh_fixture()
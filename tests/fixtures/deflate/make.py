@@ -0,0 +1,42 @@
+#!/usr/bin/env python3
+"""Writes raw DEFLATE streams from zlib for the decompressor's tests.
+
+plain.json is a store-like document, big enough for several blocks and
+for matches reaching back across the whole 32 KiB window. stored.deflate
+is level 0 (stored blocks), fixed.deflate uses the fixed Huffman codes
+and dynamic.deflate is level 9 (dynamic Huffman codes). Run it from this
+directory.
+"""
+import json
+import random
+import zlib
+
+r = random.Random(95)
+
+
+def word(n):
+    return "".join(r.choice("abcdefghijklmnopqrstuvwxyz0123456789") for _ in range(n))
+
+
+entries = [
+    {
+        "name": "%s/%s-%d" % (r.choice(["work", "home", "bank"]), word(6), i),
+        "user": "user%d@example.com" % i,
+        "password": word(20),
+        "url": "https://%s.example.com/login" % word(8),
+        "notes": "café ✓ " * r.randint(0, 3) + word(r.randint(0, 40)),
+    }
+    for i in range(400)
+]
+plain = json.dumps({"version": 2, "entries": entries, "trash": []}, ensure_ascii=False).encode()
+open("plain.json", "wb").write(plain)
+
+
+def raw(level, strategy=zlib.Z_DEFAULT_STRATEGY):
+    c = zlib.compressobj(level, zlib.DEFLATED, -15, 9, strategy)
+    return c.compress(plain) + c.flush()
+
+
+open("stored.deflate", "wb").write(raw(0))
+open("fixed.deflate", "wb").write(raw(9, zlib.Z_FIXED))
+open("dynamic.deflate", "wb").write(raw(9))
@@ -0,0 +1,108 @@
+#!/usr/bin/env python3
+"""Writes one store per on-disk format with the release that wrote it.
+
+Each fixture is made by building the commit that introduced the format
+and running its own `init` and `add`, so the files are what users of that
+release really have on disk. Run it from the repository root; it needs
+git, cargo and a pty (the first release only read the master key from
+the terminal). Every encrypted fixture has the master key PASSPHRASE and
+the entries in ENTRIES.
+"""
+import os
+import pty
+import select
+import shutil
+import subprocess
+import sys
+import tempfile
+import time
+
+PASSPHRASE = "fixture passphrase"
+ENTRIES = [("site", "alice", "site password"), ("mail", "bob@example.com", "wörd with \"quotes\"")]
+
+# (fixture, commit, takes --kdf-mem); None is the working tree.
+FORMATS = [
+    ("rvp0.dat", "1b03eef", False),
+    ("rvp1-legacy.dat", "1b03eef", False),
+    ("rvp1-v1.dat", "df39be8", False),
+    ("v2.dat", "234a407", True),
+    ("v3.dat", "d192c59", True),
+    ("v4.dat", "acef6d8", True),
+    ("v5.dat", "09c354e", True),
+    ("v6.dat", "382fab3", True),
+    ("v7.dat", "dfb01f5", True),
+    ("v8.dat", "407548a", True),
+    ("v9.dat", "6a8c99a", True),
+    ("v10.dat", "adf546b", True),
+    ("v11.dat", "3953e6c", True),
+    ("v12.dat", None, True),
+]
+
+
+def build(commit, work):
+    target = os.path.join(work, "target")
+    if commit is None:
+        source = os.getcwd()
+    else:
+        source = os.path.join(work, commit)
+        if not os.path.exists(source):
+            subprocess.run(["git", "worktree", "add", "--detach", source, commit], check=True)
+    subprocess.run(["cargo", "build", "--release", "--quiet"], cwd=source, check=True, env=dict(os.environ, CARGO_TARGET_DIR=target))
+    binary = os.path.join(work, "bin", commit or "HEAD")
+    os.makedirs(os.path.dirname(binary), exist_ok=True)
+    shutil.copy(os.path.join(target, "release", "revaultpass"), binary)
+    return binary
+
+
+def on_tty(args, env, lines):
+    """Runs the first release, which asks for every secret on the terminal."""
+    pid, fd = pty.fork()
+    if pid == 0:
+        os.execve(args[0], args, env)
+    for line in lines:
+        time.sleep(0.5)
+        os.write(fd, (line + "\n").encode())
+    while select.select([fd], [], [], 5)[0]:
+        try:
+            if not os.read(fd, 4096):
+                break
+        except OSError:
+            break
+    _, status = os.waitpid(pid, 0)
+    assert os.waitstatus_to_exitcode(status) == 0, args
+
+
+def make(name, commit, kdf, binary, home):
+    store = os.path.join(home, ".local", "share", "revaultpass", "store.dat")
+    if os.path.exists(store):
+        os.remove(store)
+    env = dict(os.environ, HOME=home, XDG_CONFIG_HOME=os.path.join(home, "config"), XDG_DATA_HOME=os.path.join(home, ".local", "share"))
+    passphrase = "" if name == "rvp0.dat" else PASSPHRASE
+    if commit == "1b03eef":
+        on_tty([binary, "init"], env, [passphrase])
+        for entry in ENTRIES:
+            on_tty([binary, "add", *entry], env, [passphrase])
+    else:
+        env["REVAULTPASS_PASSPHRASE"] = passphrase
+        init = ["--store", store, "init"] + (["--kdf-mem", "8192", "--kdf-iters", "1"] if kdf else [])
+        subprocess.run([binary, *init], env=env, check=True, stdout=subprocess.DEVNULL)
+        for entry in ENTRIES:
+            subprocess.run([binary, "--store", store, "add", *entry], env=env, check=True, stdout=subprocess.DEVNULL)
+    shutil.copy(store, os.path.join("tests", "fixtures", "formats", name))
+
+
+if __name__ == "__main__":
+    work = tempfile.mkdtemp(prefix="revaultpass-formats-")
+    home = os.path.join(work, "home")
+    binaries = {}
+    try:
+        for name, commit, kdf in FORMATS:
+            if commit not in binaries:
+                binaries[commit] = build(commit, work)
+            make(name, commit, kdf, binaries[commit], home)
+            print(name, file=sys.stderr)
+    finally:
+        for commit in binaries:
+            if commit is not None:
+                subprocess.run(["git", "worktree", "remove", "--force", os.path.join(work, commit)])
+        shutil.rmtree(work)
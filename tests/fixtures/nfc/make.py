@@ -0,0 +1,90 @@
+#!/usr/bin/env python3
+"""Writes cases.txt, NFC test cases from Python's unicodedata, for the tests
+of `names::nfc` in src/lib.rs. With --tables it prints the tables that
+module is built on instead, in the layout used there.
+
+Each line of cases.txt is a source string and its NFC form, tab-separated,
+as space-separated hex code points: every character with a canonical
+decomposition given fully decomposed, a sample of Hangul syllables as
+jamo, and marks in both orders after a few bases.
+"""
+import sys
+import unicodedata
+
+MAX = 0x110000
+
+
+def decomposition(c):
+    d = unicodedata.decomposition(chr(c))
+    if not d or d.startswith("<"):
+        return None
+    return [int(x, 16) for x in d.split()]
+
+
+def ranges():
+    out = []
+    for c in range(MAX):
+        cc = unicodedata.combining(chr(c))
+        if not cc:
+            continue
+        if out and out[-1][1] == c - 1 and out[-1][2] == cc:
+            out[-1][1] = c
+        else:
+            out.append([c, c, cc])
+    return out
+
+
+def rows(items, per_line, fmt):
+    lines = []
+    for i in range(0, len(items), per_line):
+        lines.append("        " + " ".join(fmt(x) for x in items[i:i + per_line]))
+    return "\n".join(lines)
+
+
+def tables():
+    classes = ranges()
+    print("    static CLASSES: [(u32, u32, u8); %d] = [" % len(classes))
+    print(rows(classes, 6, lambda r: "(0x%04x, 0x%04x, %d)," % tuple(r)))
+    print("    ];\n")
+    decomps, excluded = [], []
+    for c in range(MAX):
+        d = decomposition(c)
+        if d is None:
+            continue
+        decomps.append((c, d[0], d[1] if len(d) > 1 else 0))
+        if len(d) == 2 and unicodedata.normalize("NFC", chr(c)) != chr(c):
+            excluded.append(c)
+    print("    static DECOMPOSITIONS: [(u32, u32, u32); %d] = [" % len(decomps))
+    print(rows(decomps, 6, lambda r: "(0x%04x, 0x%04x, 0x%04x)," % r))
+    print("    ];\n")
+    print("    static EXCLUDED: [u32; %d] = [" % len(excluded))
+    print(rows(excluded, 12, lambda c: "0x%04x," % c))
+    print("    ];")
+
+
+def hexes(s):
+    return " ".join("%04X" % ord(c) for c in s)
+
+
+def cases():
+    sources = []
+    for c in range(MAX):
+        if decomposition(c) is not None:
+            sources.append(unicodedata.normalize("NFD", chr(c)))
+    sources += [unicodedata.normalize("NFD", chr(c)) for c in range(0xAC00, 0xD7A4, 97)]
+    marks = ["\u0301", "\u0302", "\u0308", "\u0323", "\u0327", "\u0306", "\u0345", "\u0313"]
+    for base in "aeouAE\u03b1\u03b7\u03c9\u0438\u0443":
+        for m in marks:
+            for n in marks:
+                if m != n:
+                    sources.append(base + m + n)
+    with open("cases.txt", "w") as f:
+        for s in sources:
+            f.write("%s\t%s\n" % (hexes(s), hexes(unicodedata.normalize("NFC", s))))
+
+
+if __name__ == "__main__":
+    if sys.argv[1:] == ["--tables"]:
+        tables()
+    else:
+        cases()
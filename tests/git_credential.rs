@@ -0,0 +1,87 @@
+//! Drives `revaultpass git-credential` the way git does: a request of
+//! `key=value` lines on stdin, the master key from the environment.
+
+pub mod common;
+
+use std::path::{Path, PathBuf};
+
+use common::{run, TempDir};
+
+const MASTER_KEY: &str = "git credential test master key";
+
+/// Runs the helper with `request` and returns what it printed, checking
+/// that it succeeded.
+fn helper(store: &Path, args: &[&str], request: &str) -> String {
+    let output = run(store, MASTER_KEY, &[&["git-credential"][..], args].concat(), request);
+    assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn store(dir: &TempDir) -> PathBuf {
+    let store = dir.join("store.dat");
+    common::init(&store, MASTER_KEY);
+    store
+}
+
+const GET: &str = "protocol=https\nhost=example.com\n\n";
+
+#[test]
+fn store_get_and_erase_a_credential() {
+    let dir = TempDir::new("git-credential");
+    let store = store(&dir);
+    assert_eq!(helper(&store, &["get"], GET), "");
+
+    let approved = "protocol=https\nhost=example.com\nusername=alice\npassword=s3cret =x\n\n";
+    assert_eq!(helper(&store, &["store"], approved), "");
+    assert_eq!(helper(&store, &["get"], GET), "username=alice\npassword=s3cret =x\n");
+    assert_eq!(helper(&store, &["get"], "protocol=https\nhost=example.com\nusername=alice\n\n"), "username=alice\npassword=s3cret =x\n");
+    assert_eq!(helper(&store, &["get"], "protocol=https\nhost=example.com\nusername=bob\n\n"), "");
+    assert_eq!(helper(&store, &["get"], "protocol=https\nhost=example.org\n\n"), "");
+    let get = run(&store, MASTER_KEY, &["get", "--exact", "git/example.com"], "");
+    assert_eq!(String::from_utf8_lossy(&get.stdout), "alice:s3cret =x\n");
+
+    // A new password replaces the old one, which goes to the history.
+    helper(&store, &["store"], "protocol=https\nhost=example.com\nusername=alice\npassword=n3w\n\n");
+    assert_eq!(helper(&store, &["get"], GET), "username=alice\npassword=n3w\n");
+    let history = run(&store, MASTER_KEY, &["history", "git/example.com"], "");
+    assert!(String::from_utf8_lossy(&history.stdout).contains("s3cret =x"), "{}", String::from_utf8_lossy(&history.stderr));
+
+    // Git erases the credential it tried; a password stored since stays.
+    helper(&store, &["erase"], "protocol=https\nhost=example.com\nusername=alice\npassword=s3cret =x\n\n");
+    assert_eq!(helper(&store, &["get"], GET), "username=alice\npassword=n3w\n");
+    helper(&store, &["erase"], "protocol=https\nhost=example.com\nusername=alice\npassword=n3w\n\n");
+    assert_eq!(helper(&store, &["get"], GET), "");
+    assert_eq!(run(&store, MASTER_KEY, &["get", "--exact", "git/example.com"], "").status.code(), Some(2));
+}
+
+#[test]
+fn pattern_names_the_entry() {
+    let dir = TempDir::new("git-credential-pattern");
+    let store = store(&dir);
+    let pattern = ["--pattern", "git/{host}/{username}"];
+    helper(&store, &[&["store"][..], &pattern].concat(), "protocol=https\nhost=example.com\nusername=alice\npassword=one\n\n");
+    helper(&store, &[&["store"][..], &pattern].concat(), "protocol=https\nhost=example.com\nusername=bob\npassword=two\n\n");
+    let names = run(&store, MASTER_KEY, &["names"], "");
+    assert_eq!(String::from_utf8_lossy(&names.stdout), "git/example.com/alice\ngit/example.com/bob\n");
+    assert_eq!(helper(&store, &[&["get"][..], &pattern].concat(), "protocol=https\nhost=example.com\nusername=bob\n\n"), "username=bob\npassword=two\n");
+    // Without the pattern the default name is looked up, and there is none.
+    assert_eq!(helper(&store, &["get"], "protocol=https\nhost=example.com\nusername=bob\n\n"), "");
+}
+
+#[test]
+fn incomplete_requests_and_bad_keys_print_nothing() {
+    let dir = TempDir::new("git-credential-edge");
+    let store = store(&dir);
+    assert_eq!(helper(&store, &["store"], "protocol=https\nusername=alice\npassword=x\n\n"), "");
+    assert_eq!(helper(&store, &["store"], "protocol=https\nhost=example.com\nusername=alice\n\n"), "");
+    assert_eq!(String::from_utf8_lossy(&run(&store, MASTER_KEY, &["names"], "").stdout), "");
+
+    helper(&store, &["store"], "protocol=https\nhost=example.com\nusername=alice\npassword=s3cret\n\n");
+    let wrong = run(&store, "not the master key", &["git-credential", "get"], GET);
+    assert_eq!(wrong.status.code(), Some(4));
+    assert!(wrong.stdout.is_empty());
+    // Stdin is git's, so the master key can't come from there.
+    let stdin_key = run(&store, "", &["--passphrase-stdin", "git-credential", "get"], &format!("{}\n{}", MASTER_KEY, GET));
+    assert_eq!(stdin_key.status.code(), Some(1));
+    assert!(stdin_key.stdout.is_empty());
+}
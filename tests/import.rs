@@ -0,0 +1,167 @@
+//! `import` through the binary: what each format carries over, and what
+//! `--on-conflict` does to entries that are already in the store.
+
+pub mod common;
+
+use std::path::{Path, PathBuf};
+
+use common::TempDir;
+
+const MASTER_KEY: &str = "import test master key";
+
+fn ok(store: &Path, args: &[&str], stdin: &str) -> String {
+    common::ok(store, MASTER_KEY, args, stdin)
+}
+
+fn store(dir: &TempDir) -> PathBuf {
+    let store = dir.join("store.dat");
+    common::init(&store, MASTER_KEY);
+    store
+}
+
+/// Writes `text` to `file` in `dir` and imports it as `format`.
+fn import(dir: &TempDir, store: &Path, format: &str, file: &str, text: &str, extra: &[&str]) -> String {
+    let path = dir.join(file);
+    std::fs::write(&path, text).unwrap();
+    ok(store, &[&["import", "--format", format, path.to_str().unwrap()][..], extra].concat(), "")
+}
+
+/// The store's entries as exported to JSON.
+fn entries(store: &Path) -> Vec<serde_json::Value> {
+    let json: serde_json::Value = serde_json::from_str(&ok(store, &["export", "--format", "json", "--stdout", "--force"], "")).unwrap();
+    json.as_array().or_else(|| json["entries"].as_array()).unwrap().clone()
+}
+
+fn entry(store: &Path, name: &str) -> serde_json::Value {
+    let entries = entries(store);
+    entries.iter().find(|e| e["name"] == name).unwrap_or_else(|| panic!("no {} in {:?}", name, entries)).clone()
+}
+
+#[test]
+fn overwrite_replaces_every_field() {
+    let dir = TempDir::new("import-overwrite");
+    let store = store(&dir);
+    let old = "[[entry]]\nname = \"site\"\nuser = \"alice\"\npassword = \"old password\"\nurl = \"https://old.example.com\"\nnotes = \"old notes\"\ntags = [\"old\"]\ntotp = \"JBSWY3DPEHPK3PXP\"\ncreated_at = 1000000\nmodified_at = 1000000\n\n[entry.fields]\npin = \"1111\"\n";
+    import(&dir, &store, "toml", "old.toml", old, &[]);
+
+    let csv = "name,user,password,url,notes,tags,fields,totp\nsite,bob,new password,https://new.example.com,new notes,\"new,work\",\"{\"\"pin\"\":\"\"2222\"\"}\",GEZDGNBVGY3TQOJQ\n";
+    import(&dir, &store, "csv", "new.csv", csv, &["--on-conflict", "overwrite"]);
+    let site = entry(&store, "site");
+    assert_eq!(site["user"], "bob");
+    assert_eq!(site["password"], "new password");
+    assert_eq!(site["url"], "https://new.example.com");
+    assert_eq!(site["notes"], "new notes");
+    assert_eq!(site["tags"], serde_json::json!(["new", "work"]));
+    assert_eq!(site["fields"], serde_json::json!({ "pin": "2222" }));
+    assert_eq!(site["totp"], "GEZDGNBVGY3TQOJQ");
+    assert_eq!(site["created_at"], 1000000);
+    assert!(site["modified_at"].as_u64().unwrap() > 1000000);
+    assert_eq!(site["history"][0]["password"], "old password");
+    assert_eq!(entries(&store).len(), 1);
+}
+
+/// The `Title`, `UserName`, `Password`, `URL` and `Notes` of each entry in a
+/// KeePass XML export, unescaped, as the CSV KeePassXC exports from it.
+fn keepass_xml_to_csv(xml: &str) -> String {
+    let entry = regex::Regex::new(r"(?s)<Entry>(.*?)</Entry>").unwrap();
+    let string = regex::Regex::new(r"(?s)<String><Key>(.*?)</Key><Value[^>]*>(.*?)</Value></String>").unwrap();
+    let unescape = |v: &str| v.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&");
+    let quote = |v: &str| format!("\"{}\"", v.replace('"', "\"\""));
+    let mut csv = String::from("\"Group\",\"Title\",\"Username\",\"Password\",\"URL\",\"Notes\"\n");
+    for e in entry.captures_iter(xml) {
+        let value = |key: &str| string.captures_iter(&e[1]).find(|s| &s[1] == key).map(|s| unescape(&s[2])).unwrap_or_default();
+        let row: Vec<String> = ["RevaultPass".to_string(), value("Title"), value("UserName"), value("Password"), value("URL"), value("Notes")].iter().map(|v| quote(v)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+#[test]
+fn keepass_xml_export_imports_back_through_keepass_csv() {
+    let dir = TempDir::new("import-keepass-xml");
+    let store = store(&dir);
+    let batch = [
+        r#"{"name":"a <&>\"' b","user":"u<&>\"'","password":"p<&>\"'w &amp;","url":"https://example.com/?a=1&b=<2>","notes":"one <&>\n\"two\" 'three'"}"#,
+        r#"{"name":"plain","user":"bob","password":"hunter2"}"#,
+    ];
+    ok(&store, &["add", "--batch"], &batch.join("\n"));
+    let xml = ok(&store, &["export", "--format", "keepass-xml", "--stdout", "--force"], "");
+    assert!(xml.contains("<Value>a &lt;&amp;&gt;&quot;&apos; b</Value>"), "{}", xml);
+    assert!(!xml.contains("<&>") && !xml.contains("\"'"), "{}", xml);
+
+    let copy = dir.join("copy.dat");
+    common::init(&copy, MASTER_KEY);
+    import(&dir, &copy, "keepass", "keepass.csv", &keepass_xml_to_csv(&xml), &[]);
+    assert_eq!(ok(&copy, &["names"], ""), "RevaultPass/a <&>\"' b\nRevaultPass/plain\n");
+    for (name, original) in [("RevaultPass/a <&>\"' b", "a <&>\"' b"), ("RevaultPass/plain", "plain")] {
+        let (imported, original) = (entry(&copy, name), entry(&store, original));
+        for key in ["user", "password", "url", "notes"] {
+            assert_eq!(imported.get(key), original.get(key), "{} of {}", key, name);
+        }
+    }
+}
+
+#[test]
+fn keepass_csv_keeps_url_and_multi_line_notes() {
+    let dir = TempDir::new("import-keepass");
+    let store = store(&dir);
+    let csv = concat!(
+        "\"Group\",\"Title\",\"Username\",\"Password\",\"URL\",\"Notes\"\n",
+        "\"Root/Web\",\"Mail\",\"alice\",\"pw, with comma\",\"https://mail.example.com\",\"first line\n\"\"quoted\"\", second\n\nlast, line\"\n",
+        "\"Root\",\"Bare\",\"bob\",\"pw\",\"\",\"\"\n",
+    );
+    import(&dir, &store, "keepass", "keepass.csv", csv, &[]);
+    let mail = entry(&store, "Root/Web/Mail");
+    assert_eq!(mail["password"], "pw, with comma");
+    assert_eq!(mail["url"], "https://mail.example.com");
+    assert_eq!(mail["notes"], "first line\n\"quoted\", second\n\nlast, line");
+    let bare = entry(&store, "Root/Bare");
+    assert!(bare.get("url").is_none() && bare.get("notes").is_none(), "{}", bare);
+}
+
+#[test]
+fn lastpass_secure_notes_become_notes() {
+    let dir = TempDir::new("import-lastpass");
+    let store = store(&dir);
+    let csv = concat!(
+        "url,username,password,totp,extra,name,grouping,fav\n",
+        "https://mail.example.com,alice,pw1,,\"recovery: 1234\nsecond line\",Mail,Personal,0\n",
+        "https://bank.example.com,bob,pw2,,,Bank,,1\n",
+        "http://sn,,,,\"Wi-Fi: home\npassword, with comma\",Wi-Fi,Personal,0\n",
+        "http://sn,,,,,Empty note,,0\n",
+        "https://new.example.com,carol,pw3,,,Generated Password for new.example.com,,0\n",
+    );
+    import(&dir, &store, "lastpass", "lastpass.csv", csv, &[]);
+    assert_eq!(ok(&store, &["names"], ""), "Bank\nEmpty note\nPersonal/Mail\nPersonal/Wi-Fi\n");
+
+    let mail = entry(&store, "Personal/Mail");
+    assert_eq!(mail["url"], "https://mail.example.com");
+    assert_eq!(mail["notes"], "recovery: 1234\nsecond line");
+    let bank = entry(&store, "Bank");
+    assert_eq!(bank["password"], "pw2");
+    assert!(bank.get("notes").is_none(), "{}", bank);
+
+    let wifi = entry(&store, "Personal/Wi-Fi");
+    assert_eq!(wifi["kind"], "note");
+    assert_eq!(wifi["body"], "Wi-Fi: home\npassword, with comma");
+    assert_eq!(ok(&store, &["get", "--exact", "Personal/Wi-Fi"], ""), "Wi-Fi: home\npassword, with comma");
+    assert_eq!(entry(&store, "Empty note")["kind"], "note");
+}
+
+#[test]
+fn bitwarden_keeps_the_first_uri() {
+    let dir = TempDir::new("import-bitwarden");
+    let store = store(&dir);
+    let json = r#"{"encrypted": false, "items": [
+        {"type": 1, "name": "Mail", "login": {"username": "alice", "password": "pw1", "uris": [{"match": null, "uri": "https://mail.example.com/login"}, {"match": 3, "uri": "https://other.example.com"}]}},
+        {"type": 1, "name": "No uris", "login": {"username": "bob", "password": "pw2", "uris": null}},
+        {"type": 1, "name": "Missing uris", "login": {"username": "carol", "password": "pw3"}},
+        {"type": 2, "name": "A note", "secureNote": {"type": 0}}
+    ]}"#;
+    import(&dir, &store, "bitwarden", "bitwarden.json", json, &[]);
+    assert_eq!(ok(&store, &["names"], ""), "Mail\nMissing uris\nNo uris\n");
+    assert_eq!(entry(&store, "Mail")["url"], "https://mail.example.com/login");
+    assert_eq!(entry(&store, "Mail")["password"], "pw1");
+    assert!(entry(&store, "No uris").get("url").is_none() && entry(&store, "Missing uris").get("url").is_none());
+}
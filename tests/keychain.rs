@@ -0,0 +1,37 @@
+//! `keychain` where there is no keychain to use: `status` says why, and
+//! `enable` fails before asking for the master key. Outside macOS, an empty
+//! `PATH` hides `secret-tool`.
+
+#![cfg(not(target_os = "macos"))]
+
+pub mod common;
+
+use common::TempDir;
+
+const MASTER_KEY: &str = "keychain test master key";
+
+#[test]
+fn no_keychain_is_reported() {
+    let dir = TempDir::new("keychain");
+    let store = dir.join("store.dat");
+    common::init(&store, MASTER_KEY);
+    let empty = dir.join("empty");
+    std::fs::create_dir(&empty).unwrap();
+    let run = |args: &[&str]| common::output(common::command(&store).args(args).env("PATH", &empty), "");
+
+    let status = run(&["keychain", "status", "--json"]);
+    assert!(status.status.success(), "{}", String::from_utf8_lossy(&status.stderr));
+    let json: serde_json::Value = serde_json::from_slice(&status.stdout).unwrap();
+    assert_eq!(json["available"], false);
+    assert_eq!(json["saved"], false);
+    let reason = json["reason"].as_str().unwrap();
+    assert!(reason.contains(if cfg!(windows) { "Windows" } else { "secret-tool" }), "{}", reason);
+
+    let status = run(&["keychain", "status"]);
+    assert!(String::from_utf8_lossy(&status.stdout).starts_with(if cfg!(windows) { "The keychain isn't supported" } else { "No keychain available" }));
+
+    // No master key in the environment: enable would have to prompt for one.
+    let enable = run(&["keychain", "enable"]);
+    assert_eq!(enable.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&enable.stderr).contains(reason), "{}", String::from_utf8_lossy(&enable.stderr));
+}
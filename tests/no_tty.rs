@@ -0,0 +1,96 @@
+//! Commands run with stdin redirected from a file and no controlling
+//! terminal, as under cron or systemd: they fail fast where they'd have to
+//! prompt, and take the master key and passwords from stdin when told to.
+
+#![cfg(unix)]
+
+pub mod common;
+
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use common::TempDir;
+
+const MASTER_KEY: &str = "no tty test master key";
+const PASSWORD: &str = "Xq7!vR2#pL9@wZ4$";
+
+/// Runs `revaultpass --store <store> <args>` in a session of its own, so
+/// without a controlling terminal, with stdin read from a file holding
+/// `stdin` and the master key in the environment only if given.
+fn run(store: &Path, passphrase: Option<&str>, args: &[&str], stdin: &str) -> Output {
+    let input = store.with_extension("stdin");
+    std::fs::write(&input, stdin).unwrap();
+    let mut cmd = common::command(store);
+    cmd.args(args).stdin(std::fs::File::open(&input).unwrap());
+    if let Some(passphrase) = passphrase {
+        cmd.env("REVAULTPASS_PASSPHRASE", passphrase);
+    }
+    // SAFETY: setsid only changes the session of the forked child.
+    unsafe {
+        cmd.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+    cmd.output().unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// A store holding "site", added through the files above.
+fn store(dir: &TempDir) -> PathBuf {
+    let store = dir.join("store.dat");
+    let init = run(&store, Some(MASTER_KEY), &["init", "--kdf-mem", "8192", "--kdf-iters", "1"], "");
+    assert!(init.status.success(), "{}", stderr(&init));
+    let add = run(&store, Some(MASTER_KEY), &["add", "site", "alice", "--password-stdin"], &format!("{}\n", PASSWORD));
+    assert!(add.status.success(), "{}", stderr(&add));
+    store
+}
+
+#[test]
+fn no_master_key_source_fails_fast() {
+    let dir = TempDir::new("no-tty-key");
+    let store = store(&dir);
+    for args in [&["get", "site"][..], &["list"], &["add", "other", "bob", "--password-stdin"]] {
+        let output = run(&store, None, args, &format!("{}\n", MASTER_KEY));
+        assert_eq!(output.status.code(), Some(1), "{:?}: {}", args, stderr(&output));
+        assert!(output.stdout.is_empty());
+        let err = stderr(&output);
+        assert!(err.contains("no terminal to ask for 'Master key' on"), "{:?}: {}", args, err);
+        for option in ["--passphrase-stdin", "REVAULTPASS_PASSPHRASE", "REVAULTPASS_AGENT_SOCK", "REVAULTPASS_ASKPASS", "--pinentry"] {
+            assert!(err.contains(option), "{:?}: no {} in {}", args, option, err);
+        }
+    }
+    assert_eq!(run(&store, Some(MASTER_KEY), &["names"], "").stdout, b"site\n");
+}
+
+#[test]
+fn passphrase_stdin_reads_the_first_line() {
+    let dir = TempDir::new("no-tty-passphrase-stdin");
+    let store = store(&dir);
+    let get = run(&store, None, &["--passphrase-stdin", "get", "site"], &format!("{}\n", MASTER_KEY));
+    assert!(get.status.success(), "{}", stderr(&get));
+    assert_eq!(String::from_utf8_lossy(&get.stdout), format!("alice:{}\n", PASSWORD));
+    let wrong = run(&store, None, &["--passphrase-stdin", "get", "site"], "not the master key\n");
+    assert_eq!(wrong.status.code(), Some(4));
+    assert!(wrong.stdout.is_empty());
+}
+
+#[test]
+fn password_stdin_needs_no_terminal() {
+    let dir = TempDir::new("no-tty-password-stdin");
+    let store = store(&dir);
+    let add = run(&store, None, &["--passphrase-stdin", "add", "other", "bob", "--password-stdin"], &format!("{}\nsecond {}\n", MASTER_KEY, PASSWORD));
+    assert!(add.status.success(), "{}", stderr(&add));
+    let get = run(&store, Some(MASTER_KEY), &["get", "--field", "password", "other"], "");
+    assert_eq!(String::from_utf8_lossy(&get.stdout), format!("second {}\n", PASSWORD));
+
+    // Without --password-stdin there is no one to ask for the password.
+    let prompt = run(&store, Some(MASTER_KEY), &["add", "third", "carol"], &format!("{}\n", PASSWORD));
+    assert_eq!(prompt.status.code(), Some(1));
+    assert!(stderr(&prompt).contains("no terminal to ask for 'Password' on; pipe it in with --password-stdin"), "{}", stderr(&prompt));
+    assert_eq!(run(&store, Some(MASTER_KEY), &["names"], "").stdout, b"other\nsite\n");
+}
@@ -0,0 +1,119 @@
+//! What goes to stdout is only what was asked for, so it can be piped:
+//! prompts, status lines and warnings go to stderr.
+
+pub mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use common::TempDir;
+use revaultpass::totp::Totp;
+
+const MASTER_KEY: &str = "stdout test master key";
+const PASSWORD: &str = "Xq7!vR2#pL9@wZ4$ x";
+const SEED: &str = "JBSWY3DPEHPK3PXP";
+
+/// Runs a command that has to succeed, with the master key in the
+/// environment unless `--passphrase-stdin` is among `args`.
+fn run(store: &Path, args: &[&str], stdin: &str) -> Output {
+    let output = common::run(store, MASTER_KEY, args, stdin);
+    assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    output
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+fn store(dir: &TempDir) -> PathBuf {
+    let store = dir.join("store.dat");
+    assert_eq!(common::init(&store, MASTER_KEY), "");
+    assert_eq!(stdout(&run(&store, &["add", "site", "alice", "--password-stdin", "--totp", SEED], &format!("{}\n", PASSWORD))), "");
+    store
+}
+
+#[test]
+fn get_prints_only_the_entry() {
+    let dir = TempDir::new("stdout-get");
+    let store = store(&dir);
+    assert_eq!(stdout(&run(&store, &["get", "site"], "")), format!("alice:{}\n", PASSWORD));
+    assert_eq!(stdout(&run(&store, &["get", "--field", "password", "site"], "")), format!("{}\n", PASSWORD));
+    assert_eq!(stdout(&run(&store, &["--passphrase-stdin", "get", "--field", "password", "site"], &format!("{}\n", MASTER_KEY))), format!("{}\n", PASSWORD));
+    assert_eq!(stdout(&run(&store, &["--quiet", "get", "--field", "user", "site"], "")), "alice\n");
+}
+
+#[test]
+fn get_json_prints_one_object() {
+    let dir = TempDir::new("stdout-json");
+    let store = store(&dir);
+    let output = run(&store, &["get", "--json", "site"], "");
+    let text = stdout(&output);
+    assert!(text.ends_with("}\n") && text.lines().count() == 1, "{:?}", text);
+    let entry: serde_json::Value = serde_json::from_str(text).unwrap();
+    assert_eq!(entry["name"], "site");
+    assert_eq!(entry["user"], "alice");
+    assert_eq!(entry["password"], PASSWORD);
+}
+
+#[test]
+fn list_json_is_an_array_of_entries() {
+    let dir = TempDir::new("stdout-list-json");
+    let store = store(&dir);
+    run(&store, &["add", "other", "bob", "--password-stdin"], "second password\n");
+
+    let list: serde_json::Value = serde_json::from_str(stdout(&run(&store, &["list", "--json"], ""))).unwrap();
+    let entries = list.as_array().unwrap();
+    let pairs: Vec<(&str, &str)> = entries.iter().map(|e| (e["name"].as_str().unwrap(), e["user"].as_str().unwrap())).collect();
+    assert_eq!(pairs, [("site", "alice"), ("other", "bob")]);
+    assert!(entries.iter().all(|e| e.get("password").is_none()), "{}", list);
+
+    let list: serde_json::Value = serde_json::from_str(stdout(&run(&store, &["list", "--json", "--show-passwords"], ""))).unwrap();
+    let passwords: Vec<&str> = list.as_array().unwrap().iter().map(|e| e["password"].as_str().unwrap()).collect();
+    assert_eq!(passwords, [PASSWORD, "second password"]);
+}
+
+#[test]
+fn json_errors_are_one_object_on_stderr() {
+    let dir = TempDir::new("stdout-json-error");
+    let store = store(&dir);
+    let output = common::run(&store, MASTER_KEY, &["get", "--json", "missing"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    let err = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(err.lines().count(), 1, "{:?}", err);
+    let error: serde_json::Value = serde_json::from_str(&err).unwrap();
+    assert_eq!(error["code"], 2);
+    assert!(error["error"].as_str().unwrap().contains("'missing'"), "{}", error);
+    assert_eq!(error.as_object().unwrap().len(), 2, "{}", error);
+}
+
+#[test]
+fn otp_prints_only_the_code() {
+    let dir = TempDir::new("stdout-otp");
+    let store = store(&dir);
+    let totp = Totp::parse(SEED).unwrap();
+    let now = || std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    // The code can roll over while the command runs.
+    let (before, output, after) = (totp.code_at(now()), run(&store, &["otp", "site"], ""), totp.code_at(now()));
+    assert!([format!("{}\n", before), format!("{}\n", after)].contains(&stdout(&output).to_string()), "{:?}", stdout(&output));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("valid for "));
+
+    let quiet = run(&store, &["--quiet", "otp", "site"], "");
+    assert!(stdout(&quiet).trim_end().chars().all(|c| c.is_ascii_digit()) && stdout(&quiet).len() == 7);
+    assert!(quiet.stderr.is_empty());
+
+    let json: serde_json::Value = serde_json::from_str(stdout(&run(&store, &["otp", "--json", "site"], ""))).unwrap();
+    assert!(json["code"].as_str().is_some_and(|c| c.len() == 6) && json["remaining"].as_u64().is_some_and(|r| (1..=30).contains(&r)));
+}
+
+#[test]
+fn status_messages_go_to_stderr() {
+    let dir = TempDir::new("stdout-status");
+    let store = store(&dir);
+    let add = run(&store, &["add", "other", "bob", "--password-stdin"], &format!("{}\n", PASSWORD));
+    assert_eq!(stdout(&add), "");
+    assert!(String::from_utf8_lossy(&add.stderr).contains("Saved."));
+    let delete = run(&store, &["--quiet", "delete", "other", "--force"], "");
+    assert!(delete.stdout.is_empty() && delete.stderr.is_empty(), "{}", String::from_utf8_lossy(&delete.stderr));
+    assert_eq!(stdout(&run(&store, &["names"], "")), "site\n");
+}
@@ -0,0 +1,47 @@
+//! Separate stores through `--store` and `REVAULTPASS_STORE`: each command
+//! reads and writes only the store it was pointed at.
+
+pub mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use common::TempDir;
+
+const MASTER_KEY: &str = "store path test master key";
+
+/// Runs `revaultpass <args>`, with its home in `dir` and
+/// `REVAULTPASS_STORE=<store>`, and returns its stdout.
+fn with_env(dir: &TempDir, store: &Path, args: &[&str], stdin: &str) -> String {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_revaultpass"));
+    cmd.args(args);
+    common::isolate(&mut cmd, &dir.0).env("REVAULTPASS_STORE", store).env("REVAULTPASS_PASSPHRASE", MASTER_KEY);
+    let output = common::output(&mut cmd, stdin);
+    assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn flag_and_variable_keep_stores_apart() {
+    let dir = TempDir::new("store-path");
+    let work = dir.join("work/nested/work.dat");
+    let personal = dir.join("personal/deeper/still/personal.dat");
+
+    // `init` creates the directories the store goes in.
+    common::init(&work, MASTER_KEY);
+    with_env(&dir, &personal, &["init", "--kdf-mem", "8192", "--kdf-iters", "1"], "");
+    assert!(work.is_file() && personal.is_file());
+
+    common::ok(&work, MASTER_KEY, &["add", "jira", "alice", "--password-stdin"], "work password\n");
+    with_env(&dir, &personal, &["add", "bank", "alice", "--password-stdin"], "personal password\n");
+    with_env(&dir, &personal, &["add", "mail", "alice", "--password-stdin"], "personal password\n");
+
+    let list = common::ok(&work, MASTER_KEY, &["list"], "");
+    assert!(list.contains("jira") && !list.contains("bank") && !list.contains("mail"), "{}", list);
+    let list = with_env(&dir, &personal, &["list"], "");
+    assert!(list.contains("bank") && list.contains("mail") && !list.contains("jira"), "{}", list);
+    assert_eq!(with_env(&dir, &personal, &["names"], ""), "bank\nmail\n");
+
+    // The flag wins over the variable.
+    assert_eq!(with_env(&dir, &personal, &["--store", work.to_str().unwrap(), "names"], ""), "jira\n");
+}
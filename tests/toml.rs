@@ -0,0 +1,95 @@
+//! `export --format toml` and `import --format toml` through the binary,
+//! which holds the TOML reader: an export imported into an empty store
+//! exports the same again.
+
+pub mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use common::TempDir;
+
+const MASTER_KEY: &str = "toml test master key";
+
+fn run(store: &Path, args: &[&str], stdin: &str) -> Output {
+    common::run(store, MASTER_KEY, args, stdin)
+}
+
+fn ok(store: &Path, args: &[&str], stdin: &str) -> String {
+    common::ok(store, MASTER_KEY, args, stdin)
+}
+
+fn init(dir: &TempDir, name: &str) -> PathBuf {
+    let store = dir.join(name);
+    common::init(&store, MASTER_KEY);
+    store
+}
+
+/// Logins and a note whose every string needs escaping somewhere: quotes,
+/// backslashes, newlines, tabs, TOML's own delimiters and non-ASCII text.
+const BATCH: &[&str] = &[
+    r#"{"name":"café/Zürich","user":"zoë@example.com","password":"p\"w'd\\x\ny","url":"https://例え.jp/login?a=1&b=\"2\"","notes":"line one\nline \"two\"\n\n\"\"\" and ''' inside\n\ttabbed\\","tags":["wörk","say \"hi\"","[x]"],"fields":{"api key":"k=\"v\"","größe":"42 ✓","a.b":"dotted key"},"totp":"JBSWY3DPEHPK3PXP"}"#,
+    r##"{"name":"日本語 \"quoted\" name","user":"","password":"# not a comment = [not a table]"}"##,
+    r#"{"name":"plain","user":"bob","password":"hunter2"}"#,
+];
+const NOTE_BODY: &str = "Wi-Fi: \"Café Ünïcødé\"\npassword = 'x' \\ \"\"\"\n\n  indented\r\nend without a newline";
+
+fn populate(store: &Path) {
+    ok(store, &["add", "--batch"], &BATCH.join("\n"));
+    ok(store, &["add-note", "recovery codes ★", "--tag", "nötes"], NOTE_BODY);
+    // One password change, for a history entry.
+    ok(store, &["edit", "plain", "--password", "new \"hunter3\""], "");
+}
+
+fn export(store: &Path, format: &str) -> String {
+    ok(store, &["export", "--format", format, "--stdout", "--force"], "")
+}
+
+#[test]
+fn toml_export_imports_into_an_empty_store_unchanged() {
+    let dir = TempDir::new("toml-round-trip");
+    let original = init(&dir, "original.dat");
+    populate(&original);
+    let toml = export(&original, "toml");
+    for text in ["[[entry]]", "[entry.fields]", "[[entry.history]]", "kind = \"note\""] {
+        assert!(toml.contains(text), "no {:?} in:\n{}", text, toml);
+    }
+
+    let file = dir.join("export.toml");
+    std::fs::write(&file, &toml).unwrap();
+    let copy = init(&dir, "copy.dat");
+    ok(&copy, &["import", "--format", "toml", file.to_str().unwrap()], "");
+    assert_eq!(export(&copy, "toml"), toml);
+    assert_eq!(export(&copy, "json"), export(&original, "json"));
+
+    let json: serde_json::Value = serde_json::from_str(&export(&copy, "json")).unwrap();
+    let entries = json.as_array().or_else(|| json["entries"].as_array()).unwrap();
+    let by_name = |name: &str| entries.iter().find(|e| e["name"] == name).unwrap_or_else(|| panic!("no {} in {}", name, json));
+    assert_eq!(by_name("café/Zürich")["password"], "p\"w'd\\x\ny");
+    assert_eq!(by_name("café/Zürich")["notes"], "line one\nline \"two\"\n\n\"\"\" and ''' inside\n\ttabbed\\");
+    assert_eq!(by_name("café/Zürich")["fields"]["größe"], "42 ✓");
+    assert_eq!(by_name("日本語 \"quoted\" name")["password"], "# not a comment = [not a table]");
+    assert_eq!(by_name("recovery codes ★")["body"], NOTE_BODY);
+    assert_eq!(ok(&copy, &["get", "--exact", "--field", "password", "plain"], ""), "new \"hunter3\"\n");
+}
+
+#[test]
+fn toml_import_names_the_entry_it_rejects() {
+    let dir = TempDir::new("toml-errors");
+    let store = init(&dir, "store.dat");
+    let file = dir.join("import.toml");
+    let cases = [
+        ("[[entry]]\nname = \"first\"\npassword = \"x\"\n\n[[entry]]\nname = \"zoë\"\nuser = \"u\"\n", "entry 2 ('zoë')", "missing password"),
+        ("[[entry]]\nname = \"first\"\npassword = \"x\"\ncolour = \"blue\"\n", "entry 1 ('first')", "colour"),
+        ("[[entry]]\nname = \"first\"\npassword = 7\n", "entry 1 ('first')", "password"),
+        ("[[entry]]\nname = \"open\npassword = \"x\"\n", "entry 1", "line 2"),
+    ];
+    for (text, entry, detail) in cases {
+        std::fs::write(&file, text).unwrap();
+        let output = run(&store, &["import", "--format", "toml", file.to_str().unwrap()], "");
+        let err = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success() && err.contains(entry) && err.contains(detail), "{:?}: {}", text, err);
+    }
+    // Nothing was imported, not even the valid first entry.
+    assert_eq!(ok(&store, &["names"], ""), "");
+}
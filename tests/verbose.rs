@@ -0,0 +1,70 @@
+//! Runs commands with `-vv` and checks that what they explain on stderr
+//! never includes a password, the master key or a note body.
+
+pub mod common;
+
+use std::path::Path;
+use std::process::Output;
+
+use common::TempDir;
+
+const MASTER_KEY: &str = "verbose-test-master-key";
+const PASSWORD: &str = "verbose-test-password";
+const IMPORTED: &str = "verbose-test-imported";
+const NOTE: &str = "verbose-test-note-body";
+const SECRETS: [&str; 4] = [MASTER_KEY, PASSWORD, IMPORTED, NOTE];
+
+/// Runs `revaultpass --store <store> -vv <args>`, with the master key in
+/// the environment unless `--passphrase-stdin` is among `args`.
+fn run(store: &Path, args: &[&str], stdin: &str) -> Output {
+    common::run(store, MASTER_KEY, &[&["-vv"][..], args].concat(), stdin)
+}
+
+#[test]
+fn verbose_output_never_shows_a_secret() {
+    let dir = TempDir::new("verbose");
+    let store = dir.join("store.dat");
+    let csv = dir.join("import.csv");
+    std::fs::write(&csv, format!("name,user,password\nsite,bob,{0}\n  other ,carol,{0}\n", IMPORTED)).unwrap();
+    let csv = csv.to_str().unwrap();
+    let password_line = format!("{}\n", PASSWORD);
+    let key_and_password = format!("{}\n{}\n", MASTER_KEY, PASSWORD);
+
+    let commands: Vec<(Vec<&str>, &str)> = vec![
+        (vec!["init", "--kdf-mem", "8192", "--kdf-iters", "1"], ""),
+        (vec!["add", "site", "alice", "--password-stdin"], &password_line),
+        (vec!["add", "second", "alice", "--passphrase-stdin", "--password-stdin"], &key_and_password),
+        (vec!["add-note", "note"], NOTE),
+        (vec!["get", "site"], ""),
+        (vec!["get", "--json", "site"], ""),
+        (vec!["get", "note"], ""),
+        (vec!["list", "--json", "--show-passwords"], ""),
+        (vec!["search", "site"], ""),
+        (vec!["import", csv, "--on-conflict", "rename"], ""),
+        (vec!["import", csv, "--on-conflict", "overwrite"], ""),
+        (vec!["history", "site"], ""),
+        (vec!["export", "--format", "json", "--stdout", "--force"], ""),
+        (vec!["audit"], ""),
+        (vec!["delete", "second", "--force"], ""),
+        (vec!["verify"], ""),
+    ];
+    let mut stderr = String::new();
+    let mut stdout = String::new();
+    for (args, stdin) in &commands {
+        let output = run(&store, args, stdin);
+        let err = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success() || args[0] == "audit", "{:?}: {}", args, err);
+        for secret in SECRETS {
+            assert!(!err.contains(secret), "{:?} printed {:?} on stderr:\n{}", args, secret, err);
+        }
+        stderr.push_str(&err);
+        stdout.push_str(&String::from_utf8_lossy(&output.stdout));
+    }
+    // The commands did explain themselves, and did print the secrets where asked to.
+    for line in ["info: store ", "info: format RVP2", "info: loaded ", "debug: master key from ", "already exists, imported as", "normalized to"] {
+        assert!(stderr.contains(line), "no {:?} in:\n{}", line, stderr);
+    }
+    for secret in [PASSWORD, IMPORTED, NOTE] {
+        assert!(stdout.contains(secret), "{}", secret);
+    }
+}